@@ -1,57 +1,72 @@
+use crate::config::{Config, ReasoningHistory};
+use crate::monitor::{ConsoleMonitor, Event, Monitor};
+use crate::secret::Secret;
+use crate::transcript::{Replay, Transcript};
 use anyhow::Result;
 use bytes::Bytes;
 use futures_util::stream::StreamExt;
-use reqwest;
 use ringbuffer::{AllocRingBuffer, RingBuffer};
-use serde_json;
+use serde::{Deserialize, Serialize};
 use std::io::BufRead;
-use tokio;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Instant;
 
 pub struct Agent {
     url: String,
-    token: String,
+    token: Secret,
     config: serde_json::Value,
     system_prompt: String,
     messages: AllocRingBuffer<serde_json::Value>,
-    async_runtime: tokio::runtime::Runtime,
+    async_runtime: Arc<tokio::runtime::Runtime>,
+    transcript: Option<Transcript>,
+    replay: Option<Replay>,
+    monitor: Arc<dyn Monitor>,
+    reasoning_history: ReasoningHistory,
 }
 
 impl Agent {
-    pub fn new(config: serde_json::Value) -> Self {
+    // when replaying a transcript, the provider is never contacted and needs no token
+    pub fn new(config: &Config) -> Result<Self> {
+        let replay = match &config.replay {
+            Some(path) => Some(Replay::load(path)?),
+            None => {
+                config.check_provider()?;
+                None
+            }
+        };
         let mut this = Self {
-            url: config["url"].as_str().unwrap().to_string(),
-            token: std::env::var("TOKEN").unwrap_or(config["token"].as_str().unwrap().to_string()),
+            url: config.url.clone(),
+            token: match replay {
+                Some(_) => Secret::default(),
+                None => config.get_token(|name| std::env::var(name).ok())?,
+            },
             config: serde_json::json!({
-                "model": config["model"].as_str().unwrap(),
+                "model": config.model,
                 "stream": true,
-                "max_tokens": config["max_tokens"].as_u64().unwrap_or(4096),
+                "max_tokens": config.max_tokens,
             }),
-            system_prompt: config["system_prompt"]
-                .as_str()
-                .unwrap_or_default()
-                .to_string(),
-            messages: AllocRingBuffer::new(config["max_history"].as_u64().unwrap_or(20) as usize),
-            async_runtime: tokio::runtime::Runtime::new().unwrap(),
+            system_prompt: String::new(),
+            messages: AllocRingBuffer::new(config.max_history),
+            async_runtime: Arc::new(tokio::runtime::Runtime::new()?),
+            transcript: None,
+            replay,
+            monitor: Arc::new(ConsoleMonitor::new(config.show_reasoning)),
+            reasoning_history: config.reasoning_history,
         };
-        if let Some(value) = config.get("temperature") {
-            this.config["temperature"] = value.clone();
+        if let Some(value) = config.temperature {
+            this.config["temperature"] = value.into();
         }
-        if let Some(value) = config.get("top_p") {
-            this.config["top_p"] = value.clone();
+        if let Some(value) = config.top_p {
+            this.config["top_p"] = value.into();
         }
-        if let Some(value) = config.get("top_k") {
-            this.config["top_k"] = value.clone();
+        if let Some(value) = config.top_k {
+            this.config["top_k"] = value.into();
         }
-        if let Some(value) = config.get("frequency_penalty") {
-            this.config["frequency_penalty"] = value.clone();
+        if let Some(value) = config.frequency_penalty {
+            this.config["frequency_penalty"] = value.into();
         }
-        this
-    }
-
-    pub fn new_with_config_file(config_file: &std::path::Path) -> Result<Self> {
-        Ok(Self::new(serde_json::from_reader(std::fs::File::open(
-            config_file,
-        )?)?))
+        Ok(this)
     }
 
     pub fn add_message(&mut self, role: &str, content: &str) {
@@ -61,11 +76,94 @@ impl Agent {
         }));
     }
 
+    // appends to the last message if it has the same role, as some providers reject two
+    // messages of the user in a row
+    pub fn append_message(&mut self, role: &str, content: &str) {
+        match self.messages.back_mut() {
+            Some(last) if last["role"] == role => {
+                let previous = last["content"].as_str().unwrap_or_default();
+                last["content"] = format!("{}{}", previous, content).into();
+            }
+            _ => self.add_message(role, content),
+        }
+    }
+
     pub fn clear_messages(&mut self) {
         self.messages.clear();
     }
 
-    pub fn post(&self) -> Result<String> {
+    pub fn add_reply(&mut self, reply: &Reply) {
+        let mut message = serde_json::json!({
+            "role": "assistant",
+            "content": reply.content
+        });
+        // the reasoning is always kept in the history (and thus the session), and only sent
+        // to the provider as the reasoning policy says
+        if !reply.reasoning.is_empty() {
+            message["reasoning"] = reply.reasoning.as_str().into();
+        }
+        self.messages.push(message);
+    }
+
+    // replaces the history with saved messages, as returned by `get_messages`
+    pub fn set_messages(&mut self, messages: &[serde_json::Value]) {
+        self.clear_messages();
+        for message in messages {
+            let role = message["role"].as_str().unwrap_or("user");
+            let content = message["content"].as_str().unwrap_or_default();
+            if role == "assistant" {
+                self.add_reply(&Reply {
+                    reasoning: message["reasoning"]
+                        .as_str()
+                        .unwrap_or_default()
+                        .to_string(),
+                    content: content.to_string(),
+                    usage: None,
+                });
+            } else {
+                self.add_message(role, content);
+            }
+        }
+    }
+
+    // keeps the first `len` messages of the history
+    pub fn truncate_messages(&mut self, len: usize) {
+        let mut messages = self.get_messages();
+        messages.truncate(len);
+        self.clear_messages();
+        for message in messages {
+            self.messages.push(message);
+        }
+    }
+
+    fn get_request_message(&self, message: &serde_json::Value) -> serde_json::Value {
+        let reasoning = match message
+            .get("reasoning")
+            .and_then(|reasoning| reasoning.as_str())
+        {
+            Some(reasoning) => reasoning,
+            None => return message.clone(),
+        };
+        let mut message = message.clone();
+        if let Some(message) = message.as_object_mut() {
+            message.remove("reasoning");
+        }
+        match self.reasoning_history {
+            ReasoningHistory::Strip => {}
+            ReasoningHistory::Inline => {
+                message["content"] = format!(
+                    "<think>\n{}\n</think>\n{}",
+                    reasoning,
+                    message["content"].as_str().unwrap_or_default()
+                )
+                .into();
+            }
+            ReasoningHistory::Field => message["reasoning_content"] = reasoning.into(),
+        }
+        message
+    }
+
+    pub fn post(&self) -> Result<Reply> {
         let mut messages = Vec::new();
         if !self.system_prompt.is_empty() {
             messages.push(serde_json::json!({
@@ -73,21 +171,113 @@ impl Agent {
                 "content": self.system_prompt
             }));
         }
-        messages.extend(self.messages.iter().cloned());
+        messages.extend(
+            self.messages
+                .iter()
+                .map(|message| self.get_request_message(message)),
+        );
         let mut data = self.config.clone();
         data["messages"] = serde_json::Value::Array(messages);
-        self.async_runtime
-            .block_on(post_request(&self.url, &self.token, &data))
+        self.record("request", serde_json::json!({ "payload": data }));
+        let start = Instant::now();
+        let reply = match &self.replay {
+            Some(replay) => replay.next().map(|response| {
+                let reply = Reply {
+                    reasoning: response["reasoning"]
+                        .as_str()
+                        .unwrap_or_default()
+                        .to_string(),
+                    content: response["content"].as_str().unwrap_or_default().to_string(),
+                    usage: serde_json::from_value(response["usage"].clone()).ok(),
+                };
+                self.monitor.notify(Event::Delta(&reply));
+                reply
+            }),
+            None => self
+                .async_runtime
+                .block_on(post_request(
+                    &self.url,
+                    self.token.expose(),
+                    &data,
+                    self.monitor.as_ref(),
+                ))
+                .map_err(|e| anyhow::anyhow!("{}", self.token.redact(&e.to_string()))),
+        };
+        let elapsed_ms = start.elapsed().as_millis() as u64;
+        match reply {
+            Ok(mut reply) => {
+                reply.split_inline_reasoning();
+                self.record(
+                    "response",
+                    serde_json::json!({
+                        "content": reply.content,
+                        "reasoning": reply.reasoning,
+                        "usage": reply.usage,
+                        "elapsed_ms": elapsed_ms,
+                    }),
+                );
+                Ok(reply)
+            }
+            Err(e) => {
+                self.record(
+                    "error",
+                    serde_json::json!({
+                        "error": e.to_string(),
+                        "elapsed_ms": elapsed_ms,
+                    }),
+                );
+                Err(e)
+            }
+        }
+    }
+
+    // starts or continues the transcript `{dir}/{id}.jsonl`, with the token redacted
+    pub fn open_transcript(&mut self, dir: &Path, id: &str) -> Result<()> {
+        let transcript = Transcript::open(dir, id, self.token.clone())?;
+        transcript.record(
+            "start",
+            serde_json::json!({
+                "url": self.url,
+                "params": self.config,
+                "replay": self.replay.as_ref().map(|replay| replay.get_path()),
+            }),
+        );
+        self.transcript = Some(transcript);
+        Ok(())
+    }
+
+    pub fn set_monitor(&mut self, monitor: Arc<dyn Monitor>) {
+        self.monitor = monitor;
+    }
+
+    pub fn get_transcript(&self) -> Option<&Transcript> {
+        self.transcript.as_ref()
+    }
+
+    pub fn record(&self, event: &str, data: serde_json::Value) {
+        if let Some(transcript) = &self.transcript {
+            transcript.record(event, data);
+        }
     }
 
     pub fn list_models(&self) -> Vec<String> {
-        self.async_runtime
-            .block_on(get_model_list(&self.url, &self.token))
-            .unwrap_or_default()
+        self.get_model_lister().list()
+    }
+
+    pub fn get_model_lister(&self) -> ModelLister {
+        ModelLister {
+            url: self.url.clone(),
+            token: self.token.clone(),
+            async_runtime: self.async_runtime.clone(),
+        }
     }
 
-    pub fn get_config(&self) -> &serde_json::Value {
-        &self.config
+    pub fn get_messages(&self) -> Vec<serde_json::Value> {
+        self.messages.iter().cloned().collect()
+    }
+
+    pub fn get_system_prompt(&self) -> &str {
+        &self.system_prompt
     }
 
     pub fn set_model(&mut self, model: &str) {
@@ -119,29 +309,76 @@ impl Agent {
     }
 }
 
-struct Reply {
-    reasoning_content: String,
-    content: String,
+impl std::fmt::Debug for Agent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Agent")
+            .field("url", &self.url)
+            .field("token", &self.token)
+            .field("config", &format_args!("{}", self.config))
+            .field("messages", &self.messages.len())
+            .finish()
+    }
+}
+
+// lists the models available at the endpoint of an agent, without borrowing the agent
+#[derive(Clone)]
+pub struct ModelLister {
+    url: String,
+    token: Secret,
+    async_runtime: Arc<tokio::runtime::Runtime>,
 }
 
-impl Default for Reply {
-    fn default() -> Self {
-        Self {
-            reasoning_content: String::new(),
-            content: String::new(),
+impl ModelLister {
+    pub fn list(&self) -> Vec<String> {
+        self.async_runtime
+            .block_on(get_model_list(&self.url, self.token.expose()))
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub struct Usage {
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Reply {
+    pub reasoning: String,
+    pub content: String,
+    pub usage: Option<Usage>,
+}
+
+impl Reply {
+    // some models write their reasoning into the content, enclosed in `<think>` tags
+    fn split_inline_reasoning(&mut self) {
+        let content = self.content.trim_start();
+        if let Some(rest) = content.strip_prefix("<think>")
+            && let Some((reasoning, content)) = rest.split_once("</think>")
+        {
+            self.reasoning.push_str(reasoning.trim());
+            self.content = content.trim_start().to_string();
         }
     }
 }
 
+// the body of a failed request usually explains the failure, e.g. an unknown model
+async fn check_status(response: reqwest::Response) -> Result<reqwest::Response> {
+    if let Err(e) = response.error_for_status_ref() {
+        let body = response.text().await.unwrap_or_default();
+        anyhow::bail!("{}: {}", e, body.trim());
+    }
+    Ok(response)
+}
+
 async fn get_model_list(url: &str, token: &str) -> Result<Vec<String>> {
     let url = url.replace("chat/completions", "models");
     let response = reqwest::Client::new()
         .get(url)
         .bearer_auth(token)
         .send()
-        .await?
-        .text()
         .await?;
+    let response = check_status(response).await?.text().await?;
     let json: serde_json::Value = serde_json::from_str(&response)?;
     let models = json["data"]
         .as_array()
@@ -153,64 +390,125 @@ async fn get_model_list(url: &str, token: &str) -> Result<Vec<String>> {
 }
 
 fn parse_response_line(line: Result<String>) -> Result<Reply> {
-    let json: serde_json::Value = serde_json::from_str(&line?.trim().trim_start_matches("data: "))?;
-    let reasoning_content = json["choices"][0]["delta"]["reasoning_content"]
-        .as_str()
-        .unwrap_or_default()
-        .to_string();
-    let content = json["choices"][0]["delta"]["content"]
+    let json: serde_json::Value = serde_json::from_str(line?.trim().trim_start_matches("data: "))?;
+    let delta = &json["choices"][0]["delta"];
+    // providers name the reasoning either `reasoning_content` or `reasoning`
+    let reasoning = delta["reasoning_content"]
         .as_str()
+        .or(delta["reasoning"].as_str())
         .unwrap_or_default()
         .to_string();
+    let content = delta["content"].as_str().unwrap_or_default().to_string();
+    // the usage is only sent with the last chunk, and not by every provider
+    let usage = serde_json::from_value(json["usage"].clone()).ok();
     Ok(Reply {
-        reasoning_content,
+        reasoning,
         content,
+        usage,
     })
 }
 
-fn parse_response(response: reqwest::Result<Bytes>, is_reasoning: &mut bool) -> String {
+// reports the delta of each chunk as it arrives, and collects the content and reasoning
+fn parse_response(response: reqwest::Result<Bytes>, monitor: &dyn Monitor, full: &mut Reply) {
     if let Ok(response) = response {
-        response
-            .lines()
-            .map(|line| {
-                let reply = parse_response_line(line.map_err(Into::into)).unwrap_or_default();
-                let mut delta = String::new();
-                if !reply.reasoning_content.is_empty() {
-                    if !*is_reasoning {
-                        *is_reasoning = true;
-                        delta.push_str("<think>\n");
-                    }
-                    delta.push_str(&reply.reasoning_content);
-                }
-                if !reply.content.is_empty() {
-                    if *is_reasoning {
-                        *is_reasoning = false;
-                        delta.push_str("</think>\n");
-                    }
-                    delta.push_str(&reply.content);
-                }
-                eprint!("{}", delta);
-                delta
-            })
-            .collect::<Vec<_>>()
-            .join("")
-    } else {
-        String::new()
+        for line in response.lines() {
+            let delta = parse_response_line(line.map_err(Into::into)).unwrap_or_default();
+            full.reasoning.push_str(&delta.reasoning);
+            full.content.push_str(&delta.content);
+            if delta.usage.is_some() {
+                full.usage = delta.usage;
+            }
+            monitor.notify(Event::Delta(&delta));
+        }
     }
 }
 
-async fn post_request(url: &str, token: &str, data: &serde_json::Value) -> Result<String> {
-    let mut is_reasoning = false;
-    let text = reqwest::Client::new()
+async fn post_request(
+    url: &str,
+    token: &str,
+    data: &serde_json::Value,
+    monitor: &dyn Monitor,
+) -> Result<Reply> {
+    let mut reply = Reply::default();
+    let response = reqwest::Client::new()
         .post(url)
         .bearer_auth(token)
         .json(&data)
         .send()
-        .await?
-        .bytes_stream()
-        .map(|bytes| parse_response(bytes, &mut is_reasoning))
-        .collect::<Vec<_>>()
-        .await
-        .join("");
-    Ok(text)
+        .await?;
+    let mut stream = check_status(response).await?.bytes_stream();
+    while let Some(bytes) = stream.next().await {
+        parse_response(bytes, monitor, &mut reply);
+    }
+    Ok(reply)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn messages_of_the_user_are_appended_to_the_pending_one() {
+        let config = Config {
+            url: "https://example.com/v1/chat/completions".to_string(),
+            token: Secret::new("token"),
+            model: "model".to_string(),
+            ..Config::default()
+        };
+        let mut agent = Agent::new(&config).unwrap();
+        agent.append_message("user", "document the files");
+        agent.add_message("assistant", "[[[[INVOKE]]]]");
+        agent.add_message("user", "tool output\n");
+        agent.append_message("user", "skip the backends\n");
+
+        let messages = agent.get_messages();
+        assert_eq!(messages.len(), 3);
+        assert_eq!(messages[0]["content"], "document the files");
+        assert_eq!(messages[2]["role"], "user");
+        assert_eq!(messages[2]["content"], "tool output\nskip the backends\n");
+    }
+
+    #[test]
+    fn failed_requests_report_the_status_and_the_body() {
+        use std::io::{Read, Write};
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!(
+            "http://{}/v1/chat/completions",
+            listener.local_addr().unwrap()
+        );
+        let server = std::thread::spawn(move || {
+            for _ in 0..2 {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut request = [0; 4096];
+                let _ = stream.read(&mut request).unwrap();
+                let body = "{\"error\": \"invalid token\"}";
+                write!(
+                    stream,
+                    "HTTP/1.1 401 Unauthorized\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                )
+                .unwrap();
+            }
+        });
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let list = runtime.block_on(get_model_list(&url, "token"));
+        let post = runtime.block_on(post_request(
+            &url,
+            "token",
+            &serde_json::json!({}),
+            &ConsoleMonitor::new(false),
+        ));
+        server.join().unwrap();
+
+        for error in [list.unwrap_err(), post.unwrap_err()] {
+            let error = error.to_string();
+            assert!(error.contains("401 Unauthorized"), "{}", error);
+            assert!(
+                error.ends_with("{\"error\": \"invalid token\"}"),
+                "{}",
+                error
+            );
+        }
+    }
 }
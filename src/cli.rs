@@ -0,0 +1,185 @@
+// this module defines the command line interface. the global options override the values
+// in the config file, and each of them can also be given as an environment variable.
+
+use crate::config::Config;
+use anyhow::Result;
+use clap::{Args, Parser, Subcommand};
+use std::path::PathBuf;
+
+#[derive(Parser)]
+#[command(
+    name = "laluisa",
+    version,
+    about = "LLM-Automated Documentation for Luisa"
+)]
+pub struct Cli {
+    /// The project config file, by default the first `laluisa.{toml,yaml,yml,json}` or
+    /// `config.json` found in the working directory.
+    #[arg(short, long, env = "LALUISA_CONFIG", global = true)]
+    pub config: Option<PathBuf>,
+
+    /// The named profile of provider and model settings to use, overriding `profile` in the config.
+    #[arg(short, long, env = "LALUISA_PROFILE", global = true)]
+    pub profile: Option<String>,
+
+    /// The chat completions endpoint, overriding `url` in the config.
+    #[arg(long, env = "LALUISA_URL", global = true)]
+    pub url: Option<String>,
+
+    /// The model to use, overriding `model` in the config.
+    #[arg(short, long, env = "LALUISA_MODEL", global = true)]
+    pub model: Option<String>,
+
+    /// The maximum number of tokens per reply, overriding `max_tokens` in the config.
+    #[arg(long, env = "LALUISA_MAX_TOKENS", global = true)]
+    pub max_tokens: Option<u64>,
+
+    /// The sampling temperature, overriding `temperature` in the config.
+    #[arg(long, env = "LALUISA_TEMPERATURE", global = true)]
+    pub temperature: Option<f64>,
+
+    /// The directory to save sessions in, overriding `session_dir` in the config.
+    #[arg(long, env = "LALUISA_SESSION_DIR", global = true)]
+    pub session_dir: Option<PathBuf>,
+
+    /// The directory to write transcripts in, overriding `transcript_dir` in the config.
+    #[arg(long, env = "LALUISA_TRANSCRIPT_DIR", global = true)]
+    pub transcript_dir: Option<PathBuf>,
+
+    /// Do not write a transcript of the run.
+    #[arg(long, global = true)]
+    pub no_transcript: bool,
+
+    /// Review each edit, and the tool calls that need approval, before it is carried out.
+    #[arg(long, global = true)]
+    pub supervise: bool,
+
+    /// Answer the requests with the responses recorded in a transcript instead of the provider.
+    #[arg(long, global = true)]
+    pub replay: Option<PathBuf>,
+
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Documents the files of a codebase.
+    Document(DocumentArgs),
+    /// Starts an interactive chat with the model.
+    Chat,
+    /// Lists the models available at the endpoint.
+    Models,
+    /// Reports how many declarations of a codebase are documented.
+    Coverage(CoverageArgs),
+    /// Continues a saved documentation run.
+    Resume {
+        /// The session id, or the path to a session file.
+        session: String,
+
+        /// Show the run in a terminal UI, where edits are reviewed before they are written.
+        #[arg(long)]
+        tui: bool,
+    },
+    /// Sends a message to the model of a running session, e.g. "skip the backends folder".
+    Tell {
+        /// The session id, or the path to a session file.
+        session: String,
+
+        /// The message, added to the conversation before the next request.
+        #[arg(required = true, num_args = 1..)]
+        message: Vec<String>,
+    },
+}
+
+#[derive(Args)]
+pub struct FileArgs {
+    /// The root directory of the codebase.
+    #[arg(default_value = ".")]
+    pub path: PathBuf,
+
+    /// Only consider files matching the glob pattern (relative to the codebase), may be repeated.
+    #[arg(long)]
+    pub include: Vec<String>,
+
+    /// Skip files matching the glob pattern (relative to the codebase), may be repeated.
+    #[arg(long)]
+    pub exclude: Vec<String>,
+}
+
+#[derive(Args)]
+pub struct DocumentArgs {
+    #[command(flatten)]
+    pub files: FileArgs,
+
+    /// Validate the edits without writing them to the files.
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// The number of conversations documenting disjoint sets of files in parallel.
+    #[arg(short = 'j', long, default_value_t = 1)]
+    pub concurrency: usize,
+
+    /// Show the run in a terminal UI, where edits are reviewed before they are written.
+    #[arg(long)]
+    pub tui: bool,
+}
+
+#[derive(Args)]
+pub struct CoverageArgs {
+    #[command(flatten)]
+    pub files: FileArgs,
+
+    /// List the undocumented declarations of each file.
+    #[arg(long)]
+    pub details: bool,
+}
+
+impl Cli {
+    // applies the layers above the config files: the selected profile, the environment and
+    // then the command line, and checks the result
+    pub fn configure(
+        &self,
+        mut config: Config,
+        getenv: impl Fn(&str) -> Option<String>,
+    ) -> Result<Config> {
+        if let Some(profile) = &self.profile {
+            config.profile = Some(profile.clone());
+        }
+        config.apply_profile()?;
+        config.apply_environment(getenv);
+        self.apply_overrides(&mut config);
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn apply_overrides(&self, config: &mut Config) {
+        if let Some(url) = &self.url {
+            config.url = url.clone();
+        }
+        if let Some(model) = &self.model {
+            config.model = model.clone();
+        }
+        if let Some(max_tokens) = self.max_tokens {
+            config.max_tokens = max_tokens;
+        }
+        if let Some(temperature) = self.temperature {
+            config.temperature = Some(temperature);
+        }
+        if let Some(session_dir) = &self.session_dir {
+            config.session_dir = session_dir.clone();
+        }
+        if let Some(transcript_dir) = &self.transcript_dir {
+            config.transcript_dir = transcript_dir.clone();
+        }
+        if self.no_transcript {
+            config.transcript = false;
+        }
+        if self.supervise {
+            config.supervise = true;
+        }
+        if let Some(replay) = &self.replay {
+            config.replay = Some(replay.clone());
+        }
+    }
+}
@@ -0,0 +1,651 @@
+// this module loads the configuration. the config is merged from several layers, each
+// overriding the previous one: the global file in `~/.config/laluisa`, the project file,
+// the environment and finally the command line. files may be written in TOML, YAML or JSON.
+
+use crate::prompt;
+use crate::secret::{self, Secret};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+pub const CONFIG_EXTENSIONS: &[&str] = &["toml", "yaml", "yml", "json"];
+// `config.json` is kept for configs written before the other formats were supported
+pub const PROJECT_CONFIG_FILES: &[&str] = &[
+    "laluisa.toml",
+    "laluisa.yaml",
+    "laluisa.yml",
+    "laluisa.json",
+    "config.json",
+];
+
+// provider and sampling settings that a named profile may override
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    pub url: Option<String>,
+    pub token: Option<Secret>,
+    pub token_env: Option<String>,
+    pub token_file: Option<PathBuf>,
+    pub token_command: Option<String>,
+    pub model: Option<String>,
+    pub max_tokens: Option<u64>,
+    pub temperature: Option<f64>,
+    pub top_p: Option<f64>,
+    pub top_k: Option<u64>,
+    pub frequency_penalty: Option<f64>,
+    pub show_reasoning: Option<bool>,
+    pub reasoning_history: Option<ReasoningHistory>,
+    pub input_price: Option<f64>,
+    pub output_price: Option<f64>,
+}
+
+// how the reasoning of earlier replies is sent back to the provider
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReasoningHistory {
+    // not at all, which most providers expect
+    #[default]
+    Strip,
+    // in `<think>` tags before the content
+    Inline,
+    // in the `reasoning_content` field of the message
+    Field,
+}
+
+// whether a tool call of a supervised run needs the approval of the user
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Approval {
+    Auto,
+    Ask,
+    // refused without asking, also in runs that are not supervised
+    Deny,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub url: String,
+    pub token: Secret,
+    // the name of the environment variable holding the token
+    pub token_env: Option<String>,
+    pub token_file: Option<PathBuf>,
+    // a credential helper printing the token
+    pub token_command: Option<String>,
+    pub model: String,
+    pub max_tokens: u64,
+    pub max_history: usize,
+    pub temperature: Option<f64>,
+    pub top_p: Option<f64>,
+    pub top_k: Option<u64>,
+    pub frequency_penalty: Option<f64>,
+    // whether to print the reasoning of the model while it streams
+    pub show_reasoning: bool,
+    pub reasoning_history: ReasoningHistory,
+    // the prices per million prompt and completion tokens, to show the cost of a run
+    pub input_price: Option<f64>,
+    pub output_price: Option<f64>,
+    pub system_prompt: Option<String>,
+    pub prompt_template: Option<PathBuf>,
+    pub prompt_variables: BTreeMap<String, serde_json::Value>,
+    pub styles: BTreeMap<String, String>,
+    pub default_style: Option<String>,
+    pub verify: BTreeMap<String, String>,
+    pub verify_timeout: u64,
+    // whether edits and tool calls are reviewed by the user before they are carried out
+    pub supervise: bool,
+    // the approval of each tool (`document` for the edits) in a supervised run, with `*` for
+    // the tools not listed. by default only the edits are reviewed.
+    pub approval: BTreeMap<String, Approval>,
+    // the seconds a call of each tool may take, with `*` for the tools not listed
+    pub tool_timeouts: BTreeMap<String, u64>,
+    pub session_dir: PathBuf,
+    // the input history of the chat
+    pub chat_history: PathBuf,
+    // whether to write the transcript of each run to `transcript_dir`
+    pub transcript: bool,
+    pub transcript_dir: PathBuf,
+    // a transcript whose responses answer the requests, set from the command line only
+    #[serde(skip)]
+    pub replay: Option<PathBuf>,
+    pub profile: Option<String>,
+    pub profiles: BTreeMap<String, Profile>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            url: String::new(),
+            token: Secret::default(),
+            token_env: None,
+            token_file: None,
+            token_command: None,
+            model: String::new(),
+            max_tokens: 4096,
+            max_history: 20,
+            temperature: None,
+            top_p: None,
+            top_k: None,
+            frequency_penalty: None,
+            show_reasoning: true,
+            reasoning_history: ReasoningHistory::default(),
+            input_price: None,
+            output_price: None,
+            system_prompt: None,
+            prompt_template: None,
+            prompt_variables: BTreeMap::new(),
+            styles: BTreeMap::new(),
+            default_style: None,
+            verify: BTreeMap::new(),
+            verify_timeout: 300,
+            supervise: false,
+            approval: BTreeMap::new(),
+            tool_timeouts: BTreeMap::new(),
+            session_dir: PathBuf::from(crate::session::DEFAULT_SESSION_DIR),
+            chat_history: PathBuf::from(crate::repl::DEFAULT_HISTORY_FILE),
+            transcript: true,
+            transcript_dir: PathBuf::from(crate::transcript::DEFAULT_TRANSCRIPT_DIR),
+            replay: None,
+            profile: None,
+            profiles: BTreeMap::new(),
+        }
+    }
+}
+
+// makes the paths of a table (the config or a profile) relative to the file that names them,
+// and returns whether it holds a plaintext token
+fn resolve_table(table: &mut serde_json::Value, dir: &Path) -> bool {
+    for key in ["prompt_template", "token_file"] {
+        if let Some(relative) = table.get(key).and_then(|relative| relative.as_str()) {
+            table[key] = dir.join(relative).to_string_lossy().as_ref().into();
+        }
+    }
+    table
+        .get("token")
+        .and_then(|token| token.as_str())
+        .is_some_and(|token| !token.is_empty())
+}
+
+fn parse_file(path: &Path) -> Result<serde_json::Value> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| anyhow::anyhow!("Cannot read config file {}: {}", path.display(), e))?;
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or_default()
+        .to_lowercase();
+    let value: serde_json::Value = match extension.as_str() {
+        "toml" => toml::from_str(&text).map_err(|e| anyhow::anyhow!("{}", e)),
+        "yaml" | "yml" => serde_yaml::from_str(&text).map_err(|e| anyhow::anyhow!("{}", e)),
+        _ => serde_json::from_str(&text).map_err(|e| anyhow::anyhow!("{}", e)),
+    }
+    .map_err(|e| anyhow::anyhow!("Invalid config file {}: {}", path.display(), e))?;
+    let mut value = match value {
+        serde_json::Value::Object(_) => value,
+        serde_json::Value::Null => serde_json::json!({}),
+        _ => anyhow::bail!(
+            "Invalid config file {}: expected a table of settings",
+            path.display()
+        ),
+    };
+    let dir = path.parent().unwrap_or(Path::new("."));
+    let mut holds_token = resolve_table(&mut value, dir);
+    if let Some(profiles) = value
+        .get_mut("profiles")
+        .and_then(|profiles| profiles.as_object_mut())
+    {
+        for profile in profiles.values_mut() {
+            holds_token |= resolve_table(profile, dir);
+        }
+    }
+    if holds_token {
+        secret::warn_if_world_readable(path, "config file");
+    }
+    Ok(value)
+}
+
+// tables are merged key by key, any other value of the layer replaces the previous one
+fn merge(base: &mut serde_json::Value, layer: serde_json::Value) {
+    match (base, layer) {
+        (serde_json::Value::Object(base), serde_json::Value::Object(layer)) => {
+            for (key, value) in layer {
+                match base.get_mut(&key) {
+                    Some(previous) => merge(previous, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, layer) => *base = layer,
+    }
+}
+
+fn find_file(dir: &Path, names: &[String]) -> Option<PathBuf> {
+    names
+        .iter()
+        .map(|name| dir.join(name))
+        .find(|path| path.is_file())
+}
+
+pub fn global_config_file() -> Option<PathBuf> {
+    let dir = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| {
+            std::env::var_os("HOME")
+                .or_else(|| std::env::var_os("USERPROFILE"))
+                .map(|home| PathBuf::from(home).join(".config"))
+        })?
+        .join("laluisa");
+    let names = CONFIG_EXTENSIONS
+        .iter()
+        .map(|extension| format!("config.{}", extension))
+        .collect::<Vec<_>>();
+    find_file(&dir, &names)
+}
+
+pub fn project_config_file() -> Option<PathBuf> {
+    let names = PROJECT_CONFIG_FILES
+        .iter()
+        .map(|name| name.to_string())
+        .collect::<Vec<_>>();
+    find_file(Path::new("."), &names)
+}
+
+impl Config {
+    // merges the global file and the given project file, or the first of `PROJECT_CONFIG_FILES`
+    // found in the working directory
+    pub fn load(project: Option<&Path>) -> Result<Self> {
+        let mut value = serde_json::json!({});
+        if let Some(path) = global_config_file() {
+            merge(&mut value, parse_file(&path)?);
+        }
+        match project {
+            Some(path) => merge(&mut value, parse_file(path)?),
+            None => {
+                if let Some(path) = project_config_file() {
+                    merge(&mut value, parse_file(&path)?);
+                }
+            }
+        }
+        Self::from_value(value)
+    }
+
+    pub fn from_value(value: serde_json::Value) -> Result<Self> {
+        serde_path_to_error::deserialize(value).map_err(|e| {
+            let path = e.path().to_string();
+            anyhow::anyhow!("Invalid config: `{}`: {}", path, e.into_inner())
+        })
+    }
+
+    // overrides the provider and sampling settings with those of the selected profile
+    pub fn apply_profile(&mut self) -> Result<()> {
+        let name = match &self.profile {
+            Some(name) => name,
+            None => return Ok(()),
+        };
+        let profile = self.profiles.get(name).cloned().ok_or(anyhow::anyhow!(
+            "Invalid config: `profile`: unknown profile `{}`, available profiles: {}",
+            name,
+            self.profiles
+                .keys()
+                .map(|name| name.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ))?;
+        if let Some(url) = profile.url {
+            self.url = url;
+        }
+        // the token sources of the profile replace all of those configured before
+        if profile.token.is_some()
+            || profile.token_env.is_some()
+            || profile.token_file.is_some()
+            || profile.token_command.is_some()
+        {
+            self.token = profile.token.unwrap_or_default();
+            self.token_env = profile.token_env;
+            self.token_file = profile.token_file;
+            self.token_command = profile.token_command;
+        }
+        if let Some(model) = profile.model {
+            self.model = model;
+        }
+        if let Some(max_tokens) = profile.max_tokens {
+            self.max_tokens = max_tokens;
+        }
+        self.temperature = profile.temperature.or(self.temperature);
+        self.top_p = profile.top_p.or(self.top_p);
+        self.top_k = profile.top_k.or(self.top_k);
+        self.frequency_penalty = profile.frequency_penalty.or(self.frequency_penalty);
+        if let Some(show_reasoning) = profile.show_reasoning {
+            self.show_reasoning = show_reasoning;
+        }
+        if let Some(reasoning_history) = profile.reasoning_history {
+            self.reasoning_history = reasoning_history;
+        }
+        self.input_price = profile.input_price.or(self.input_price);
+        self.output_price = profile.output_price.or(self.output_price);
+        Ok(())
+    }
+
+    // `getenv` looks up an environment variable, so that the tests need not change the
+    // environment of the process
+    pub fn apply_environment(&mut self, getenv: impl Fn(&str) -> Option<String>) {
+        if let Some(token) = getenv("TOKEN") {
+            self.token = Secret::new(&token);
+            self.token_env = None;
+            self.token_file = None;
+            self.token_command = None;
+        }
+    }
+
+    // checks the ranges of the settings, the provider settings are checked by `check_provider`
+    // as they are not needed by every command
+    pub fn validate(&self) -> Result<()> {
+        fn check_range(name: &str, value: Option<f64>, min: f64, max: f64) -> Result<()> {
+            match value {
+                Some(value) if !(min..=max).contains(&value) => anyhow::bail!(
+                    "Invalid config: `{}` must be between {} and {}, got {}",
+                    name,
+                    min,
+                    max,
+                    value
+                ),
+                _ => Ok(()),
+            }
+        }
+        // the profiles are checked first, as the selected one has been applied to the settings
+        for (name, profile) in &self.profiles {
+            let field = |key: &str| format!("profiles.{}.{}", name, key);
+            check_range(&field("temperature"), profile.temperature, 0.0, 2.0)?;
+            check_range(&field("top_p"), profile.top_p, 0.0, 1.0)?;
+            check_range(
+                &field("frequency_penalty"),
+                profile.frequency_penalty,
+                -2.0,
+                2.0,
+            )?;
+            if profile.max_tokens == Some(0) {
+                anyhow::bail!("Invalid config: `{}` must be positive", field("max_tokens"));
+            }
+        }
+        check_range("temperature", self.temperature, 0.0, 2.0)?;
+        check_range("top_p", self.top_p, 0.0, 1.0)?;
+        check_range("frequency_penalty", self.frequency_penalty, -2.0, 2.0)?;
+        if self.max_tokens == 0 {
+            anyhow::bail!("Invalid config: `max_tokens` must be positive");
+        }
+        if self.max_history == 0 {
+            anyhow::bail!("Invalid config: `max_history` must be positive");
+        }
+        if self.verify_timeout == 0 {
+            anyhow::bail!("Invalid config: `verify_timeout` must be positive");
+        }
+        if let Some(name) = prompt::RESERVED_VARIABLES
+            .iter()
+            .find(|name| self.prompt_variables.contains_key(**name))
+        {
+            anyhow::bail!(
+                "Invalid config: `prompt_variables.{}` is set by LaLuisa, please choose another name",
+                name
+            );
+        }
+        if let Some((tool, _)) = self
+            .tool_timeouts
+            .iter()
+            .find(|(_, timeout)| **timeout == 0)
+        {
+            anyhow::bail!("Invalid config: `tool_timeouts.{}` must be positive", tool);
+        }
+        Ok(())
+    }
+
+    pub fn check_provider(&self) -> Result<()> {
+        if !self.url.starts_with("http://") && !self.url.starts_with("https://") {
+            anyhow::bail!(
+                "Invalid config: `url` must be the http(s) URL of a chat completions endpoint, got `{}`",
+                self.url
+            );
+        }
+        if self.model.is_empty() {
+            anyhow::bail!("Invalid config: `model` is missing");
+        }
+        Ok(())
+    }
+
+    // the token is only looked up when an agent is created, so that credential helpers are
+    // not run for commands which never contact the provider. `getenv` looks up `token_env`.
+    pub fn get_token(&self, getenv: impl Fn(&str) -> Option<String>) -> Result<Secret> {
+        let token = if let Some(command) = &self.token_command {
+            secret::run_token_command(command)?
+        } else if let Some(path) = &self.token_file {
+            secret::read_token_file(path)?
+        } else if let Some(name) = &self.token_env {
+            Secret::new(&getenv(name).ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Invalid config: `token_env`: environment variable `{}` is not set",
+                    name
+                )
+            })?)
+        } else {
+            self.token.clone()
+        };
+        if token.is_empty() {
+            anyhow::bail!(
+                "Invalid config: `token` is missing, set one of `token`, `token_env`, `token_file` or `token_command`"
+            );
+        }
+        Ok(token)
+    }
+
+    pub fn get_approval(&self, tool: &str) -> Approval {
+        let approval = self
+            .approval
+            .get(tool)
+            .or_else(|| self.approval.get("*"))
+            .copied();
+        match approval {
+            Some(Approval::Deny) => Approval::Deny,
+            _ if !self.supervise => Approval::Auto,
+            Some(approval) => approval,
+            None if tool == "document" => Approval::Ask,
+            None => Approval::Auto,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::Cli;
+    use clap::Parser;
+    use serde_json::json;
+
+    fn config() -> Config {
+        Config::from_value(json!({
+            "url": "https://base.example/v1/chat/completions",
+            "token": "base-token",
+            "model": "base-model",
+            "temperature": 0.5,
+            "top_p": 0.9,
+            "profile": "fast",
+            "profiles": {
+                "fast": { "model": "fast-model", "temperature": 0.7, "token": "profile-token" },
+                "slow": { "model": "slow-model", "max_tokens": 8192 },
+            },
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn unknown_fields_are_rejected_with_their_path() {
+        let error = Config::from_value(json!({ "modle": "x" })).unwrap_err();
+        assert!(
+            error
+                .to_string()
+                .starts_with("Invalid config: `modle`: unknown field `modle`"),
+            "{}",
+            error
+        );
+
+        let error =
+            Config::from_value(json!({ "profiles": { "fast": { "modle": "x" } } })).unwrap_err();
+        assert!(
+            error
+                .to_string()
+                .starts_with("Invalid config: `profiles.fast.modle`: unknown field `modle`"),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn tables_are_merged_key_by_key() {
+        let mut value = json!({
+            "model": "global",
+            "styles": { "rs": "rustdoc", "py": "pydoc" },
+            "approval": { "*": "ask" },
+        });
+        merge(
+            &mut value,
+            json!({ "model": "project", "styles": { "py": "google" }, "approval": "none" }),
+        );
+        assert_eq!(
+            value,
+            json!({
+                "model": "project",
+                "styles": { "rs": "rustdoc", "py": "google" },
+                "approval": "none",
+            })
+        );
+    }
+
+    #[test]
+    fn profile_overrides_only_the_settings_it_names() {
+        let mut config = config();
+        config.apply_profile().unwrap();
+        assert_eq!(config.model, "fast-model");
+        assert_eq!(config.temperature, Some(0.7));
+        assert_eq!(config.top_p, Some(0.9));
+        assert_eq!(config.url, "https://base.example/v1/chat/completions");
+        assert_eq!(config.token.expose(), "profile-token");
+
+        config.profile = Some("missing".to_string());
+        let error = config.apply_profile().unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Invalid config: `profile`: unknown profile `missing`, available profiles: fast, slow"
+        );
+    }
+
+    #[test]
+    fn command_line_overrides_the_environment_and_the_profile() {
+        let getenv = |name: &str| (name == "TOKEN").then(|| "environment-token".to_string());
+        let cli = Cli::try_parse_from(["laluisa", "--model", "cli-model", "chat"]).unwrap();
+        let layered = cli.configure(config(), getenv);
+        let cli = Cli::try_parse_from([
+            "laluisa",
+            "--profile",
+            "slow",
+            "--max-tokens",
+            "100",
+            "chat",
+        ])
+        .unwrap();
+        let selected = cli.configure(config(), |_| None);
+
+        let config = layered.unwrap();
+        assert_eq!(config.model, "cli-model");
+        assert_eq!(config.temperature, Some(0.7));
+        assert_eq!(config.token.expose(), "environment-token");
+
+        let config = selected.unwrap();
+        assert_eq!(config.profile.as_deref(), Some("slow"));
+        assert_eq!(config.model, "slow-model");
+        assert_eq!(config.max_tokens, 100);
+        assert_eq!(config.temperature, Some(0.5));
+
+        let cli = Cli::try_parse_from(["laluisa", "--temperature", "3", "chat"]).unwrap();
+        let error = cli.configure(Config::default(), |_| None).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Invalid config: `temperature` must be between 0 and 2, got 3"
+        );
+    }
+
+    #[test]
+    fn reserved_prompt_variables_are_rejected() {
+        let config =
+            Config::from_value(json!({ "prompt_variables": { "tools": "none" } })).unwrap();
+        assert_eq!(
+            config.validate().unwrap_err().to_string(),
+            "Invalid config: `prompt_variables.tools` is set by LaLuisa, please choose another name"
+        );
+    }
+
+    #[test]
+    fn files_of_each_format_resolve_paths_next_to_them() {
+        let dir = std::env::temp_dir().join(format!("laluisa-config-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let toml = dir.join("laluisa.toml");
+        std::fs::write(
+            &toml,
+            "model = \"toml-model\"\ntoken_file = \"token\"\n\n[profiles.fast]\ntoken_file = \"fast-token\"\n",
+        )
+        .unwrap();
+        let yaml = dir.join("laluisa.yaml");
+        std::fs::write(&yaml, "model: yaml-model\r\nmax_tokens: 100\r\n").unwrap();
+        let empty = dir.join("empty.yml");
+        std::fs::write(&empty, "").unwrap();
+        let list = dir.join("list.json");
+        std::fs::write(&list, "[1, 2]").unwrap();
+
+        let mut value = parse_file(&toml).unwrap();
+        merge(&mut value, parse_file(&yaml).unwrap());
+        merge(&mut value, parse_file(&empty).unwrap());
+        let list_error = parse_file(&list).unwrap_err();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let config = Config::from_value(value).unwrap();
+        assert_eq!(config.model, "yaml-model");
+        assert_eq!(config.max_tokens, 100);
+        assert_eq!(config.token_file, Some(dir.join("token")));
+        assert_eq!(
+            config.profiles["fast"].token_file,
+            Some(dir.join("fast-token"))
+        );
+        assert!(
+            list_error
+                .to_string()
+                .ends_with("expected a table of settings"),
+            "{}",
+            list_error
+        );
+    }
+
+    #[test]
+    fn approvals_fall_back_to_the_wildcard_and_apply_only_when_supervised() {
+        let mut config = Config {
+            approval: BTreeMap::from([
+                ("shell".to_string(), Approval::Deny),
+                ("read".to_string(), Approval::Auto),
+                ("*".to_string(), Approval::Ask),
+            ]),
+            ..Config::default()
+        };
+        assert_eq!(config.get_approval("shell"), Approval::Deny);
+        assert_eq!(config.get_approval("read"), Approval::Auto);
+        assert_eq!(config.get_approval("search"), Approval::Auto);
+        assert_eq!(config.get_approval("document"), Approval::Auto);
+
+        config.supervise = true;
+        assert_eq!(config.get_approval("shell"), Approval::Deny);
+        assert_eq!(config.get_approval("read"), Approval::Auto);
+        assert_eq!(config.get_approval("search"), Approval::Ask);
+
+        config.approval.remove("*");
+        assert_eq!(config.get_approval("search"), Approval::Auto);
+        assert_eq!(config.get_approval("document"), Approval::Ask);
+    }
+}
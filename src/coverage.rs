@@ -0,0 +1,286 @@
+// this module computes the documentation coverage of a codebase, i.e. how many of the
+// functions and types declared at namespace, class or module level carry a doc comment.
+
+use crate::lexer::{Token, tokenize};
+use crate::style::{Language, StyleRegistry};
+use std::path::Path;
+
+pub struct Item {
+    pub line: usize,
+    pub text: String,
+    pub documented: bool,
+}
+
+pub struct FileCoverage {
+    pub path: String,
+    pub items: Vec<Item>,
+}
+
+impl FileCoverage {
+    pub fn documented(&self) -> usize {
+        self.items.iter().filter(|item| item.documented).count()
+    }
+}
+
+const CONTAINER_KEYWORDS: &[&str] = &[
+    "namespace",
+    "class",
+    "struct",
+    "union",
+    "enum",
+    "impl",
+    "trait",
+    "mod",
+    "interface",
+    "extern",
+];
+const TYPE_KEYWORDS: &[&str] = &[
+    "class",
+    "struct",
+    "union",
+    "enum",
+    "trait",
+    "mod",
+    "interface",
+];
+const IGNORED_HEADS: &[&str] = &[
+    "impl",
+    "namespace",
+    "extern",
+    "use",
+    "using",
+    "import",
+    "typedef",
+    "return",
+    "if",
+    "else",
+    "for",
+    "while",
+    "do",
+    "switch",
+    "static_assert",
+    "friend",
+];
+
+fn is_documented(lines: &[&str], line: usize) -> bool {
+    // skip attributes and decorators between the comment and the declaration
+    let previous = match lines[..line.saturating_sub(1)].iter().rposition(|line| {
+        let line = line.trim();
+        !line.starts_with("#[") && !line.starts_with('@')
+    }) {
+        Some(previous) => previous,
+        None => return false,
+    };
+    let text = lines[previous].trim();
+    if text.starts_with("///") || text.starts_with("//!") {
+        return true;
+    }
+    if !text.ends_with("*/") {
+        return false;
+    }
+    lines[..=previous]
+        .iter()
+        .rev()
+        .find(|line| line.contains("/*"))
+        .is_some_and(|line| {
+            let line = line.trim_start();
+            line.starts_with("/**") || line.starts_with("/*!")
+        })
+}
+
+fn make_item(lines: &[&str], statement: &[&Token], opens_block: bool) -> Option<Item> {
+    let mut texts = statement
+        .iter()
+        .map(|token| token.text.as_str())
+        .collect::<Vec<_>>();
+    // drop a leading rust visibility restriction such as `pub(crate)`
+    if texts.len() > 1 && texts[0] == "pub" && texts[1] == "(" {
+        let close = texts.iter().position(|text| *text == ")")?;
+        texts.drain(1..=close);
+    }
+    let head = texts
+        .iter()
+        .find(|text| !matches!(**text, "pub" | "export" | "unsafe" | "inline" | "default"))?;
+    if IGNORED_HEADS.contains(head) {
+        return None;
+    }
+    let is_type = texts
+        .iter()
+        .any(|text| TYPE_KEYWORDS.contains(text) && opens_block || *text == "type" && !opens_block);
+    let is_arrow = texts.windows(2).any(|pair| pair == ["=", ">"]);
+    let open = texts.iter().position(|text| *text == "(");
+    let assigned = texts.iter().position(|text| *text == "=");
+    let is_function = match (open, assigned) {
+        (Some(open), Some(assigned)) => open < assigned || is_arrow,
+        (Some(_), None) => true,
+        _ => false,
+    };
+    // invocations of macros such as `LUISA_STRUCT(...)` are not declarations
+    let is_macro = open == Some(1)
+        && texts[0]
+            .chars()
+            .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_');
+    if !(is_type || is_function) || is_macro {
+        return None;
+    }
+    let line = statement[0].line;
+    Some(Item {
+        line,
+        text: lines
+            .get(line - 1)
+            .map(|line| line.trim().to_string())
+            .unwrap_or_default(),
+        documented: is_documented(lines, line),
+    })
+}
+
+fn analyze_c_like(language: Language, source: &str) -> Vec<Item> {
+    let lines = source.lines().collect::<Vec<_>>();
+    // preprocessor directives and attribute lines never end with a semicolon
+    let tokens = tokenize(language, source)
+        .into_iter()
+        .filter(|token| {
+            !lines
+                .get(token.line - 1)
+                .is_some_and(|line| line.trim_start().starts_with('#'))
+        })
+        .collect::<Vec<_>>();
+    let mut items = Vec::new();
+    // whether each enclosing scope is a namespace, class or module, rather than a body
+    let mut scopes: Vec<bool> = Vec::new();
+    let mut statement: Vec<&Token> = Vec::new();
+    for token in &tokens {
+        let at_top = scopes.iter().all(|container| *container);
+        match token.text.as_str() {
+            "{" => {
+                let is_container = statement
+                    .iter()
+                    .any(|token| CONTAINER_KEYWORDS.contains(&token.text.as_str()))
+                    && !statement.iter().any(|token| token.text == "(");
+                if at_top && !statement.is_empty() {
+                    items.extend(make_item(&lines, &statement, true));
+                }
+                scopes.push(at_top && is_container);
+                statement.clear();
+            }
+            "}" => {
+                scopes.pop();
+                statement.clear();
+            }
+            ";" => {
+                if at_top && !statement.is_empty() {
+                    items.extend(make_item(&lines, &statement, false));
+                }
+                statement.clear();
+            }
+            ":" if statement.len() == 1
+                && matches!(
+                    statement[0].text.as_str(),
+                    "public" | "private" | "protected"
+                ) =>
+            {
+                statement.clear();
+            }
+            _ => statement.push(token),
+        }
+    }
+    items
+}
+
+fn analyze_python(source: &str) -> Vec<Item> {
+    let lines = source.lines().collect::<Vec<_>>();
+    let mut items = Vec::new();
+    for (i, line) in lines.iter().enumerate() {
+        let trimmed = line.trim_start();
+        if !["def ", "async def ", "class "]
+            .iter()
+            .any(|keyword| trimmed.starts_with(keyword))
+        {
+            continue;
+        }
+        let header_end = (i..lines.len())
+            .find(|j| {
+                lines[*j]
+                    .split('#')
+                    .next()
+                    .unwrap_or_default()
+                    .trim_end()
+                    .ends_with(':')
+            })
+            .unwrap_or(i);
+        let documented = lines[header_end + 1..]
+            .iter()
+            .map(|line| line.trim())
+            .find(|line| !line.is_empty())
+            .is_some_and(|line| {
+                let line = line.trim_start_matches(['r', 'u', 'R', 'U']);
+                line.starts_with('"') || line.starts_with('\'')
+            });
+        items.push(Item {
+            line: i + 1,
+            text: trimmed.trim_end().to_string(),
+            documented,
+        });
+    }
+    items
+}
+
+pub fn analyze_source(language: Language, source: &str) -> Vec<Item> {
+    match language {
+        Language::Python => analyze_python(source),
+        _ => analyze_c_like(language, source),
+    }
+}
+
+pub fn analyze_files(files: &[String], styles: &StyleRegistry) -> Vec<FileCoverage> {
+    files
+        .iter()
+        .filter_map(|path| {
+            let source = std::fs::read_to_string(path).ok()?;
+            let language = styles.for_path(Path::new(path)).language;
+            Some(FileCoverage {
+                path: path.clone(),
+                items: analyze_source(language, &source),
+            })
+        })
+        .collect()
+}
+
+fn percentage(documented: usize, total: usize) -> f64 {
+    if total == 0 {
+        100.0
+    } else {
+        documented as f64 * 100.0 / total as f64
+    }
+}
+
+pub fn print_report(report: &[FileCoverage], root: &Path, details: bool) {
+    let mut documented = 0;
+    let mut total = 0;
+    for file in report {
+        let path = Path::new(&file.path)
+            .strip_prefix(root)
+            .unwrap_or(Path::new(&file.path));
+        println!(
+            "{:>6}/{:<6} {:>6.1}%  {}",
+            file.documented(),
+            file.items.len(),
+            percentage(file.documented(), file.items.len()),
+            path.display()
+        );
+        if details {
+            for item in file.items.iter().filter(|item| !item.documented) {
+                println!("{:>22} line {}: {}", "", item.line, item.text);
+            }
+        }
+        documented += file.documented();
+        total += file.items.len();
+    }
+    println!(
+        "\nTotal: {}/{} declarations documented ({:.1}%) in {} files.",
+        documented,
+        total,
+        percentage(documented, total),
+        report.len()
+    );
+}
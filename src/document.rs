@@ -0,0 +1,355 @@
+// this module parses the [[[[DOCUMENT]]]] blocks emitted by the model into structured edits,
+// and applies them to the documented files.
+
+use crate::lexer::tokenize;
+use crate::style::Language;
+use anyhow::Result;
+use std::path::PathBuf;
+
+pub const DOCUMENT_TOKEN: &str = "[[[[DOCUMENT]]]]";
+const SEARCH_MARKER: &str = "<<<<<<< SEARCH";
+const REPLACE_MARKER: &str = "======= REPLACE";
+const FINISH_MARKER: &str = ">>>>>>> FINISH";
+
+#[derive(Debug, Clone)]
+pub struct Hunk {
+    // the line number of the first SEARCH line, if the model kept the `LINE xxxxx:` prefix
+    pub line: Option<usize>,
+    pub search: Vec<String>,
+    pub replace: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct DocumentEdit {
+    pub path: PathBuf,
+    pub hunks: Vec<Hunk>,
+}
+
+fn strip_line_number(line: &str) -> (Option<usize>, &str) {
+    if let Some(rest) = line.strip_prefix("LINE ")
+        && let Some((number, content)) = rest.split_once(": ").or_else(|| rest.split_once(':'))
+        && let Ok(number) = number.trim().parse::<usize>()
+    {
+        return (Some(number), content);
+    }
+    (None, line)
+}
+
+pub fn parse_document(input: &str) -> Result<DocumentEdit> {
+    let body = input
+        .rsplit_once(DOCUMENT_TOKEN)
+        .ok_or(anyhow::anyhow!(
+            "Invalid input. Cannot find document heading of {}",
+            DOCUMENT_TOKEN
+        ))?
+        .1;
+    let mut lines = body.lines().skip_while(|line| line.trim().is_empty());
+    let path = lines
+        .next()
+        .map(|line| line.trim().trim_matches('`').trim())
+        .filter(|line| !line.is_empty() && !line.starts_with(SEARCH_MARKER))
+        .ok_or(anyhow::anyhow!(
+            "Invalid input. Cannot find the file name after {}",
+            DOCUMENT_TOKEN
+        ))?;
+
+    enum State {
+        Outside,
+        Search,
+        Replace,
+    }
+    let mut state = State::Outside;
+    let mut hunks = Vec::new();
+    let mut current = Hunk {
+        line: None,
+        search: Vec::new(),
+        replace: Vec::new(),
+    };
+    for line in lines {
+        match state {
+            State::Outside => {
+                if line.trim_end() == SEARCH_MARKER {
+                    state = State::Search;
+                }
+            }
+            State::Search => {
+                if line.trim_end() == REPLACE_MARKER {
+                    state = State::Replace;
+                } else {
+                    let (number, content) = strip_line_number(line);
+                    if current.search.is_empty() {
+                        current.line = number;
+                    }
+                    current.search.push(content.to_string());
+                }
+            }
+            State::Replace => {
+                if line.trim_end() == FINISH_MARKER {
+                    if current.search.is_empty() {
+                        return Err(anyhow::anyhow!(
+                            r#"Invalid input. Empty "{}" section."#,
+                            SEARCH_MARKER
+                        ));
+                    }
+                    hunks.push(std::mem::replace(
+                        &mut current,
+                        Hunk {
+                            line: None,
+                            search: Vec::new(),
+                            replace: Vec::new(),
+                        },
+                    ));
+                    state = State::Outside;
+                } else {
+                    current.replace.push(line.to_string());
+                }
+            }
+        }
+    }
+    match state {
+        State::Outside if !hunks.is_empty() => Ok(DocumentEdit {
+            path: PathBuf::from(path),
+            hunks,
+        }),
+        State::Outside => Err(anyhow::anyhow!(
+            r#"Invalid input. Cannot find any "{}" section."#,
+            SEARCH_MARKER
+        )),
+        _ => Err(anyhow::anyhow!(
+            r#"Invalid input. Unterminated change, missing "{}"."#,
+            FINISH_MARKER
+        )),
+    }
+}
+
+impl std::fmt::Display for Hunk {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{}", SEARCH_MARKER)?;
+        for line in &self.search {
+            writeln!(f, "{}", line)?;
+        }
+        writeln!(f, "{}", REPLACE_MARKER)?;
+        for line in &self.replace {
+            writeln!(f, "{}", line)?;
+        }
+        write!(f, "{}", FINISH_MARKER)
+    }
+}
+
+fn matches_at(lines: &[&str], begin: usize, search: &[String], exact: bool) -> bool {
+    search.iter().enumerate().all(|(i, line)| {
+        if exact {
+            lines[begin + i].trim_end() == line.trim_end()
+        } else {
+            lines[begin + i].trim() == line.trim()
+        }
+    })
+}
+
+// finds the first line of the SEARCH section, preferring exact matches and then the
+// candidate closest to the line number given by the model
+fn locate_hunk(lines: &[&str], hunk: &Hunk) -> Result<usize> {
+    if hunk.search.len() <= lines.len() {
+        for exact in [true, false] {
+            let candidates = (0..=lines.len() - hunk.search.len())
+                .filter(|begin| matches_at(lines, *begin, &hunk.search, exact))
+                .collect::<Vec<_>>();
+            match (candidates.len(), hunk.line) {
+                (0, _) => continue,
+                (1, _) => return Ok(candidates[0]),
+                (_, Some(line)) => {
+                    return Ok(*candidates
+                        .iter()
+                        .min_by_key(|begin| begin.abs_diff(line.saturating_sub(1)))
+                        .unwrap());
+                }
+                (_, None) => {
+                    return Err(anyhow::anyhow!(
+                        "The following change matches {} places in the file. Please keep the `LINE xxxxx:` prefixes or include more lines to make it unique:\n{}",
+                        candidates.len(),
+                        hunk
+                    ));
+                }
+            }
+        }
+    }
+    Err(anyhow::anyhow!(
+        "Cannot find the lines of the following change in the file. Please read the file again and copy the lines exactly:\n{}",
+        hunk
+    ))
+}
+
+pub fn apply_hunks(source: &str, hunks: &[Hunk]) -> Result<String> {
+    let newline = if source.contains("\r\n") {
+        "\r\n"
+    } else {
+        "\n"
+    };
+    let lines = source.lines().collect::<Vec<_>>();
+    let mut ranges = hunks
+        .iter()
+        .map(|hunk| Ok((locate_hunk(&lines, hunk)?, hunk)))
+        .collect::<Result<Vec<_>>>()?;
+    ranges.sort_by_key(|(begin, _)| *begin);
+    for pair in ranges.windows(2) {
+        let (begin, hunk) = pair[0];
+        if begin + hunk.search.len() > pair[1].0 {
+            return Err(anyhow::anyhow!(
+                "The following changes overlap with each other, please merge them:\n{}\n{}",
+                hunk,
+                pair[1].1
+            ));
+        }
+    }
+    let mut result = Vec::new();
+    let mut next = 0;
+    for (begin, hunk) in ranges {
+        result.extend(lines[next..begin].iter().map(|line| line.to_string()));
+        result.extend(hunk.replace.iter().cloned());
+        next = begin + hunk.search.len();
+    }
+    result.extend(lines[next..].iter().map(|line| line.to_string()));
+    let mut result = result.join(newline);
+    if source.ends_with('\n') {
+        result.push_str(newline);
+    }
+    Ok(result)
+}
+
+// returns the first differing non-comment token as (line in the new text, old, new)
+fn find_code_change(
+    language: Language,
+    before: &str,
+    after: &str,
+) -> Option<(usize, String, String)> {
+    let before = tokenize(language, before);
+    let after = tokenize(language, after);
+    let length = before.len().max(after.len());
+    (0..length).find_map(|i| match (before.get(i), after.get(i)) {
+        (Some(old), Some(new)) if old.text == new.text => None,
+        (old, new) => Some((
+            new.or(after.last()).map(|token| token.line).unwrap_or(1),
+            old.map(|token| token.text.clone())
+                .unwrap_or("<end of file>".to_string()),
+            new.map(|token| token.text.clone())
+                .unwrap_or("<end of file>".to_string()),
+        )),
+    })
+}
+
+// makes sure that applying the edit only adds, changes or removes comments
+pub fn check_comments_only(
+    language: Language,
+    source: &str,
+    edit: &DocumentEdit,
+) -> Result<String> {
+    let updated = apply_hunks(source, &edit.hunks)?;
+    if find_code_change(language, source, &updated).is_none() {
+        return Ok(updated);
+    }
+    let mut report = Vec::new();
+    for hunk in &edit.hunks {
+        let single = apply_hunks(source, std::slice::from_ref(hunk))?;
+        if let Some((line, old, new)) = find_code_change(language, source, &single) {
+            report.push(format!(
+                "{}\nAt line {} of the edited file, the code `{}` became `{}`.",
+                hunk, line, old, new
+            ));
+        }
+    }
+    Err(anyhow::anyhow!(
+        "The changes to {} were rejected because they modify code, not only comments. Documentation must never change the code itself. Please fix the following changes and output them again:\n\n{}",
+        edit.path.display(),
+        report.join("\n\n")
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hunk(line: Option<usize>, search: &[&str], replace: &[&str]) -> Hunk {
+        Hunk {
+            line,
+            search: search.iter().map(|line| line.to_string()).collect(),
+            replace: replace.iter().map(|line| line.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn parses_hunks_with_line_numbers() {
+        let edit = parse_document(
+            "[[[[DOCUMENT]]]]\n`src/a.rs`\n<<<<<<< SEARCH\nLINE 00002: fn a() {}\n======= REPLACE\n/// A.\nfn a() {}\n>>>>>>> FINISH\n",
+        )
+        .unwrap();
+        assert_eq!(edit.path, PathBuf::from("src/a.rs"));
+        assert_eq!(edit.hunks.len(), 1);
+        assert_eq!(edit.hunks[0].line, Some(2));
+        assert_eq!(edit.hunks[0].search, ["fn a() {}"]);
+        assert_eq!(edit.hunks[0].replace, ["/// A.", "fn a() {}"]);
+    }
+
+    #[test]
+    fn duplicate_and_overlapping_hunks_are_rejected() {
+        let source = "fn a() {}\nfn b() {}\nfn c() {}\n";
+        let first = hunk(None, &["fn b() {}"], &["/// B.", "fn b() {}"]);
+        let error = apply_hunks(source, &[first.clone(), first]).unwrap_err();
+        assert!(error.to_string().contains("overlap"), "{}", error);
+
+        let error = apply_hunks(
+            source,
+            &[
+                hunk(
+                    None,
+                    &["fn a() {}", "fn b() {}"],
+                    &["fn a() {}", "fn b() {}"],
+                ),
+                hunk(
+                    None,
+                    &["fn b() {}", "fn c() {}"],
+                    &["fn b() {}", "fn c() {}"],
+                ),
+            ],
+        )
+        .unwrap_err();
+        assert!(error.to_string().contains("overlap"), "{}", error);
+
+        // adjacent hunks do not overlap, and are applied in the order of the file
+        let updated = apply_hunks(
+            source,
+            &[
+                hunk(None, &["fn b() {}"], &["/// B.", "fn b() {}"]),
+                hunk(None, &["fn a() {}"], &["/// A.", "fn a() {}"]),
+            ],
+        )
+        .unwrap();
+        assert_eq!(updated, "/// A.\nfn a() {}\n/// B.\nfn b() {}\nfn c() {}\n");
+    }
+
+    #[test]
+    fn repeated_lines_need_a_line_number() {
+        let source = "}\nfn a() {\n}\n";
+        let error = apply_hunks(source, &[hunk(None, &["}"], &["} // a"])]).unwrap_err();
+        assert!(error.to_string().contains("matches 2 places"), "{}", error);
+        let updated = apply_hunks(source, &[hunk(Some(3), &["}"], &["} // a"])]).unwrap();
+        assert_eq!(updated, "}\nfn a() {\n} // a\n");
+    }
+
+    #[test]
+    fn crlf_line_endings_are_kept() {
+        let source = "fn a() {}\r\nfn b() {}\r\n";
+        // the model writes LF, the file keeps CRLF
+        let updated = apply_hunks(
+            source,
+            &[hunk(None, &["fn b() {}"], &["/// B.", "fn b() {}"])],
+        )
+        .unwrap();
+        assert_eq!(updated, "fn a() {}\r\n/// B.\r\nfn b() {}\r\n");
+
+        let edit = DocumentEdit {
+            path: PathBuf::from("a.rs"),
+            hunks: vec![hunk(None, &["fn a() {}"], &["fn a() { 1 }"])],
+        };
+        assert!(check_comments_only(Language::Rust, source, &edit).is_err());
+    }
+}
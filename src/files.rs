@@ -0,0 +1,140 @@
+// this module selects the files of the codebase to be documented, honoring the include and
+// exclude glob patterns given on the command line.
+
+use crate::style::StyleRegistry;
+use anyhow::Result;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+pub const IGNORED_DIRECTORIES: &[&str] = &["target", "build", "node_modules", "__pycache__"];
+const MAX_LISTED_FILES: usize = 2000;
+
+pub struct FileFilter {
+    root: PathBuf,
+    include: Option<GlobSet>,
+    exclude: GlobSet,
+    // the only files matched if set, e.g. the share of a worker of a concurrent run
+    files: Option<HashSet<PathBuf>>,
+}
+
+fn build_glob_set(patterns: &[String]) -> Result<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        builder.add(
+            Glob::new(pattern)
+                .map_err(|e| anyhow::anyhow!("Invalid glob pattern `{}`: {}", pattern, e))?,
+        );
+    }
+    Ok(builder.build()?)
+}
+
+impl FileFilter {
+    // patterns are matched against paths relative to the root, an empty include list
+    // includes everything
+    pub fn new(root: &Path, include: &[String], exclude: &[String]) -> Result<Self> {
+        Ok(Self {
+            root: root.to_path_buf(),
+            include: if include.is_empty() {
+                None
+            } else {
+                Some(build_glob_set(include)?)
+            },
+            exclude: build_glob_set(exclude)?,
+            files: None,
+        })
+    }
+
+    pub fn restrict_to(&mut self, files: &[String]) {
+        self.files = Some(
+            files
+                .iter()
+                .map(|file| {
+                    Path::new(file)
+                        .canonicalize()
+                        .unwrap_or(PathBuf::from(file))
+                })
+                .collect(),
+        );
+    }
+
+    // a relative path, e.g. given by the model, is relative to the root rather than the working
+    // directory
+    pub fn resolve(&self, path: &Path) -> PathBuf {
+        if path.is_absolute() {
+            path.canonicalize().unwrap_or(path.to_path_buf())
+        } else {
+            self.root.join(path)
+        }
+    }
+
+    pub fn matches(&self, path: &Path) -> bool {
+        let path = self.resolve(path);
+        if let Some(files) = &self.files
+            && !files.contains(&path.canonicalize().unwrap_or(path.clone()))
+        {
+            return false;
+        }
+        let relative = match path.strip_prefix(&self.root) {
+            Ok(relative) => relative,
+            Err(_) => return false,
+        };
+        self.include
+            .as_ref()
+            .is_none_or(|include| include.is_match(relative))
+            && !self.exclude.is_match(relative)
+    }
+}
+
+// lists the files under the codebase that pass the filter and have a documentation style
+pub fn list_source_files(root: &Path, styles: &StyleRegistry, filter: &FileFilter) -> Vec<String> {
+    fn visit(path: &Path, styles: &StyleRegistry, filter: &FileFilter, files: &mut Vec<String>) {
+        let mut entries = match std::fs::read_dir(path) {
+            Ok(entries) => entries.filter_map(|entry| entry.ok()).collect::<Vec<_>>(),
+            Err(_) => return,
+        };
+        entries.sort_by_key(|entry| entry.file_name());
+        for entry in entries {
+            if files.len() >= MAX_LISTED_FILES {
+                return;
+            }
+            let name = entry.file_name().to_string_lossy().to_string();
+            let path = entry.path();
+            if path.is_dir() {
+                if !name.starts_with('.') && !IGNORED_DIRECTORIES.contains(&name.as_str()) {
+                    visit(&path, styles, filter, files);
+                }
+            } else if styles.has_style(&path) && filter.matches(&path) {
+                files.push(path.to_string_lossy().to_string());
+            }
+        }
+    }
+    let mut files = Vec::new();
+    visit(root, styles, filter, &mut files);
+    files
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn restricted_filter_only_matches_the_given_files() {
+        let root = std::env::temp_dir().join(format!("laluisa-files-{}", std::process::id()));
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        for file in ["src/a.rs", "src/b.rs"] {
+            std::fs::write(root.join(file), "").unwrap();
+        }
+        let root = root.canonicalize().unwrap();
+        let mut filter = FileFilter::new(&root, &["src/**".to_string()], &[]).unwrap();
+        assert!(filter.matches(Path::new("src/a.rs")));
+        assert!(filter.matches(&root.join("src/b.rs")));
+
+        filter.restrict_to(&[root.join("src/a.rs").to_string_lossy().to_string()]);
+        assert!(filter.matches(Path::new("src/a.rs")));
+        assert!(filter.matches(&root.join("src/../src/a.rs")));
+        assert!(!filter.matches(Path::new("src/b.rs")));
+        assert!(!filter.matches(&root.join("src/b.rs")));
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,473 @@
+// this module implements a small language-aware lexer. it splits source code into tokens
+// with comments (and python docstrings) stripped, so that documentation edits can be
+// checked to leave the code itself untouched.
+
+use crate::style::Language;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Token {
+    pub text: String,
+    pub line: usize,
+}
+
+struct RawToken {
+    text: String,
+    line: usize,
+    is_string: bool,
+    starts_line: bool,
+    depth: i32,
+}
+
+struct Lexer {
+    language: Language,
+    chars: Vec<char>,
+    position: usize,
+    line: usize,
+    last_line: usize,
+    depth: i32,
+    tokens: Vec<RawToken>,
+}
+
+impl Lexer {
+    fn new(language: Language, source: &str) -> Self {
+        Self {
+            language,
+            chars: source.chars().collect(),
+            position: 0,
+            line: 1,
+            last_line: 0,
+            depth: 0,
+            tokens: Vec::new(),
+        }
+    }
+
+    fn peek(&self, offset: usize) -> Option<char> {
+        self.chars.get(self.position + offset).copied()
+    }
+
+    fn starts_with(&self, text: &str) -> bool {
+        text.chars()
+            .enumerate()
+            .all(|(i, c)| self.peek(i) == Some(c))
+    }
+
+    fn advance(&mut self, count: usize) -> String {
+        let end = (self.position + count).min(self.chars.len());
+        let text = self.chars[self.position..end].iter().collect::<String>();
+        self.line += text.matches('\n').count();
+        self.position = end;
+        text
+    }
+
+    fn push(&mut self, line: usize, text: String, is_string: bool) {
+        self.tokens.push(RawToken {
+            text,
+            line,
+            is_string,
+            starts_line: line != self.last_line,
+            depth: self.depth,
+        });
+        self.last_line = self.line;
+    }
+
+    fn is_line_comment(&self) -> bool {
+        if self.language == Language::Python {
+            self.peek(0) == Some('#')
+        } else {
+            self.starts_with("//")
+        }
+    }
+
+    fn skip_line_comment(&mut self) {
+        while let Some(c) = self.peek(0) {
+            if c == '\n' {
+                break;
+            }
+            self.position += 1;
+        }
+    }
+
+    fn skip_block_comment(&mut self) {
+        // rust block comments nest, c-like ones end at the first `*/`
+        let nested = self.language == Language::Rust;
+        let mut depth = 0;
+        while self.position < self.chars.len() {
+            if self.starts_with("/*") {
+                depth += 1;
+                self.advance(2);
+            } else if self.starts_with("*/") {
+                depth -= 1;
+                self.advance(2);
+                if depth == 0 || !nested {
+                    return;
+                }
+            } else {
+                self.advance(1);
+            }
+        }
+    }
+
+    // consumes a literal whose opening delimiter has `open` chars, up to the `close` delimiter
+    fn quoted(&mut self, open: usize, close: &str, escapes: bool) -> String {
+        let mut length = open;
+        let close_chars = close.chars().collect::<Vec<_>>();
+        loop {
+            let position = self.position + length;
+            if position >= self.chars.len() {
+                break;
+            }
+            if escapes && self.chars[position] == '\\' {
+                length += 2;
+                continue;
+            }
+            if self.chars[position..].starts_with(&close_chars) {
+                length += close_chars.len();
+                break;
+            }
+            // unterminated single-line literals end at the line break
+            if (close == "\"" || close == "'") && self.chars[position] == '\n' {
+                break;
+            }
+            length += 1;
+        }
+        self.advance(length)
+    }
+
+    fn identifier_length(&self, offset: usize) -> usize {
+        let mut length = 0;
+        while let Some(c) = self.peek(offset + length) {
+            if c.is_alphanumeric()
+                || c == '_'
+                || (self.language == Language::TypeScript && c == '$')
+            {
+                length += 1;
+            } else {
+                break;
+            }
+        }
+        length
+    }
+
+    fn number_length(&self) -> usize {
+        let mut length = 0;
+        while let Some(c) = self.peek(length) {
+            let is_exponent_sign = (c == '+' || c == '-')
+                && length > 0
+                && matches!(self.peek(length - 1), Some('e' | 'E' | 'p' | 'P'))
+                && !matches!(self.peek(1), Some('x' | 'X'));
+            // c++14 digit separators
+            let is_separator = c == '\''
+                && self.language == Language::Cpp
+                && self
+                    .peek(length + 1)
+                    .is_some_and(|c| c.is_ascii_alphanumeric());
+            if c.is_alphanumeric() || c == '_' || c == '.' || is_exponent_sign || is_separator {
+                length += 1;
+            } else {
+                break;
+            }
+        }
+        length
+    }
+
+    // handles string prefixes such as `r#"`, `b"`, `R"(`, `u8"` and `f"`
+    fn try_prefixed_string(&mut self, identifier: &str) -> Option<String> {
+        let length = identifier.chars().count();
+        let next = self.peek(length)?;
+        match self.language {
+            Language::Rust if matches!(identifier, "r" | "br" | "cr") => {
+                let mut hashes = 0;
+                while self.peek(length + hashes) == Some('#') {
+                    hashes += 1;
+                }
+                if self.peek(length + hashes) != Some('"') {
+                    return None;
+                }
+                let terminator = format!("\"{}", "#".repeat(hashes));
+                let prefix = self.advance(length + hashes);
+                Some(prefix + &self.quoted(1, &terminator, false))
+            }
+            Language::Rust if matches!(identifier, "b" | "c") && (next == '"' || next == '\'') => {
+                self.advance(length);
+                Some(identifier.to_string() + &self.quoted(1, &next.to_string(), true))
+            }
+            Language::Cpp if identifier.ends_with('R') && next == '"' => {
+                if !matches!(identifier, "R" | "u8R" | "uR" | "UR" | "LR") {
+                    return None;
+                }
+                let delimiter = self.chars[self.position + length + 1..]
+                    .iter()
+                    .take_while(|c| **c != '(')
+                    .collect::<String>();
+                let terminator = format!("){}\"", delimiter);
+                let prefix = self.advance(length + 1 + delimiter.chars().count());
+                Some(prefix + &self.quoted(1, &terminator, false))
+            }
+            Language::Cpp if matches!(identifier, "u8" | "u" | "U" | "L") && next == '"' => {
+                self.advance(length);
+                Some(identifier.to_string() + &self.quoted(1, "\"", true))
+            }
+            Language::Python
+                if (next == '"' || next == '\'')
+                    && identifier.len() <= 2
+                    && identifier
+                        .chars()
+                        .all(|c| matches!(c.to_ascii_lowercase(), 'r' | 'b' | 'u' | 'f')) =>
+            {
+                let raw = identifier.to_ascii_lowercase().contains('r');
+                self.advance(length);
+                Some(identifier.to_string() + &self.python_string(!raw))
+            }
+            _ => None,
+        }
+    }
+
+    // in typescript a `/` starts a regex literal where an operand is expected, i.e. after an
+    // operator or a keyword, and a division after an operand
+    fn is_regex_start(&self) -> bool {
+        match self.tokens.last() {
+            None => true,
+            Some(token) if token.is_string => false,
+            Some(token) => {
+                let text = token.text.as_str();
+                if text.starts_with(|c: char| c.is_alphanumeric() || c == '_' || c == '$') {
+                    matches!(
+                        text,
+                        "return"
+                            | "typeof"
+                            | "instanceof"
+                            | "in"
+                            | "of"
+                            | "new"
+                            | "delete"
+                            | "void"
+                            | "throw"
+                            | "case"
+                            | "do"
+                            | "else"
+                            | "yield"
+                            | "await"
+                    )
+                } else {
+                    !matches!(text, ")" | "]" | "}")
+                }
+            }
+        }
+    }
+
+    // consumes a regex literal and its flags. a `/` in a character class does not close it,
+    // and an unterminated literal ends at the line break.
+    fn regex(&mut self) -> String {
+        let mut length = 1;
+        let mut in_class = false;
+        while let Some(c) = self.peek(length) {
+            match c {
+                '\\' => length += 1,
+                '[' => in_class = true,
+                ']' => in_class = false,
+                '/' if !in_class => {
+                    length += 1;
+                    break;
+                }
+                '\n' => break,
+                _ => {}
+            }
+            length += 1;
+        }
+        length += self.identifier_length(length);
+        self.advance(length)
+    }
+
+    fn python_string(&mut self, escapes: bool) -> String {
+        let quote = self.peek(0).unwrap_or('"');
+        let triple = quote.to_string().repeat(3);
+        if self.starts_with(&triple) {
+            self.quoted(3, &triple, escapes)
+        } else {
+            self.quoted(1, &quote.to_string(), escapes)
+        }
+    }
+
+    fn run(mut self) -> Vec<RawToken> {
+        while let Some(c) = self.peek(0) {
+            let line = self.line;
+            if c.is_whitespace() {
+                self.advance(1);
+            } else if self.is_line_comment() {
+                self.skip_line_comment();
+            } else if self.language != Language::Python && self.starts_with("/*") {
+                self.skip_block_comment();
+            } else if self.language == Language::TypeScript && c == '/' && self.is_regex_start() {
+                let text = self.regex();
+                self.push(line, text, true);
+            } else if c.is_ascii_digit() {
+                let length = self.number_length();
+                let text = self.advance(length);
+                self.push(line, text, false);
+            } else if c.is_alphabetic()
+                || c == '_'
+                || (self.language == Language::TypeScript && c == '$')
+            {
+                let length = self.identifier_length(0);
+                let identifier = self.chars[self.position..self.position + length]
+                    .iter()
+                    .collect::<String>();
+                if let Some(text) = self.try_prefixed_string(&identifier) {
+                    self.push(line, text, true);
+                } else {
+                    let text = self.advance(length);
+                    self.push(line, text, false);
+                }
+            } else if c == '"' || (c == '`' && self.language == Language::TypeScript) {
+                let text = if self.language == Language::Python {
+                    self.python_string(true)
+                } else {
+                    self.quoted(1, &c.to_string(), true)
+                };
+                self.push(line, text, true);
+            } else if c == '\'' {
+                let text = match self.language {
+                    Language::Python => self.python_string(true),
+                    // rust lifetimes and labels are not closed by a quote
+                    Language::Rust
+                        if self
+                            .peek(1)
+                            .is_some_and(|c| c.is_alphanumeric() || c == '_')
+                            && self.peek(2) != Some('\'') =>
+                    {
+                        self.advance(1 + self.identifier_length(1))
+                    }
+                    _ => self.quoted(1, "'", true),
+                };
+                self.push(line, text, true);
+            } else {
+                match c {
+                    '(' | '[' | '{' => self.depth += 1,
+                    ')' | ']' | '}' => self.depth -= 1,
+                    _ => {}
+                }
+                let text = self.advance(1);
+                self.push(line, text, false);
+            }
+        }
+        self.tokens
+    }
+}
+
+pub fn tokenize(language: Language, source: &str) -> Vec<Token> {
+    let tokens = Lexer::new(language, source).run();
+    if language != Language::Python {
+        return tokens
+            .into_iter()
+            .map(|token| Token {
+                text: token.text,
+                line: token.line,
+            })
+            .collect();
+    }
+    // in python, a string forming a statement on its own is a docstring (or a no-op), and
+    // the indentation of each line is significant
+    let lines = source.lines().collect::<Vec<_>>();
+    let mut result = Vec::new();
+    for (i, token) in tokens.iter().enumerate() {
+        let is_statement = token.starts_line
+            && token.depth == 0
+            && tokens
+                .get(i + 1)
+                .is_none_or(|next| next.starts_line && next.depth == 0);
+        if token.is_string && is_statement {
+            continue;
+        }
+        if token.starts_line && token.depth == 0 {
+            let indentation = lines
+                .get(token.line - 1)
+                .map(|line| line.len() - line.trim_start().len())
+                .unwrap_or_default();
+            result.push(Token {
+                text: format!("<indent {}>", indentation),
+                line: token.line,
+            });
+        }
+        result.push(Token {
+            text: token.text.clone(),
+            line: token.line,
+        });
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texts(language: Language, source: &str) -> Vec<String> {
+        tokenize(language, source)
+            .into_iter()
+            .map(|token| token.text)
+            .collect()
+    }
+
+    #[test]
+    fn comments_are_stripped() {
+        assert_eq!(
+            texts(Language::Rust, "/* a /* nested */ b */ x // y\n/// z\nfn"),
+            ["x", "fn"]
+        );
+        assert_eq!(texts(Language::Cpp, "/* a /* b */ c"), ["c"]);
+        assert_eq!(
+            texts(Language::Python, "x # y\nz"),
+            ["<indent 0>", "x", "<indent 0>", "z"]
+        );
+    }
+
+    #[test]
+    fn unterminated_literals_and_comments() {
+        // a string ends at the line break, a block comment at the end of the file
+        assert_eq!(
+            texts(Language::Rust, "let s = \"abc\nx;"),
+            ["let", "s", "=", "\"abc", "x", ";"]
+        );
+        assert_eq!(
+            texts(Language::Cpp, "int x; /* never closed\nint y;"),
+            ["int", "x", ";"]
+        );
+        assert_eq!(texts(Language::Rust, "r#\"raw"), ["r#\"raw"]);
+        let tokens = tokenize(Language::Python, "x = '''abc\ny");
+        assert_eq!(tokens.last().unwrap().text, "'''abc\ny");
+    }
+
+    #[test]
+    fn crlf_line_endings_keep_line_numbers() {
+        let tokens = tokenize(Language::Rust, "a\r\n// b\r\nc");
+        assert_eq!(
+            tokens
+                .iter()
+                .map(|token| (token.text.as_str(), token.line))
+                .collect::<Vec<_>>(),
+            [("a", 1), ("c", 3)]
+        );
+    }
+
+    #[test]
+    fn typescript_regex_literals() {
+        assert_eq!(
+            texts(Language::TypeScript, "const r = /a\\/*b/g; // c\nx"),
+            ["const", "r", "=", "/a\\/*b/g", ";", "x"]
+        );
+        assert_eq!(
+            texts(Language::TypeScript, "if (/[/*]/.test(s)) return /\\/\\//;"),
+            [
+                "if", "(", "/[/*]/", ".", "test", "(", "s", ")", ")", "return", "/\\/\\//", ";"
+            ]
+        );
+        // after an operand, a slash divides
+        assert_eq!(
+            texts(Language::TypeScript, "a / b / c"),
+            ["a", "/", "b", "/", "c"]
+        );
+        assert_eq!(
+            texts(Language::TypeScript, "f(x) / 2 /* d */"),
+            ["f", "(", "x", ")", "/", "2"]
+        );
+    }
+}
@@ -1,274 +1,347 @@
 mod agent;
+mod cli;
+mod config;
+mod coverage;
+mod document;
+mod files;
+mod lexer;
+mod monitor;
+mod pipeline;
+mod prompt;
+mod repl;
+mod secret;
+mod session;
+mod style;
 mod tools;
+mod transcript;
+#[cfg(feature = "tui")]
+mod tui;
+mod verify;
 
 use agent::Agent;
 use anyhow::Result;
-use std::io::{BufRead, Read};
-use std::path::Path;
+use clap::Parser;
+use cli::{Cli, Command, CoverageArgs, DocumentArgs};
+use config::{Approval, Config};
+use files::FileFilter;
+use monitor::{ConsoleMonitor, Event, Monitor};
+use pipeline::Pipeline;
+use prompt::PromptTemplate;
+use session::Session;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use style::StyleRegistry;
+use verify::Verifier;
 
-fn read_input() -> String {
-    eprint!("\nYou: ");
-    let mut input = String::new();
-    loop {
-        let _ = std::io::stdin().lock().read_line(&mut input);
-        if input.trim_start().starts_with(":") || input.ends_with("\n\n") {
-            break;
-        }
+// the profile is applied before the environment and the command line, so that both of them
+// can still override single settings of the profile
+fn load_config(cli: &Cli) -> Result<Config> {
+    cli.configure(Config::load(cli.config.as_deref())?, |name| {
+        std::env::var(name).ok()
+    })
+}
+
+fn create_agent(config: &Config, id: &str) -> Result<Agent> {
+    let mut agent = Agent::new(config)?;
+    if config.transcript {
+        agent.open_transcript(&config.transcript_dir, id)?;
     }
-    input.trim().to_string()
+    Ok(agent)
 }
 
-fn set_args(chat: &mut Agent, key: &str, value: &str) {
-    match key.trim() {
-        "model" => {
-            chat.set_model(value);
-        }
-        "temperature" | "temp" => {
-            if let Ok(temperature) = value.parse::<f64>() {
-                chat.set_temperature(temperature);
-            } else {
-                println!("Invalid temperature value: {}", value);
-            }
-        }
-        "top_p" => {
-            if let Ok(top_p) = value.parse::<f64>() {
-                chat.set_top_p(top_p);
-            } else {
-                println!("Invalid top_p value: {}", value);
-            }
-        }
-        "top_k" => {
-            if let Ok(top_k) = value.parse::<u64>() {
-                chat.set_top_k(top_k);
-            } else {
-                println!("Invalid top_k value: {}", value);
-            }
-        }
-        "frequency_penalty" => {
-            if let Ok(frequency_penalty) = value.parse::<f64>() {
-                chat.set_frequency_penalty(frequency_penalty);
-            } else {
-                println!("Invalid frequency_penalty value: {}", value);
-            }
-        }
-        "max_tokens" => {
-            if let Ok(max_tokens) = value.parse::<u64>() {
-                chat.set_max_tokens(max_tokens);
-            } else {
-                println!("Invalid max_tokens value: {}", value);
-            }
-        }
-        _ => {
-            println!("Unknown key: {}", key);
+fn create_monitor(config: &Config) -> Arc<dyn Monitor> {
+    Arc::new(ConsoleMonitor::new(config.show_reasoning))
+}
+
+// runs a session on a worker thread while the TUI shows its progress
+#[cfg(feature = "tui")]
+fn run_in_tui<F>(config: &Config, mut session: Session, run: F) -> Result<()>
+where
+    F: FnOnce(&Config, &mut Session, Arc<dyn Monitor>) -> Result<()> + Send + 'static,
+{
+    let styles = StyleRegistry::from_config(config)?;
+    let filter = FileFilter::new(&session.codebase, &session.include, &session.exclude)?;
+    let files = match &session.assigned {
+        Some(files) => files.clone(),
+        None => files::list_source_files(&session.codebase, &styles, &filter),
+    };
+    let title = format!("LaLuisa {}", session.codebase.display());
+    let id = session.id.clone();
+    let path = session.get_path().to_path_buf();
+    // the TUI is made for reviewing, its auto-approve key turns the reviews off
+    let mut owned = config.clone();
+    owned.supervise = true;
+    tui::run(
+        &title,
+        &files,
+        (config.input_price, config.output_price),
+        move |monitor| run(&owned, &mut session, monitor),
+    )?;
+    println!(
+        "Session {} is saved to {}, continue it with `resume {}`.",
+        id,
+        path.display(),
+        id
+    );
+    Ok(())
+}
+
+#[cfg(not(feature = "tui"))]
+fn run_in_tui<F>(_config: &Config, _session: Session, _run: F) -> Result<()>
+where
+    F: FnOnce(&Config, &mut Session, Arc<dyn Monitor>) -> Result<()> + Send + 'static,
+{
+    anyhow::bail!("LaLuisa was built without the `tui` feature")
+}
+
+fn create_toolset(config: &Config) -> Result<tools::ToolSet> {
+    let mut toolset = tools::ToolSet::new();
+    toolset.register_tools(tools::create_all_tools()?);
+    for (tool, timeout) in &config.tool_timeouts {
+        let timeout = std::time::Duration::from_secs(*timeout);
+        if tool == "*" {
+            toolset.set_default_timeout(timeout);
+        } else {
+            toolset.set_timeout(tool, timeout);
         }
     }
+    Ok(toolset)
 }
 
-fn invoke_tool(toolset: &tools::ToolSet, request: &str) -> Result<String> {
-    if let Ok((tool, args)) = parse_tool_invoke_json(request) {
-        let result = toolset.invoke(&tool, &args)?;
-        Ok(result)
-    } else if request.contains("[[[[DOCUMENT]]]]") {
-        Ok(r#"File has been documented. Please keep up the good work!
+fn get_approvals(config: &Config, toolset: &tools::ToolSet) -> BTreeMap<String, Approval> {
+    toolset
+        .get_tools()
+        .keys()
+        .map(String::as_str)
+        .chain(["document"])
+        .map(|tool| (tool.to_string(), config.get_approval(tool)))
+        .collect()
+}
 
-Or, if you think you have finished your task and want to stop,
-please just output a special token [[[[DONE]]]]."#
-            .to_string())
-    } else {
-        Err(anyhow::anyhow!(
+fn build_prompt(config: &Config, toolset: &tools::ToolSet, session: &Session) -> Result<String> {
+    let assigned = session.assigned.as_deref();
+    let styles = StyleRegistry::from_config(config)?;
+    let filter = FileFilter::new(&session.codebase, &session.include, &session.exclude)?;
+    let mut template = PromptTemplate::from_config(config)?;
+    template.set_variable("codebase", &session.codebase.to_string_lossy());
+    template.set_variable("tools", &serde_json::to_string_pretty(&toolset.get_help())?);
+    template.set_variable("style", &styles.get_prompt());
+    if template.uses("file_list") {
+        let files = match assigned {
+            Some(files) => files.to_vec(),
+            None => files::list_source_files(&session.codebase, &styles, &filter),
+        };
+        template.set_variable("file_list", &files.join("\n"));
+    }
+    let mut prompt = template.render();
+    if let Some(files) = assigned {
+        prompt.push_str(&format!(
             r#"
-I cannot find correct tool name or arguments in the request.
-Please check the format of the request and try again.
-
-If you think you have finished your task and want to stop,
-please just output a special token [[[[DONE]]]].
-
-Otherwise, please either continue to use the tool with [[[[INVOKE]]]] or
-write the documentation with [[[[DOCUMENT]]]] without outputting any [[[[DONE]]]].
-"#
-        ))
+Other assistants are documenting the rest of this codebase in parallel.
+You are responsible for the following files only, please leave all other files unchanged:
+{}
+"#,
+            files.join("\n")
+        ));
     }
+    Ok(prompt)
 }
 
-fn run_pipeline(agent: &mut Agent, toolset: &tools::ToolSet) {
-    loop {
-        println!("\n============= LLM RESPONSE =============");
-        if let Ok(response) = agent.post() {
-            if response.trim().ends_with("[[[[DONE]]]]") {
-                println!("\n\nDone.");
-                break;
-            }
-            agent.add_message("assistant", &response);
-            let invoke_result = format!(
-                "\n============= TOOL OUTPUT =============\n{}\n",
-                invoke_tool(toolset, &response).unwrap_or_else(|e| format!("Error: {}", e))
-            );
-            println!("{}", invoke_result);
-            agent.add_message("user", &invoke_result);
-        }
+fn run_session(
+    config: &Config,
+    agent: &mut Agent,
+    session: &mut Session,
+    monitor: Arc<dyn Monitor>,
+) -> Result<()> {
+    let mut toolset = create_toolset(config)?;
+    let styles = StyleRegistry::from_config(config)?;
+    let verifier = Verifier::from_config(config, &session.codebase)?;
+    let mut filter = FileFilter::new(&session.codebase, &session.include, &session.exclude)?;
+    if let Some(files) = &session.assigned {
+        filter.restrict_to(files);
     }
+    let mut pipeline = Pipeline::new(styles, verifier, filter);
+    pipeline.set_dry_run(session.dry_run);
+    pipeline.set_approvals(get_approvals(config, &toolset));
+    pipeline.set_monitor(monitor.clone());
+    toolset.add_observer(pipeline.get_tool_observer());
+    agent.set_monitor(monitor.clone());
+    monitor.notify(Event::Notice(&format!(
+        "Session {} is saved to {}",
+        session.id,
+        session.get_path().display()
+    )));
+    if let Some(transcript) = agent.get_transcript() {
+        monitor.notify(Event::Notice(&format!(
+            "Transcript is written to {}",
+            transcript.get_path().display()
+        )));
+    }
+    monitor.notify(Event::Notice(&format!(
+        "Send messages to the model with `tell {} <message>`",
+        session.id
+    )));
+    pipeline.run(agent, &toolset, session);
+    Ok(())
 }
 
-fn run(chat: &mut Agent) {
-    chat.clear_messages();
-    loop {
-        let input = read_input();
-        if input == ":exit" || input == ":quit" || input == ":q" {
-            break;
-        }
-        if input == ":list" || input == ":l" {
-            let models = chat.list_models();
-            for model in models {
-                println!("{}", model);
-            }
-            continue;
+fn start_session(config: &Config, session: &mut Session, monitor: Arc<dyn Monitor>) -> Result<()> {
+    let prompt = build_prompt(config, &create_toolset(config)?, session)?;
+    monitor.notify(Event::Prompt(&prompt));
+    let mut agent = create_agent(config, &session.id)?;
+    agent.set_system_prompt(&prompt);
+    run_session(config, &mut agent, session, monitor)
+}
+
+fn document(config: &Config, args: &DocumentArgs) -> Result<()> {
+    let codebase =
+        args.files.path.canonicalize().map_err(|e| {
+            anyhow::anyhow!("Invalid codebase {}: {}", args.files.path.display(), e)
+        })?;
+    let id = session::new_session_id();
+    let create_session = |id: &str| {
+        Session::new(
+            &config.session_dir,
+            id,
+            &codebase,
+            args.dry_run,
+            &args.files.include,
+            &args.files.exclude,
+        )
+    };
+    if args.tui {
+        if args.concurrency > 1 {
+            anyhow::bail!(
+                "The TUI shows a single conversation, it cannot be used with --concurrency"
+            );
         }
-        if input.starts_with(":set ") || input.starts_with(":s ") {
-            let args = input.split_whitespace().skip(1).collect::<Vec<&str>>();
-            if args.len() < 2 {
-                println!("Usage: :set <key> <value>");
-            } else {
-                set_args(chat, args[0], args[1]);
+        return run_in_tui(config, create_session(&id), start_session);
+    }
+    if args.concurrency <= 1 {
+        return start_session(config, &mut create_session(&id), create_monitor(config));
+    }
+    // split the files round-robin, so that each worker gets a similar share of each directory
+    let styles = StyleRegistry::from_config(config)?;
+    let filter = FileFilter::new(&codebase, &args.files.include, &args.files.exclude)?;
+    let mut assignments = vec![Vec::new(); args.concurrency];
+    for (i, file) in files::list_source_files(&codebase, &styles, &filter)
+        .into_iter()
+        .enumerate()
+    {
+        assignments[i % args.concurrency].push(file);
+    }
+    std::thread::scope(|scope| {
+        let workers = assignments
+            .iter()
+            .filter(|files| !files.is_empty())
+            .enumerate()
+            .map(|(i, files)| {
+                let mut session = create_session(&format!("{}-{}", id, i));
+                session.assigned = Some(files.clone());
+                scope.spawn(move || start_session(config, &mut session, create_monitor(config)))
+            })
+            .collect::<Vec<_>>();
+        for worker in workers {
+            match worker.join() {
+                Ok(Err(e)) => eprintln!("Worker failed: {}", e),
+                Err(_) => eprintln!("Worker panicked"),
+                _ => {}
             }
-            continue;
-        }
-        if input == ":clear" || input == ":c" {
-            chat.clear_messages();
-            continue;
         }
-        if input == ":show" {
-            println!("{:?}", chat.get_config());
-            continue;
-        }
-        if input.is_empty() {
-            continue;
-        }
-        chat.add_message("user", &input);
-        if let Ok(response) = chat.post() {
-            chat.add_message("assistant", &response);
-        }
-    }
+    });
+    Ok(())
+}
+
+fn continue_session(
+    config: &Config,
+    session: &mut Session,
+    monitor: Arc<dyn Monitor>,
+) -> Result<()> {
+    let mut agent = create_agent(config, &session.id)?;
+    session.restore(&mut agent);
+    monitor.notify(Event::Notice(&format!(
+        "Resuming session {} with {} messages",
+        session.id,
+        session.messages.len()
+    )));
+    run_session(config, &mut agent, session, monitor)
 }
 
-fn parse_tool_invoke_json(input: &str) -> Result<(String, serde_json::Value)> {
-    let input = input
-        .rsplit_once("[[[[INVOKE]]]]")
-        .ok_or(anyhow::anyhow!(
-            "Invalid input. Cannot find invoke heading of [[[[INVOKE]]]]"
-        ))?
-        .1
-        .split_once("```json")
-        .ok_or(anyhow::anyhow!(
-            r#"Invalid input. Cannot find invoke begin of "```json"."#
-        ))?
-        .1
-        .rsplit_once("```")
-        .ok_or(anyhow::anyhow!(
-            r#"Invalid input. Cannot find invoke end of "```"."#
-        ))?
-        .0;
-    let invoke: serde_json::Value = serde_json::from_str(input)?;
-    let invoke = invoke
-        .as_object()
-        .ok_or(anyhow::anyhow!("Input is not a valid JSON object"))?;
-    if invoke.len() != 1 {
-        Err(anyhow::anyhow!(
-            "Invalid input. You can only call one tool at a time."
-        ))
+fn resume(config: &Config, name: &str, tui: bool) -> Result<()> {
+    let mut session = Session::load(&config.session_dir, name)?;
+    if tui {
+        run_in_tui(config, session, continue_session)
     } else {
-        let (tool, args) = invoke.iter().next().ok_or(anyhow::anyhow!(
-            "Invalid input. JSON object must have exactly one key"
-        ))?;
-        Ok((tool.clone(), args.clone()))
+        continue_session(config, &mut session, create_monitor(config))
     }
 }
 
-fn main() {
-    let mut toolset = tools::ToolSet::new();
-    toolset.register_tool(tools::Tree::create());
-    toolset.register_tool(tools::Read::create());
-
-    let help = serde_json::to_string_pretty(&toolset.get_help()).unwrap();
-    let config_file = std::env::args().nth(1).unwrap_or("config.json".to_string());
-    let mut chat_agent = Agent::new_with_config_file(Path::new(&config_file)).unwrap();
-    let codebase = Path::new(&std::env::args().nth(2).unwrap_or(".".to_string()))
-        .canonicalize()
-        .unwrap();
-    let prompt = format!(
-        r#"
-I would like you to help write documentation for importance interface, headers, and source files in a codebase:
-{:?}
-
-There are some tools you can use. You can call them by providing the tool name and the arguments in JSON.
-Here are the tools:
-{}
-
-Now tell me what you want to do and I will return you the output of the tool.
-Please output a special heading and than JSON requests following the format (must be wrapped in triple backticks):
-
-[[[[INVOKE]]]]
-```json
-{{
-  "<tool-nane>": {{
-    "arg1": value1,
-    "arg2": value2
-  }}
-}}
-```
-
-For example, if you want to read the contents of a file, you can use the `read` tool like this:
-[[[[INVOKE]]]]
-```json
-{{
-  "read": {{
-    "path": "/path/to/file"
-  }}
-}}
-
-Please note that you can only call **one** tool **once** at a time. Otherwise errors will be returned.
-
-If you would like to document a file, please output a special [[[[DOCUMENT]]]] token and then the
-documentation in the target language's standard format (or doxygen format as a fallback):
-[[[[DOCUMENT]]]]
-<file name here with path on a new line>
-
-<<<<<<< SEARCH
-LINE 00001: mod xxx;
-LINE 00002: use yyyy;
-======= REPLACE
-/// Some description here
-/// Some description here
-mod xxx;
-use yyyy;
->>>>>>> FINISH
-
-<<<<<<< SEARCH
-LINE 00123: fn foo() {{
-======= REPLACE
-/// Some description here
-/// Some description here
-fn foo() {{
-...
->>>>>>> FINISH
-
-Note that you **MUST** output the changes in the diff-style, with "<<<<<<< SEARCH" and "======= REPLACE" and ">>>>>>> FINISH" signs!!!
-
-And you **MUST** keep the part between "<<<<<<< SEARCH" and "======= REPLACE" AS SMALL AS POSSIBLE!!! DO NOT INCLUDE THE WHOLE FILE CONTENTS!!!
-
-You may want to look at README (if any) and make a plan first, determine all the files to be processed.
-During each step, you should always be checking if you are on the right track.
-Do not leave any files unprocessed. Remember to check and update the plan carefully.
-
-Keep track of the files you have processed and the ones you have not.
-"#,
-        codebase.to_str(),
-        help
-    );
-    println!(
-        "\n============= PROMPT =============\n{}\n==================================\n",
-        prompt
+// the chat answers questions about the codebase in the working directory with the help of
+// the tools, but does not write documentation
+fn chat(config: &Config) -> Result<()> {
+    let codebase = std::env::current_dir()?;
+    let mut toolset = create_toolset(config)?;
+    let mut agent = create_agent(config, &session::new_session_id())?;
+    if let Some(transcript) = agent.get_transcript() {
+        println!(
+            "Transcript is written to {}",
+            transcript.get_path().display()
+        );
+    }
+    let mut template = PromptTemplate::new(prompt::CHAT_TEMPLATE);
+    template.set_variable("codebase", &codebase.to_string_lossy());
+    template.set_variable("tools", &serde_json::to_string_pretty(&toolset.get_help())?);
+    agent.set_system_prompt(&template.render());
+    let mut pipeline = Pipeline::new(
+        StyleRegistry::from_config(config)?,
+        Verifier::from_config(config, &codebase)?,
+        FileFilter::new(&codebase, &[], &[])?,
     );
-    chat_agent.set_system_prompt(&prompt);
+    let mut approvals = get_approvals(config, &toolset);
+    approvals.insert("document".to_string(), Approval::Deny);
+    pipeline.set_approvals(approvals);
+    toolset.add_observer(pipeline.get_tool_observer());
+    let monitor: Arc<dyn Monitor> = Arc::new(repl::ChatMonitor::new(config.show_reasoning));
+    pipeline.set_monitor(monitor.clone());
+    agent.set_monitor(monitor);
+    repl::run(&mut agent, &toolset, &pipeline, &config.chat_history)
+}
+
+fn report_coverage(config: &Config, args: &CoverageArgs) -> Result<()> {
+    let codebase =
+        args.files.path.canonicalize().map_err(|e| {
+            anyhow::anyhow!("Invalid codebase {}: {}", args.files.path.display(), e)
+        })?;
+    let styles = StyleRegistry::from_config(config)?;
+    let filter = FileFilter::new(&codebase, &args.files.include, &args.files.exclude)?;
+    let files = files::list_source_files(&codebase, &styles, &filter);
+    let report = coverage::analyze_files(&files, &styles);
+    coverage::print_report(&report, &codebase, args.details);
+    Ok(())
+}
 
-    run_pipeline(&mut chat_agent, &toolset);
+fn main() -> Result<()> {
+    let cli = Cli::parse();
+    let config = load_config(&cli)?;
+    match &cli.command {
+        Command::Document(args) => document(&config, args),
+        Command::Chat => chat(&config),
+        Command::Models => {
+            for model in Agent::new(&config)?.list_models() {
+                println!("{}", model);
+            }
+            Ok(())
+        }
+        Command::Coverage(args) => report_coverage(&config, args),
+        Command::Resume { session, tui } => resume(&config, session, *tui),
+        Command::Tell { session, message } => {
+            let session = Session::load(&config.session_dir, session)?;
+            session.send(&message.join(" "))?;
+            println!(
+                "The message will be sent to session {} before its next request.",
+                session.id
+            );
+            Ok(())
+        }
+    }
 }
@@ -0,0 +1,274 @@
+// this module defines how the progress of a run reaches the user. the pipeline reports
+// events to a monitor, which prints them (`ConsoleMonitor`) or shows them in the TUI. a
+// monitor may also hold the run before a request, review edits and add messages of the user.
+
+use crate::agent::Reply;
+use anyhow::Result;
+use std::io::{BufRead, Write};
+use std::path::Path;
+use std::sync::Mutex;
+use tokio_util::sync::CancellationToken;
+
+// the console only shows some of the events, the TUI uses all of them
+#[cfg_attr(not(feature = "tui"), allow(dead_code))]
+pub enum Event<'a> {
+    // the system prompt of a new session
+    Prompt(&'a str),
+    Request,
+    // a streamed part of the reply
+    Delta(&'a Reply),
+    Reply(&'a Reply),
+    ToolCall {
+        tool: &'a str,
+        args: &'a serde_json::Value,
+    },
+    ToolResult {
+        output: &'a str,
+        is_error: bool,
+    },
+    Document {
+        path: &'a Path,
+        outcome: DocumentOutcome,
+    },
+    RequestFailed(&'a str),
+    Notice(&'a str),
+    Done,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DocumentOutcome {
+    Written,
+    DryRun,
+    Rejected,
+    Failed,
+}
+
+pub enum Review {
+    Approve,
+    // the comment is passed on to the model
+    Reject(String),
+    // the user changed the file or the arguments of the tool call to the given text
+    Edit(String),
+}
+
+#[cfg_attr(not(feature = "tui"), allow(dead_code))]
+pub enum Control {
+    // continue with the given user messages added before the request
+    Continue(Vec<String>),
+    Stop,
+}
+
+pub trait Monitor: Send + Sync {
+    fn notify(&self, event: Event);
+
+    // called before each request, may block while the run is paused
+    fn before_request(&self) -> Control {
+        Control::Continue(Vec::new())
+    }
+
+    // called with the unified diff of each validated edit of a supervised run before it is
+    // written, `updated` is the whole edited file
+    fn review_edit(&self, _path: &Path, _diff: &str, _updated: &str) -> Review {
+        Review::Approve
+    }
+
+    // called before each tool call of a supervised run that needs approval
+    fn review_tool(&self, _tool: &str, _args: &serde_json::Value) -> Review {
+        Review::Approve
+    }
+
+    // cancelled when the user stops the run, the running tool calls then fail
+    fn get_cancellation(&self) -> CancellationToken {
+        CancellationToken::new()
+    }
+}
+
+// parallel runs ask on the same terminal, one at a time
+static PROMPT_LOCK: Mutex<()> = Mutex::new(());
+
+// reads a line from stdin, None at the end of the input
+fn read_answer(prompt: &str) -> Option<String> {
+    eprint!("{}", prompt);
+    let _ = std::io::stderr().flush();
+    let mut answer = String::new();
+    match std::io::stdin().lock().read_line(&mut answer) {
+        Ok(0) | Err(_) => None,
+        Ok(_) => Some(answer.trim().to_string()),
+    }
+}
+
+// asks the user on the terminal to approve, reject or edit. `text` is what the user edits,
+// with `extension` selecting the syntax highlighting of the editor.
+fn ask_review(heading: &str, body: &str, text: &str, extension: &str) -> Review {
+    let _lock = PROMPT_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    println!(
+        "\n============= REVIEW =============\n{}\n{}",
+        heading, body
+    );
+    loop {
+        let answer = match read_answer("[a]pprove, [r]eject with a comment, or [e]dit? ") {
+            Some(answer) => answer,
+            None => return Review::Reject("No user was available to approve.".to_string()),
+        };
+        match answer.as_str() {
+            "a" | "y" | "yes" => return Review::Approve,
+            "r" | "n" | "no" => {
+                let comment = read_answer("Comment for the model: ").unwrap_or_default();
+                return Review::Reject(if comment.is_empty() {
+                    "The user rejected it without a comment.".to_string()
+                } else {
+                    comment
+                });
+            }
+            "e" => match edit_text(text, extension) {
+                Ok(edited) => return Review::Edit(edited),
+                Err(e) => eprintln!("{}", e),
+            },
+            _ => {}
+        }
+    }
+}
+
+// prints the run to the terminal, with the reasoning of the model enclosed in `<think>` tags
+pub struct ConsoleMonitor {
+    show_reasoning: bool,
+    is_reasoning: Mutex<bool>,
+}
+
+impl ConsoleMonitor {
+    pub fn new(show_reasoning: bool) -> Self {
+        Self {
+            show_reasoning,
+            is_reasoning: Mutex::new(false),
+        }
+    }
+
+    fn print_delta(&self, delta: &Reply) {
+        let mut is_reasoning = self.is_reasoning.lock().unwrap_or_else(|e| e.into_inner());
+        let mut text = String::new();
+        if !delta.reasoning.is_empty() && self.show_reasoning {
+            if !*is_reasoning {
+                *is_reasoning = true;
+                text.push_str("<think>\n");
+            }
+            text.push_str(&delta.reasoning);
+        }
+        if !delta.content.is_empty() {
+            if *is_reasoning {
+                *is_reasoning = false;
+                text.push_str("</think>\n");
+            }
+            text.push_str(&delta.content);
+        }
+        eprint!("{}", text);
+    }
+}
+
+impl Monitor for ConsoleMonitor {
+    fn notify(&self, event: Event) {
+        match event {
+            Event::Prompt(prompt) => println!(
+                "\n============= PROMPT =============\n{}\n==================================\n",
+                prompt
+            ),
+            Event::Request => println!("\n============= LLM RESPONSE ============="),
+            Event::Delta(delta) => self.print_delta(delta),
+            Event::ToolResult { output, .. } => {
+                println!("\n============= TOOL OUTPUT =============\n{}\n", output)
+            }
+            Event::RequestFailed(error) => eprintln!("\nRequest failed: {}", error),
+            Event::Notice(notice) => eprintln!("{}", notice),
+            Event::Done => println!("\n\nDone."),
+            _ => {}
+        }
+    }
+
+    fn review_edit(&self, path: &Path, diff: &str, updated: &str) -> Review {
+        let extension = path
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_string())
+            .unwrap_or_default();
+        ask_review(
+            &format!("Edit of {}:", path.display()),
+            &colorize_diff(diff),
+            updated,
+            &extension,
+        )
+    }
+
+    fn review_tool(&self, tool: &str, args: &serde_json::Value) -> Review {
+        let args = serde_json::to_string_pretty(args).unwrap_or_default();
+        ask_review(&format!("Call of `{}`:", tool), &args, &args, "json")
+    }
+}
+
+pub fn unified_diff(path: &Path, source: &str, updated: &str) -> String {
+    let name = path.to_string_lossy();
+    similar::TextDiff::from_lines(source, updated)
+        .unified_diff()
+        .context_radius(3)
+        .header(&format!("a/{}", name), &format!("b/{}", name))
+        .to_string()
+}
+
+// colors the lines of a unified diff for the terminal
+pub fn colorize_diff(diff: &str) -> String {
+    diff.lines()
+        .map(|line| {
+            let color = if line.starts_with("+++") || line.starts_with("---") {
+                "1"
+            } else if line.starts_with('+') {
+                "32"
+            } else if line.starts_with('-') {
+                "31"
+            } else if line.starts_with("@@") {
+                "36"
+            } else {
+                return format!("{}\n", line);
+            };
+            format!("\x1b[{}m{}\x1b[0m\n", color, line)
+        })
+        .collect()
+}
+
+// lets the user change the text in `$VISUAL` or `$EDITOR`, and returns the saved text
+pub fn edit_text(text: &str, extension: &str) -> Result<String> {
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_else(|_| if cfg!(windows) { "notepad" } else { "vi" }.to_string());
+    let path = std::env::temp_dir().join(format!(
+        "laluisa-{}-{}.{}",
+        std::process::id(),
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos(),
+        if extension.is_empty() {
+            "txt"
+        } else {
+            extension
+        }
+    ));
+    std::fs::write(&path, text)?;
+    // the editor may be given with arguments, e.g. `code --wait`
+    let status = if cfg!(windows) {
+        std::process::Command::new("cmd")
+            .arg("/C")
+            .arg(format!("{} \"{}\"", editor, path.display()))
+            .status()
+    } else {
+        std::process::Command::new("sh")
+            .arg("-c")
+            .arg(format!("{} \"$1\"", editor))
+            .arg("sh")
+            .arg(&path)
+            .status()
+    };
+    let edited = std::fs::read_to_string(&path);
+    let _ = std::fs::remove_file(&path);
+    let status = status.map_err(|e| anyhow::anyhow!("Cannot run editor `{}`: {}", editor, e))?;
+    if !status.success() {
+        anyhow::bail!("Editor `{}` failed with {}", editor, status);
+    }
+    Ok(edited?)
+}
@@ -0,0 +1,750 @@
+// this module implements the documentation pipeline, which lets the model call tools and
+// write documentation until it reports that the task is done.
+
+use crate::agent::{Agent, Reply};
+use crate::config::Approval;
+use crate::document;
+use crate::files::FileFilter;
+use crate::monitor::{self, ConsoleMonitor, Control, DocumentOutcome, Event, Monitor, Review};
+use crate::session::Session;
+use crate::style::StyleRegistry;
+use crate::tools::{ToolEvent, ToolObserver, ToolSet};
+use crate::verify::Verifier;
+use anyhow::Result;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tool_protocol::{ContentPart, ErrorCode, ToolError, ToolOutput};
+
+// the run stops after this many requests failed in a row
+const MAX_FAILED_REQUESTS: usize = 3;
+
+// the tool calls in a single reply
+const MAX_TOOL_CALLS_PER_TURN: usize = 16;
+
+// the tool calls answering a single message of a chat
+const MAX_CHAT_TOOL_CALLS: usize = 20;
+
+// the heading of the messages carrying the output of a tool back to the model
+pub const TOOL_OUTPUT_HEADING: &str = "\n============= TOOL OUTPUT =============\n";
+
+// the heading of the messages sent by the user during a run, appended to the tool output
+// that is about to be sent
+pub const USER_MESSAGE_HEADING: &str = "\n============= MESSAGE FROM THE USER =============\n";
+
+// the result of each call of the tool set, recorded in the transcript with the output of the
+// tool request
+#[derive(Default)]
+struct CallLog {
+    calls: Mutex<Vec<serde_json::Value>>,
+}
+
+impl CallLog {
+    fn take(&self) -> Vec<serde_json::Value> {
+        std::mem::take(&mut *self.calls.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+}
+
+impl ToolObserver for CallLog {
+    fn notify(&self, event: &ToolEvent) {
+        if let ToolEvent::Result {
+            tool,
+            output,
+            elapsed,
+            ..
+        } = event
+        {
+            self.calls
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .push(serde_json::json!({
+                    "tool": tool,
+                    "error_code": output.error_code,
+                    "elapsed_ms": elapsed.as_millis() as u64,
+                }));
+        }
+    }
+}
+
+pub struct Pipeline {
+    styles: StyleRegistry,
+    verifier: Verifier,
+    filter: FileFilter,
+    dry_run: bool,
+    // the approval of each tool, tools not listed are called without review
+    approvals: BTreeMap<String, Approval>,
+    monitor: Arc<dyn Monitor>,
+    call_log: Arc<CallLog>,
+}
+
+impl Pipeline {
+    pub fn new(styles: StyleRegistry, verifier: Verifier, filter: FileFilter) -> Self {
+        Self {
+            styles,
+            verifier,
+            filter,
+            dry_run: false,
+            approvals: BTreeMap::new(),
+            monitor: Arc::new(ConsoleMonitor::new(true)),
+            call_log: Arc::new(CallLog::default()),
+        }
+    }
+
+    pub fn set_dry_run(&mut self, dry_run: bool) {
+        self.dry_run = dry_run;
+    }
+
+    pub fn set_monitor(&mut self, monitor: Arc<dyn Monitor>) {
+        self.monitor = monitor;
+    }
+
+    pub fn set_approvals(&mut self, approvals: BTreeMap<String, Approval>) {
+        self.approvals = approvals;
+    }
+
+    // the observer to add to the tool set, so that the transcript has the time of each call
+    pub fn get_tool_observer(&self) -> Arc<dyn ToolObserver> {
+        self.call_log.clone()
+    }
+
+    fn get_approval(&self, tool: &str) -> Approval {
+        self.approvals.get(tool).copied().unwrap_or(Approval::Auto)
+    }
+
+    // asks the monitor for a review if the tool needs approval, and records the decision
+    fn review(
+        &self,
+        agent: &Agent,
+        tool: &str,
+        target: serde_json::Value,
+        review: impl FnOnce() -> Review,
+    ) -> Result<Review> {
+        let review = match self.get_approval(tool) {
+            Approval::Auto => return Ok(Review::Approve),
+            Approval::Deny => {
+                return Err(ToolError::new(
+                    ErrorCode::Denied,
+                    format!(
+                        "The tool `{}` is not allowed in this run, please do without it.",
+                        tool
+                    ),
+                )
+                .into());
+            }
+            Approval::Ask => review(),
+        };
+        let (decision, comment) = match &review {
+            Review::Approve => ("approve", None),
+            Review::Reject(comment) => ("reject", Some(comment.as_str())),
+            Review::Edit(_) => ("edit", None),
+        };
+        agent.record(
+            "review",
+            serde_json::json!({
+                "tool": tool,
+                "target": target,
+                "decision": decision,
+                "comment": comment,
+            }),
+        );
+        Ok(review)
+    }
+
+    fn apply_document(&self, agent: &Agent, request: &str) -> Result<(DocumentOutcome, String)> {
+        let edit = document::parse_document(request)?;
+        // the file is read, written and verified at this path, whatever the working directory
+        let path = self.filter.resolve(&edit.path);
+        if !self.filter.matches(&path) {
+            return Err(anyhow::anyhow!(
+                "{} is not part of the files to document, please leave it unchanged.",
+                path.display()
+            ));
+        }
+        let style = self.styles.for_path(&path);
+        let source = std::fs::read_to_string(&path)
+            .map_err(|e| anyhow::anyhow!("Cannot read {}: {}", path.display(), e))?;
+        let updated = document::check_comments_only(style.language, &source, &edit)?;
+        let violations = edit
+            .hunks
+            .iter()
+            .flat_map(|hunk| style.validate(&hunk.replace.join("\n")))
+            .collect::<Vec<_>>();
+        if !violations.is_empty() {
+            return Err(anyhow::anyhow!(
+                r#"
+The documentation of {} does not follow the `{}` style:
+- {}
+
+Style rules for this file: {}
+
+Please fix the documentation and output the changes again with [[[[DOCUMENT]]]].
+"#,
+                path.display(),
+                style.name,
+                violations.join("\n- "),
+                style.get_rules()
+            ));
+        }
+        let diff = monitor::unified_diff(&path, &source, &updated);
+        let review = self.review(agent, "document", serde_json::json!(path), || {
+            self.monitor.review_edit(&path, &diff, &updated)
+        })?;
+        // the edits of the user are trusted, they are neither limited to comments nor styled
+        let (updated, feedback) = match review {
+            Review::Approve => (updated, String::new()),
+            Review::Reject(comment) => {
+                return Ok((
+                    DocumentOutcome::Rejected,
+                    format!(
+                        r#"The user rejected your changes to {}:
+{}
+
+Please revise the documentation and output the changes again with [[[[DOCUMENT]]]],
+or continue with another file."#,
+                        path.display(),
+                        comment
+                    ),
+                ));
+            }
+            Review::Edit(edited) => {
+                let feedback = format!(
+                    "\nThe user edited your changes before they were written:\n{}",
+                    monitor::unified_diff(&path, &updated, &edited)
+                );
+                (edited, feedback)
+            }
+        };
+        if self.dry_run {
+            return Ok((
+                DocumentOutcome::DryRun,
+                format!(
+                    "{}\n(Dry run: the changes were validated but not written.)",
+                    feedback
+                ),
+            ));
+        }
+        std::fs::write(&path, &updated)?;
+        let verification = self.verify_document(&path, &source, &updated)?;
+        Ok((
+            DocumentOutcome::Written,
+            format!("{}{}", feedback, verification),
+        ))
+    }
+
+    // runs the verify command on the edited file and reverts the edit if it broke the check
+    fn verify_document(
+        &self,
+        path: &std::path::Path,
+        source: &str,
+        updated: &str,
+    ) -> Result<String> {
+        let verification = match self.verifier.verify(path) {
+            Ok(Some(verification)) => verification,
+            Ok(None) => return Ok(String::new()),
+            Err(e) => {
+                std::fs::write(path, source)?;
+                return Err(e);
+            }
+        };
+        if verification.success {
+            return Ok(format!(
+                "\nThe verify command `{}` passed.",
+                verification.command
+            ));
+        }
+        std::fs::write(path, source)?;
+        // the check may have been failing before the edit, which is not the model's fault
+        if let Ok(Some(baseline)) = self.verifier.verify(path)
+            && !baseline.success
+        {
+            std::fs::write(path, updated)?;
+            return Ok(format!(
+                "\nNote: the verify command `{}` fails with and without your changes, so they were kept.",
+                verification.command
+            ));
+        }
+        Err(anyhow::anyhow!(
+            r#"
+The changes to {} were reverted because the verify command `{}` failed:
+{}
+
+Please fix the documentation and output the changes again with [[[[DOCUMENT]]]].
+"#,
+            path.display(),
+            verification.command,
+            verification.output
+        ))
+    }
+
+    // reviews a tool call, and returns the arguments to call the tool with and a note for the
+    // model if the user changed them
+    fn prepare_call(
+        &self,
+        agent: &Agent,
+        tool: &str,
+        args: &serde_json::Value,
+    ) -> Result<(serde_json::Value, String)> {
+        let review = self.review(agent, tool, args.clone(), || {
+            self.monitor.review_tool(tool, args)
+        })?;
+        match review {
+            Review::Approve => Ok((args.clone(), String::new())),
+            Review::Reject(comment) => Err(ToolError::new(
+                ErrorCode::Rejected,
+                format!("The user rejected the call of `{}`:\n{}", tool, comment),
+            )
+            .into()),
+            Review::Edit(edited) => {
+                let args: serde_json::Value = serde_json::from_str(&edited).map_err(|e| {
+                    ToolError::new(
+                        ErrorCode::InvalidArguments,
+                        format!(
+                            "The user changed the arguments of `{}`, but they are invalid JSON: {}",
+                            tool, e
+                        ),
+                    )
+                })?;
+                let note = format!("(The user changed the arguments to {}.)", args);
+                Ok((args, note))
+            }
+        }
+    }
+
+    fn invoke_tool(&self, agent: &Agent, toolset: &ToolSet, request: &str) -> ToolOutput {
+        if let Ok(calls) = parse_tool_invoke_json(request) {
+            let mut prepared = Vec::new();
+            for (tool, args) in &calls {
+                agent.record(
+                    "tool_call",
+                    serde_json::json!({ "tool": tool, "args": args }),
+                );
+                self.monitor.notify(Event::ToolCall { tool, args });
+                prepared.push(self.prepare_call(agent, tool, args));
+            }
+            let approved = calls
+                .iter()
+                .zip(&prepared)
+                .filter_map(|((tool, _), call)| Some((tool.clone(), call.as_ref().ok()?.0.clone())))
+                .collect::<Vec<_>>();
+            let mut results = toolset
+                .invoke_all(&approved, &self.monitor.get_cancellation())
+                .into_iter();
+            let mut outputs = calls
+                .into_iter()
+                .zip(prepared)
+                .map(|((tool, args), call)| match call {
+                    Ok((args, note)) => {
+                        let mut output = results.next().unwrap_or_else(|| {
+                            ToolOutput::error(
+                                ErrorCode::Failed,
+                                format!("The tool `{}` was not called", tool),
+                            )
+                        });
+                        if !note.is_empty() && !output.is_error {
+                            output.content.insert(0, ContentPart::Text { text: note });
+                        }
+                        (tool, args, output)
+                    }
+                    Err(e) => (tool, args, ToolOutput::from(e)),
+                })
+                .collect::<Vec<_>>();
+            if outputs.len() == 1
+                && let Some((_, _, output)) = outputs.pop()
+            {
+                return output;
+            }
+            // each result is labelled, as some of them may be errors. the whole output is only an
+            // error when every call failed.
+            let is_error = outputs.iter().all(|(_, _, output)| output.is_error);
+            let content = outputs
+                .into_iter()
+                .enumerate()
+                .flat_map(|(i, (tool, args, output))| {
+                    let label = format!(
+                        "{}[{}] {} {}",
+                        if i == 0 { "" } else { "\n" },
+                        i + 1,
+                        tool,
+                        args
+                    );
+                    let mut parts = vec![ContentPart::Text { text: label }];
+                    if output.is_error {
+                        parts.push(ContentPart::Text {
+                            text: output.to_text(),
+                        });
+                    } else {
+                        parts.extend(output.content);
+                    }
+                    parts
+                })
+                .collect();
+            ToolOutput {
+                is_error,
+                ..ToolOutput::new(content)
+            }
+        } else if request.contains(document::DOCUMENT_TOKEN) {
+            let path = document::parse_document(request).ok().map(|edit| edit.path);
+            let args = serde_json::json!({ "path": path, "dry_run": self.dry_run });
+            agent.record(
+                "tool_call",
+                serde_json::json!({ "tool": "document", "args": args }),
+            );
+            self.monitor.notify(Event::ToolCall {
+                tool: "document",
+                args: &args,
+            });
+            let result = self.apply_document(agent, request);
+            if let Some(path) = &path {
+                self.monitor.notify(Event::Document {
+                    path,
+                    outcome: match &result {
+                        Ok((outcome, _)) => *outcome,
+                        Err(_) => DocumentOutcome::Failed,
+                    },
+                });
+            }
+            match result {
+                Err(e) => ToolOutput::from(e),
+                Ok((DocumentOutcome::Rejected, message)) => ToolOutput::text(message),
+                Ok((_, verification)) => ToolOutput::text(format!(
+                    r#"File has been documented. Please keep up the good work!{}
+
+Or, if you think you have finished your task and want to stop,
+please just output a special token [[[[DONE]]]]."#,
+                    verification
+                )),
+            }
+        } else if request.contains("[[[[INVOKE]]]]")
+            && let Err(e) = parse_tool_invoke_json(request)
+        {
+            // e.g. too many calls or malformed JSON, which the model can fix from the message
+            ToolOutput::error(
+                ErrorCode::InvalidCall,
+                format!(
+                    "{}\n\nPlease fix the request and call the tools again with [[[[INVOKE]]]].",
+                    e
+                ),
+            )
+        } else {
+            ToolOutput::error(
+                ErrorCode::InvalidCall,
+                r#"
+I cannot find correct tool name or arguments in the request.
+Please check the format of the request and try again.
+
+If you think you have finished your task and want to stop,
+please just output a special token [[[[DONE]]]].
+
+Otherwise, please either continue to use the tool with [[[[INVOKE]]]] or
+write the documentation with [[[[DOCUMENT]]]] without outputting any [[[[DONE]]]].
+"#,
+            )
+        }
+    }
+
+    // adds the reply to the conversation, and then the output of the tool call or the edit it
+    // requests, or the error explaining why there is none
+    fn answer_tool_call(&self, agent: &mut Agent, toolset: &ToolSet, reply: &Reply) {
+        agent.add_reply(reply);
+        let start = Instant::now();
+        // e.g. the calls of `:tool` in a chat
+        self.call_log.take();
+        let output = self.invoke_tool(agent, toolset, &reply.content);
+        let text = output.to_text();
+        agent.record(
+            "tool_result",
+            serde_json::json!({
+                "is_error": output.is_error,
+                "error_code": output.error_code,
+                "truncated": output.truncated,
+                "content": output.content,
+                "output": text,
+                "calls": self.call_log.take(),
+                "elapsed_ms": start.elapsed().as_millis() as u64,
+            }),
+        );
+        self.monitor.notify(Event::ToolResult {
+            output: &text,
+            is_error: output.is_error,
+        });
+        agent.add_message("user", &format!("{}{}\n", TOOL_OUTPUT_HEADING, text));
+    }
+
+    // answers the last message of the user in a chat. the model may call tools until it
+    // replies without a tool call, which is returned as its answer.
+    pub fn chat(&self, agent: &mut Agent, toolset: &ToolSet) -> Result<Reply> {
+        let mut tool_calls = 0;
+        loop {
+            self.monitor.notify(Event::Request);
+            let reply = agent.post()?;
+            self.monitor.notify(Event::Reply(&reply));
+            if !is_tool_call(&reply.content) {
+                agent.add_reply(&reply);
+                return Ok(reply);
+            }
+            if tool_calls >= MAX_CHAT_TOOL_CALLS {
+                agent.add_reply(&reply);
+                self.monitor.notify(Event::Notice(&format!(
+                    "Stopped after {} tool calls, ask the model to continue if it is not done yet.",
+                    tool_calls
+                )));
+                return Ok(reply);
+            }
+            tool_calls += 1;
+            self.answer_tool_call(agent, toolset, &reply);
+        }
+    }
+
+    pub fn run(&self, agent: &mut Agent, toolset: &ToolSet, session: &mut Session) {
+        let mut failed_requests = 0;
+        loop {
+            match self.monitor.before_request() {
+                Control::Stop => {
+                    agent.record("stopped", serde_json::json!({}));
+                    break;
+                }
+                Control::Continue(mut messages) => {
+                    match session.take_inbox() {
+                        Ok(message) => messages.extend(message),
+                        Err(e) => self.monitor.notify(Event::Notice(&format!(
+                            "Failed to read inbox {}: {}",
+                            session.get_inbox_path().display(),
+                            e
+                        ))),
+                    }
+                    for message in messages {
+                        self.monitor
+                            .notify(Event::Notice(&format!("Sending your message: {}", message)));
+                        agent.record("user_message", serde_json::json!({ "content": message }));
+                        agent.append_message(
+                            "user",
+                            &format!("{}{}\n", USER_MESSAGE_HEADING, message),
+                        );
+                    }
+                }
+            }
+            self.monitor.notify(Event::Request);
+            let reply = match agent.post() {
+                Ok(reply) => reply,
+                Err(e) => {
+                    self.monitor.notify(Event::RequestFailed(&e.to_string()));
+                    failed_requests += 1;
+                    if failed_requests >= MAX_FAILED_REQUESTS {
+                        self.monitor.notify(Event::Notice(&format!(
+                            "Stopping after {} failed requests, continue later with `resume {}`.",
+                            failed_requests, session.id
+                        )));
+                        break;
+                    }
+                    continue;
+                }
+            };
+            failed_requests = 0;
+            self.monitor.notify(Event::Reply(&reply));
+            if reply.content.trim().ends_with("[[[[DONE]]]]") {
+                self.monitor.notify(Event::Done);
+                agent.record("done", serde_json::json!({}));
+                break;
+            }
+            self.answer_tool_call(agent, toolset, &reply);
+            if let Err(e) = session.save(agent) {
+                self.monitor.notify(Event::Notice(&format!(
+                    "Failed to save session {}: {}",
+                    session.id, e
+                )));
+            }
+        }
+    }
+}
+
+// accepts a single call `{"tool": {args}}`, or several calls as an array of such objects
+fn parse_tool_invoke_json(input: &str) -> Result<Vec<(String, serde_json::Value)>> {
+    let input = input
+        .rsplit_once("[[[[INVOKE]]]]")
+        .ok_or(anyhow::anyhow!(
+            "Invalid input. Cannot find invoke heading of [[[[INVOKE]]]]"
+        ))?
+        .1
+        .split_once("```json")
+        .ok_or(anyhow::anyhow!(
+            r#"Invalid input. Cannot find invoke begin of "```json"."#
+        ))?
+        .1
+        .rsplit_once("```")
+        .ok_or(anyhow::anyhow!(
+            r#"Invalid input. Cannot find invoke end of "```"."#
+        ))?
+        .0;
+    let invoke: serde_json::Value = serde_json::from_str(input)
+        .map_err(|e| anyhow::anyhow!("Invalid input. The calls are not valid JSON: {}", e))?;
+    let invokes = match invoke {
+        serde_json::Value::Array(invokes) => invokes,
+        invoke => vec![invoke],
+    };
+    let mut calls = Vec::new();
+    for invoke in &invokes {
+        let invoke = invoke
+            .as_object()
+            .ok_or(anyhow::anyhow!("Input is not a valid JSON object"))?;
+        calls.extend(
+            invoke
+                .iter()
+                .map(|(tool, args)| (tool.clone(), args.clone())),
+        );
+    }
+    if calls.is_empty() {
+        Err(anyhow::anyhow!("Invalid input. There is no tool to call."))
+    } else if calls.len() > MAX_TOOL_CALLS_PER_TURN {
+        Err(anyhow::anyhow!(
+            "Invalid input. You can call at most {} tools at a time.",
+            MAX_TOOL_CALLS_PER_TURN
+        ))
+    } else {
+        Ok(calls)
+    }
+}
+
+fn is_tool_call(reply: &str) -> bool {
+    reply.contains("[[[[INVOKE]]]]") || reply.contains(document::DOCUMENT_TOKEN)
+}
+
+// whether a message of the user is the output of a tool rather than written by the user
+pub fn is_tool_output(content: &str) -> bool {
+    content.starts_with(TOOL_OUTPUT_HEADING)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::secret::Secret;
+
+    fn create_pipeline(root: &std::path::Path) -> (Pipeline, Agent) {
+        let config = Config {
+            url: "https://example.com/v1/chat/completions".to_string(),
+            token: Secret::new("token"),
+            model: "model".to_string(),
+            ..Config::default()
+        };
+        let pipeline = Pipeline::new(
+            StyleRegistry::from_config(&config).unwrap(),
+            Verifier::from_config(&config, root).unwrap(),
+            FileFilter::new(root, &[], &[]).unwrap(),
+        );
+        (pipeline, Agent::new(&config).unwrap())
+    }
+
+    fn invoke(request: &str) -> ToolOutput {
+        let (pipeline, agent) = create_pipeline(&std::env::temp_dir());
+        pipeline.invoke_tool(&agent, &ToolSet::new(), request)
+    }
+
+    #[test]
+    fn invalid_calls_are_explained_to_the_model() {
+        let calls = vec![serde_json::json!({ "list": {} }); MAX_TOOL_CALLS_PER_TURN + 1];
+        let output = invoke(&format!(
+            "[[[[INVOKE]]]]\n```json\n{}\n```",
+            serde_json::Value::Array(calls)
+        ));
+        assert!(output.is_error);
+        assert_eq!(output.error_code, Some(ErrorCode::InvalidCall));
+        assert!(
+            output
+                .to_text()
+                .contains("You can call at most 16 tools at a time."),
+            "{}",
+            output.to_text()
+        );
+
+        let output = invoke("[[[[INVOKE]]]]\n```json\n{\"list\": {\n```");
+        assert_eq!(output.error_code, Some(ErrorCode::InvalidCall));
+        assert!(
+            output
+                .to_text()
+                .contains("The calls are not valid JSON: EOF while parsing an object"),
+            "{}",
+            output.to_text()
+        );
+
+        let output = invoke("I am not sure what to do.");
+        assert!(
+            output
+                .to_text()
+                .contains("I cannot find correct tool name or arguments in the request."),
+            "{}",
+            output.to_text()
+        );
+    }
+
+    #[test]
+    fn relative_paths_are_edited_in_the_codebase() {
+        let root = std::env::temp_dir().join(format!("laluisa-pipeline-{}", std::process::id()));
+        let dir = "laluisa-relative";
+        std::fs::create_dir_all(root.join(dir)).unwrap();
+        std::fs::write(root.join(dir).join("lib.rs"), "fn a() {}\n").unwrap();
+        let (pipeline, agent) = create_pipeline(&root);
+        let output = pipeline.invoke_tool(
+            &agent,
+            &ToolSet::new(),
+            &format!(
+                "[[[[DOCUMENT]]]]\n`{}/lib.rs`\n<<<<<<< SEARCH\nfn a() {{}}\n======= REPLACE\n/// A.\nfn a() {{}}\n>>>>>>> FINISH\n",
+                dir
+            ),
+        );
+        let written = std::fs::read_to_string(root.join(dir).join("lib.rs")).unwrap();
+        std::fs::remove_dir_all(&root).unwrap();
+
+        assert!(!output.is_error, "{}", output.to_text());
+        assert_eq!(written, "/// A.\nfn a() {}\n");
+        // the working directory of the tests is the crate, not the codebase
+        assert!(!std::path::Path::new(dir).exists());
+    }
+
+    struct CountingTool {
+        schema: tool_protocol::ToolSchema,
+        calls: Arc<std::sync::atomic::AtomicUsize>,
+    }
+
+    impl tool_protocol::Tool for CountingTool {
+        fn get_schema(&self) -> &tool_protocol::ToolSchema {
+            &self.schema
+        }
+
+        fn invoke<'a>(&'a self, _args: &'a serde_json::Value) -> tool_protocol::ToolFuture<'a> {
+            self.calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            Box::pin(async { ToolOutput::text("called") })
+        }
+    }
+
+    #[test]
+    fn denied_tools_are_not_called() {
+        let (mut pipeline, agent) = create_pipeline(&std::env::temp_dir());
+        let calls = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let mut toolset = ToolSet::new();
+        toolset.register_tool(Box::new(CountingTool {
+            schema: tool_protocol::ToolSchema {
+                name: "count".to_string(),
+                help: "Counts its calls.".to_string(),
+                arguments: Vec::new(),
+                read_only: false,
+            },
+            calls: calls.clone(),
+        }));
+        let request = "[[[[INVOKE]]]]\n```json\n{\"count\": {}}\n```";
+        let output = pipeline.invoke_tool(&agent, &toolset, request);
+        assert!(!output.is_error, "{}", output.to_text());
+        assert_eq!(calls.load(std::sync::atomic::Ordering::SeqCst), 1);
+
+        pipeline.set_approvals(BTreeMap::from([("count".to_string(), Approval::Deny)]));
+        let output = pipeline.invoke_tool(&agent, &toolset, request);
+        assert_eq!(output.error_code, Some(ErrorCode::Denied));
+        assert!(
+            output
+                .to_text()
+                .contains("The tool `count` is not allowed in this run"),
+            "{}",
+            output.to_text()
+        );
+        assert_eq!(calls.load(std::sync::atomic::Ordering::SeqCst), 1);
+    }
+}
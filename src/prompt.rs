@@ -0,0 +1,118 @@
+// this module implements the system prompt templates. a template is plain text in which
+// `{name}` placeholders are replaced by the variables known to the pipeline, e.g. `{codebase}`,
+// `{tools}`, `{style}` and `{file_list}`, or by the `prompt_variables` given in the config.
+
+use crate::config::Config;
+use anyhow::Result;
+use std::collections::HashMap;
+
+pub const DEFAULT_TEMPLATE: &str = include_str!("../prompts/document.txt");
+pub const CHAT_TEMPLATE: &str = include_str!("../prompts/chat.txt");
+
+// the variables set by the pipeline, which `prompt_variables` may not override
+pub const RESERVED_VARIABLES: &[&str] = &["codebase", "tools", "style", "file_list"];
+
+pub struct PromptTemplate {
+    text: String,
+    variables: HashMap<String, String>,
+}
+
+impl PromptTemplate {
+    pub fn new(text: &str) -> Self {
+        Self {
+            text: text.to_string(),
+            variables: HashMap::new(),
+        }
+    }
+
+    // the template is taken from the inline `system_prompt` key, or else from the file named
+    // by `prompt_template` (relative to the config file), or else the built-in default
+    pub fn from_config(config: &Config) -> Result<Self> {
+        let mut template = if let Some(text) = &config.system_prompt {
+            Self::new(text)
+        } else if let Some(path) = &config.prompt_template {
+            Self::new(&std::fs::read_to_string(path).map_err(|e| {
+                anyhow::anyhow!("Cannot read prompt template {}: {}", path.display(), e)
+            })?)
+        } else {
+            Self::new(DEFAULT_TEMPLATE)
+        };
+        for (name, value) in &config.prompt_variables {
+            let value = match value {
+                serde_json::Value::String(value) => value.clone(),
+                value => value.to_string(),
+            };
+            template.set_variable(name, &value);
+        }
+        Ok(template)
+    }
+
+    pub fn set_variable(&mut self, name: &str, value: &str) {
+        self.variables.insert(name.to_string(), value.to_string());
+    }
+
+    pub fn uses(&self, name: &str) -> bool {
+        self.text.contains(&format!("{{{}}}", name))
+    }
+
+    // replaces the known `{name}` placeholders, and keeps all other braces (e.g. JSON) verbatim
+    pub fn render(&self) -> String {
+        let mut result = String::with_capacity(self.text.len());
+        let mut rest = self.text.as_str();
+        while let Some(open) = rest.find('{') {
+            result.push_str(&rest[..open]);
+            rest = &rest[open..];
+            let value = rest[1..]
+                .find('}')
+                .map(|close| &rest[1..close + 1])
+                .and_then(|name| Some((name, self.variables.get(name)?)));
+            match value {
+                Some((name, value)) => {
+                    result.push_str(value);
+                    rest = &rest[name.len() + 2..];
+                }
+                None => {
+                    result.push('{');
+                    rest = &rest[1..];
+                }
+            }
+        }
+        result.push_str(rest);
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_replaces_only_known_variables() {
+        let mut template = PromptTemplate::new(
+            "Document {codebase} in {language}.\nCall {\"read\": {\"path\": \"{path}\"}} or {}{",
+        );
+        template.set_variable("codebase", "/src");
+        template.set_variable("language", "{style}");
+        assert!(template.uses("codebase"));
+        assert!(!template.uses("style"));
+        assert_eq!(
+            template.render(),
+            "Document /src in {style}.\nCall {\"read\": {\"path\": \"{path}\"}} or {}{"
+        );
+    }
+
+    #[test]
+    fn config_variables_are_set() {
+        let config = Config {
+            system_prompt: Some("{project} has {count} files".to_string()),
+            prompt_variables: [
+                ("project".to_string(), serde_json::json!("LuisaCompute")),
+                ("count".to_string(), serde_json::json!(3)),
+            ]
+            .into(),
+            ..Config::default()
+        };
+        let template = PromptTemplate::from_config(&config).unwrap();
+        assert_eq!(template.render(), "LuisaCompute has 3 files");
+    }
+}
@@ -0,0 +1,563 @@
+// this module implements the interactive chat. the model answers each message with the help
+// of the tools, which it calls in the same loop as the documentation pipeline. the input is
+// read with a line editor, which keeps a persistent history, completes the commands, their
+// arguments and the model names, and accepts pasted text with newlines as a single message.
+
+use crate::agent::{Agent, ModelLister, Usage};
+use crate::monitor::{ConsoleMonitor, Event, Monitor, Review};
+use crate::pipeline::{self, Pipeline};
+use crate::tools::ToolSet;
+use anyhow::Result;
+use rustyline::completion::{Completer, FilenameCompleter, Pair};
+use rustyline::error::ReadlineError;
+use rustyline::history::DefaultHistory;
+use rustyline::{Cmd, Editor, EventHandler, KeyCode, KeyEvent, Modifiers};
+use rustyline::{Helper, Highlighter, Hinter, Validator};
+use serde::{Deserialize, Serialize};
+use std::cell::OnceCell;
+use std::path::{Path, PathBuf};
+
+pub const DEFAULT_HISTORY_FILE: &str = ".laluisa/chat_history";
+
+const COMMANDS: &[&str] = &[
+    ":exit", ":quit", ":q", ":list", ":l", ":set", ":s", ":clear", ":c", ":show", ":help", ":save",
+    ":load", ":system", ":attach", ":tool", ":retry", ":undo", ":tokens",
+];
+
+// the commands whose argument is a path
+const PATH_COMMANDS: &[&str] = &[":save", ":load", ":system", ":attach"];
+
+const SET_KEYS: &[&str] = &[
+    "model",
+    "temperature",
+    "temp",
+    "top_p",
+    "top_k",
+    "frequency_penalty",
+    "max_tokens",
+];
+
+const HELP: &str = r#"Enter sends the message, Alt+Enter starts a new line. Pasted text is kept as it is.
+Tab completes the commands, their arguments and the names of the models.
+
+:set <key> <value>  (:s)  sets model, temperature, top_p, top_k, frequency_penalty or max_tokens
+:list               (:l)  lists the models available at the endpoint
+:system [text|file]       sets the system prompt, or shows it without an argument
+:attach <file>            adds the content of the file to the next message
+:tool <name> <json>       runs a tool with the given arguments and shows its output
+:retry                    asks for the last reply again
+:undo                     drops the last message and the reply to it
+:tokens                   reports the size of the conversation
+:save <file>              saves the conversation
+:load <file>              continues a saved conversation
+:clear              (:c)  clears the conversation
+:show                     shows the settings of the chat
+:exit         (:quit, :q) ends the chat, as does Ctrl+D"#;
+
+// the lines of a tool output shown in the chat
+const MAX_SHOWN_OUTPUT_LINES: usize = 5;
+
+// shows the replies of the model like the console, and each tool call on a single line with
+// the beginning of its output
+pub struct ChatMonitor {
+    console: ConsoleMonitor,
+}
+
+impl ChatMonitor {
+    pub fn new(show_reasoning: bool) -> Self {
+        Self {
+            console: ConsoleMonitor::new(show_reasoning),
+        }
+    }
+}
+
+impl Monitor for ChatMonitor {
+    fn notify(&self, event: Event) {
+        match event {
+            Event::Request | Event::Reply(_) => {}
+            Event::ToolCall { tool, args } => eprintln!("\n\x1b[36m> {} {}\x1b[0m", tool, args),
+            Event::ToolResult { output, .. } => {
+                let lines = output.trim().lines().collect::<Vec<_>>();
+                for line in lines.iter().take(MAX_SHOWN_OUTPUT_LINES) {
+                    eprintln!("\x1b[2m  {}\x1b[0m", line);
+                }
+                if lines.len() > MAX_SHOWN_OUTPUT_LINES {
+                    eprintln!(
+                        "\x1b[2m  ... ({} more lines)\x1b[0m",
+                        lines.len() - MAX_SHOWN_OUTPUT_LINES
+                    );
+                }
+            }
+            event => self.console.notify(event),
+        }
+    }
+
+    fn review_edit(&self, path: &Path, diff: &str, updated: &str) -> Review {
+        self.console.review_edit(path, diff, updated)
+    }
+
+    fn review_tool(&self, tool: &str, args: &serde_json::Value) -> Review {
+        self.console.review_tool(tool, args)
+    }
+}
+
+// a conversation written by `:save`
+#[derive(Serialize, Deserialize)]
+struct SavedChat {
+    system_prompt: String,
+    messages: Vec<serde_json::Value>,
+}
+
+#[derive(Helper, Hinter, Highlighter, Validator)]
+struct ReplHelper {
+    models: ModelLister,
+    // the models are only requested the first time they are completed
+    model_names: OnceCell<Vec<String>>,
+    tool_names: Vec<String>,
+    files: FilenameCompleter,
+}
+
+fn complete_word(word: &str, candidates: &[&str]) -> Vec<Pair> {
+    candidates
+        .iter()
+        .filter(|candidate| candidate.starts_with(word))
+        .map(|candidate| Pair {
+            display: candidate.to_string(),
+            replacement: candidate.to_string(),
+        })
+        .collect()
+}
+
+impl Completer for ReplHelper {
+    type Candidate = Pair;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        ctx: &rustyline::Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let line = &line[..pos];
+        if !line.starts_with(':') {
+            return Ok((pos, Vec::new()));
+        }
+        let start = line.rfind(char::is_whitespace).map_or(0, |i| i + 1);
+        let words = line[..start].split_whitespace().collect::<Vec<_>>();
+        let word = &line[start..];
+        let mut candidates = match words.as_slice() {
+            [] => complete_word(word, COMMANDS),
+            [command] if PATH_COMMANDS.contains(command) => {
+                return self.files.complete(line, pos, ctx);
+            }
+            [":set" | ":s"] => complete_word(word, SET_KEYS),
+            [":set" | ":s", "model"] => {
+                let names = self.model_names.get_or_init(|| self.models.list());
+                complete_word(word, &names.iter().map(String::as_str).collect::<Vec<_>>())
+            }
+            [":tool"] => complete_word(
+                word,
+                &self
+                    .tool_names
+                    .iter()
+                    .map(String::as_str)
+                    .collect::<Vec<_>>(),
+            ),
+            _ => Vec::new(),
+        };
+        // a single match is completed with the space before the next word
+        if let [candidate] = candidates.as_mut_slice() {
+            candidate.replacement.push(' ');
+        }
+        Ok((start, candidates))
+    }
+}
+
+// returns None when the user ends the chat with Ctrl+D
+fn read_input(editor: &mut Editor<ReplHelper, DefaultHistory>, history: &Path) -> Option<String> {
+    eprintln!();
+    match editor.readline("You: ") {
+        Ok(input) => {
+            let input = input.trim().to_string();
+            if !input.is_empty() {
+                let _ = editor.add_history_entry(&input);
+                if let Err(e) = editor.append_history(history) {
+                    eprintln!("Failed to save history {}: {}", history.display(), e);
+                }
+            }
+            Some(input)
+        }
+        // Ctrl+C discards the input
+        Err(ReadlineError::Interrupted) => Some(String::new()),
+        Err(ReadlineError::Eof) => None,
+        Err(e) => {
+            eprintln!("Cannot read input: {}", e);
+            None
+        }
+    }
+}
+
+fn set_args(chat: &mut Agent, key: &str, value: &str) {
+    match key.trim() {
+        "model" => {
+            chat.set_model(value);
+        }
+        "temperature" | "temp" => {
+            if let Ok(temperature) = value.parse::<f64>() {
+                chat.set_temperature(temperature);
+            } else {
+                println!("Invalid temperature value: {}", value);
+            }
+        }
+        "top_p" => {
+            if let Ok(top_p) = value.parse::<f64>() {
+                chat.set_top_p(top_p);
+            } else {
+                println!("Invalid top_p value: {}", value);
+            }
+        }
+        "top_k" => {
+            if let Ok(top_k) = value.parse::<u64>() {
+                chat.set_top_k(top_k);
+            } else {
+                println!("Invalid top_k value: {}", value);
+            }
+        }
+        "frequency_penalty" => {
+            if let Ok(frequency_penalty) = value.parse::<f64>() {
+                chat.set_frequency_penalty(frequency_penalty);
+            } else {
+                println!("Invalid frequency_penalty value: {}", value);
+            }
+        }
+        "max_tokens" => {
+            if let Ok(max_tokens) = value.parse::<u64>() {
+                chat.set_max_tokens(max_tokens);
+            } else {
+                println!("Invalid max_tokens value: {}", value);
+            }
+        }
+        _ => {
+            println!("Unknown key: {}", key);
+        }
+    }
+}
+
+fn create_editor(
+    chat: &Agent,
+    toolset: &ToolSet,
+    history: &Path,
+) -> rustyline::Result<Editor<ReplHelper, DefaultHistory>> {
+    let config = rustyline::Config::builder()
+        .max_history_size(1000)?
+        .history_ignore_dups(true)?
+        .bracketed_paste(true)
+        .completion_type(rustyline::CompletionType::List)
+        .build();
+    let mut editor = Editor::with_config(config)?;
+    let mut tool_names = toolset.get_tools().keys().cloned().collect::<Vec<_>>();
+    tool_names.sort();
+    editor.set_helper(Some(ReplHelper {
+        models: chat.get_model_lister(),
+        model_names: OnceCell::new(),
+        tool_names,
+        files: FilenameCompleter::new(),
+    }));
+    editor.bind_sequence(
+        KeyEvent(KeyCode::Enter, Modifiers::ALT),
+        EventHandler::Simple(Cmd::Newline),
+    );
+    editor.bind_sequence(
+        KeyEvent(KeyCode::Enter, Modifiers::NONE),
+        EventHandler::Simple(Cmd::AcceptLine),
+    );
+    if let Some(dir) = history.parent() {
+        let _ = std::fs::create_dir_all(dir);
+    }
+    // there is no history before the first chat
+    let _ = editor.load_history(history);
+    Ok(editor)
+}
+
+fn save_chat(chat: &Agent, path: &Path) -> Result<()> {
+    let saved = SavedChat {
+        system_prompt: chat.get_system_prompt().to_string(),
+        messages: chat.get_messages(),
+    };
+    std::fs::write(path, serde_json::to_string_pretty(&saved)?)
+        .map_err(|e| anyhow::anyhow!("Cannot write {}: {}", path.display(), e))
+}
+
+fn load_chat(chat: &mut Agent, path: &Path) -> Result<()> {
+    let file = std::fs::File::open(path)
+        .map_err(|e| anyhow::anyhow!("Cannot open {}: {}", path.display(), e))?;
+    let saved: SavedChat = serde_json::from_reader(file)
+        .map_err(|e| anyhow::anyhow!("Invalid conversation {}: {}", path.display(), e))?;
+    chat.set_system_prompt(&saved.system_prompt);
+    chat.set_messages(&saved.messages);
+    Ok(())
+}
+
+// the argument is read as a file if there is one at that path
+fn set_system_prompt(chat: &mut Agent, arg: &str) -> Result<()> {
+    if arg.is_empty() {
+        println!("{}", chat.get_system_prompt());
+        return Ok(());
+    }
+    let path = Path::new(arg);
+    let prompt = if path.is_file() {
+        std::fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("Cannot read {}: {}", path.display(), e))?
+    } else {
+        arg.to_string()
+    };
+    chat.set_system_prompt(&prompt);
+    Ok(())
+}
+
+fn run_tool(toolset: &ToolSet, arg: &str) -> Result<()> {
+    let (name, args) = arg.split_once(char::is_whitespace).unwrap_or((arg, ""));
+    if name.is_empty() {
+        anyhow::bail!("Usage: :tool <name> <json>");
+    }
+    let args = if args.trim().is_empty() {
+        serde_json::json!({})
+    } else {
+        serde_json::from_str(args)
+            .map_err(|e| anyhow::anyhow!("Invalid arguments of `{}`: {}", name, e))?
+    };
+    let output = toolset.invoke(name, &args);
+    if let Some(code) = output.error_code {
+        anyhow::bail!("{} ({})", output.to_text(), code);
+    }
+    println!("{}", output.to_text());
+    Ok(())
+}
+
+// the tokens of the last request if the provider reported them, and an estimate of the whole
+// conversation from its length
+fn report_tokens(chat: &Agent, usage: Option<Usage>) {
+    let messages = chat.get_messages();
+    let characters = chat.get_system_prompt().len()
+        + messages
+            .iter()
+            .map(|message| message["content"].as_str().unwrap_or_default().len())
+            .sum::<usize>();
+    println!(
+        "{} messages, about {} tokens.",
+        messages.len(),
+        characters / 4
+    );
+    if let Some(usage) = usage {
+        println!(
+            "The last request used {} prompt and {} completion tokens.",
+            usage.prompt_tokens, usage.completion_tokens
+        );
+    }
+}
+
+// the index of the last message of the user, where the last exchange starts
+fn last_user_message(chat: &Agent) -> Option<usize> {
+    chat.get_messages().iter().rposition(|message| {
+        message["role"] == "user"
+            && !pipeline::is_tool_output(message["content"].as_str().unwrap_or_default())
+    })
+}
+
+// keeps the last message of the user, so that it is sent again, and drops the reply to it
+fn drop_last_reply(chat: &mut Agent) -> Result<()> {
+    let i = last_user_message(chat).ok_or(anyhow::anyhow!("There is no message to retry."))?;
+    chat.truncate_messages(i + 1);
+    Ok(())
+}
+
+fn drop_last_message(chat: &mut Agent) -> Result<()> {
+    let i = last_user_message(chat).ok_or(anyhow::anyhow!("There is no message to undo."))?;
+    chat.truncate_messages(i);
+    Ok(())
+}
+
+fn send(chat: &mut Agent, toolset: &ToolSet, pipeline: &Pipeline) -> Option<Usage> {
+    match pipeline.chat(chat, toolset) {
+        Ok(reply) => {
+            eprintln!();
+            reply.usage
+        }
+        Err(e) => {
+            eprintln!("\nRequest failed: {}", e);
+            None
+        }
+    }
+}
+
+pub fn run(chat: &mut Agent, toolset: &ToolSet, pipeline: &Pipeline, history: &Path) -> Result<()> {
+    let mut editor = create_editor(chat, toolset, history)?;
+    chat.clear_messages();
+    // the files added to the next message
+    let mut attachments: Vec<(PathBuf, String)> = Vec::new();
+    let mut usage = None;
+    eprintln!("Type :help for the commands and keys.");
+    while let Some(input) = read_input(&mut editor, history) {
+        if input.is_empty() {
+            continue;
+        }
+        if !input.starts_with(':') {
+            let mut message = String::new();
+            for (path, content) in attachments.drain(..) {
+                message.push_str(&format!(
+                    "Content of {}:\n```\n{}\n```\n\n",
+                    path.display(),
+                    content.trim_end()
+                ));
+            }
+            message.push_str(&input);
+            chat.add_message("user", &message);
+            usage = send(chat, toolset, pipeline);
+            continue;
+        }
+        let (command, arg) = input
+            .split_once(char::is_whitespace)
+            .unwrap_or((&input, ""));
+        let arg = arg.trim();
+        let result = match command {
+            ":exit" | ":quit" | ":q" => break,
+            ":help" => {
+                println!("{}", HELP);
+                Ok(())
+            }
+            ":list" | ":l" => {
+                for model in chat.list_models() {
+                    println!("{}", model);
+                }
+                Ok(())
+            }
+            ":set" | ":s" => {
+                let args = arg.split_whitespace().collect::<Vec<&str>>();
+                if args.len() < 2 {
+                    println!("Usage: :set <key> <value>");
+                } else {
+                    set_args(chat, args[0], args[1]);
+                }
+                Ok(())
+            }
+            ":clear" | ":c" => {
+                chat.clear_messages();
+                Ok(())
+            }
+            ":show" => {
+                println!("{:#?}", chat);
+                Ok(())
+            }
+            ":save" if !arg.is_empty() => save_chat(chat, Path::new(arg)),
+            ":load" if !arg.is_empty() => load_chat(chat, Path::new(arg)).map(|()| {
+                println!("Loaded {} messages.", chat.get_messages().len());
+            }),
+            ":save" | ":load" => Err(anyhow::anyhow!("Usage: {} <file>", command)),
+            ":system" => set_system_prompt(chat, arg),
+            ":attach" if !arg.is_empty() => std::fs::read_to_string(arg)
+                .map_err(|e| anyhow::anyhow!("Cannot read {}: {}", arg, e))
+                .map(|content| {
+                    println!(
+                        "{} ({} bytes) is added to the next message.",
+                        arg,
+                        content.len()
+                    );
+                    attachments.push((PathBuf::from(arg), content));
+                }),
+            ":attach" => Err(anyhow::anyhow!("Usage: :attach <file>")),
+            ":tool" => run_tool(toolset, arg),
+            ":retry" => drop_last_reply(chat).map(|()| {
+                usage = send(chat, toolset, pipeline);
+            }),
+            ":undo" => drop_last_message(chat),
+            ":tokens" => {
+                report_tokens(chat, usage);
+                Ok(())
+            }
+            _ => Err(anyhow::anyhow!(
+                "Unknown command: {}, type :help for the commands.",
+                command
+            )),
+        };
+        if let Err(e) = result {
+            println!("{}", e);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::pipeline::TOOL_OUTPUT_HEADING;
+    use crate::secret::Secret;
+
+    fn create_chat() -> Agent {
+        let config = Config {
+            url: "https://example.com/v1/chat/completions".to_string(),
+            token: Secret::new("token"),
+            model: "model".to_string(),
+            ..Config::default()
+        };
+        let mut chat = Agent::new(&config).unwrap();
+        chat.add_message("user", "first");
+        chat.add_message("assistant", "first reply");
+        chat.add_message("user", "second");
+        chat.add_message("assistant", "[[[[INVOKE]]]]");
+        chat.add_message("user", &format!("{}done\n", TOOL_OUTPUT_HEADING));
+        chat.add_message("assistant", "second reply");
+        chat
+    }
+
+    fn contents(chat: &Agent) -> Vec<String> {
+        chat.get_messages()
+            .iter()
+            .map(|message| message["content"].as_str().unwrap_or_default().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn retry_and_undo_skip_the_tool_outputs() {
+        let mut chat = create_chat();
+        drop_last_reply(&mut chat).unwrap();
+        assert_eq!(contents(&chat), ["first", "first reply", "second"]);
+
+        let mut chat = create_chat();
+        drop_last_message(&mut chat).unwrap();
+        assert_eq!(contents(&chat), ["first", "first reply"]);
+        drop_last_message(&mut chat).unwrap();
+        assert!(chat.get_messages().is_empty());
+        assert_eq!(
+            drop_last_message(&mut chat).unwrap_err().to_string(),
+            "There is no message to undo."
+        );
+        assert_eq!(
+            drop_last_reply(&mut chat).unwrap_err().to_string(),
+            "There is no message to retry."
+        );
+    }
+
+    #[test]
+    fn saved_chats_are_loaded_again() {
+        let path = std::env::temp_dir().join(format!("laluisa-chat-{}.json", std::process::id()));
+        let mut chat = create_chat();
+        chat.set_system_prompt("You document code.");
+        save_chat(&chat, &path).unwrap();
+
+        let mut loaded = create_chat();
+        loaded.set_system_prompt("");
+        loaded.clear_messages();
+        let result = load_chat(&mut loaded, &path);
+        std::fs::remove_file(&path).unwrap();
+
+        result.unwrap();
+        assert_eq!(loaded.get_system_prompt(), "You document code.");
+        assert_eq!(loaded.get_messages(), chat.get_messages());
+        assert!(
+            load_chat(&mut loaded, &path)
+                .unwrap_err()
+                .to_string()
+                .starts_with("Cannot open")
+        );
+    }
+}
@@ -0,0 +1,194 @@
+// this module keeps the API token out of every printed, logged or saved output. the token
+// is wrapped in `Secret`, whose `Debug`, `Display` and `Serialize` never show the value.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+pub const REDACTED: &str = "[REDACTED]";
+
+#[derive(Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(transparent)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn new(value: &str) -> Self {
+        Self(value.to_string())
+    }
+
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    // replaces every occurrence of the secret, e.g. in an error message echoing a request
+    pub fn redact(&self, text: &str) -> String {
+        if self.0.is_empty() {
+            text.to_string()
+        } else {
+            text.replace(&self.0, REDACTED)
+        }
+    }
+}
+
+impl std::fmt::Debug for Secret {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(if self.0.is_empty() { "\"\"" } else { REDACTED })
+    }
+}
+
+impl std::fmt::Display for Secret {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(if self.0.is_empty() { "" } else { REDACTED })
+    }
+}
+
+impl Serialize for Secret {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+// whether users other than the owner may read the file, always false where there are no
+// unix permissions
+pub fn is_world_readable(path: &Path) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::metadata(path).is_ok_and(|metadata| metadata.permissions().mode() & 0o004 != 0)
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = path;
+        false
+    }
+}
+
+pub fn warn_if_world_readable(path: &Path, what: &str) {
+    if is_world_readable(path) {
+        eprintln!(
+            "Warning: {} {} holds a token and is readable by other users, consider `chmod 600 {}`",
+            what,
+            path.display(),
+            path.display()
+        );
+    }
+}
+
+pub fn read_token_file(path: &Path) -> Result<Secret> {
+    warn_if_world_readable(path, "token file");
+    let token = std::fs::read_to_string(path)
+        .map_err(|e| anyhow::anyhow!("Cannot read token file {}: {}", path.display(), e))?;
+    Ok(Secret::new(token.trim()))
+}
+
+// runs a credential helper, which prints the token to stdout
+pub fn run_token_command(command: &str) -> Result<Secret> {
+    let output = if cfg!(windows) {
+        std::process::Command::new("cmd")
+            .args(["/C", command])
+            .output()
+    } else {
+        std::process::Command::new("sh")
+            .args(["-c", command])
+            .output()
+    }
+    .map_err(|e| anyhow::anyhow!("Cannot run token command `{}`: {}", command, e))?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "Token command `{}` failed with {}: {}",
+            command,
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    let token = String::from_utf8(output.stdout)
+        .map_err(|_| anyhow::anyhow!("Token command `{}` printed invalid UTF-8", command))?;
+    Ok(Secret::new(token.trim()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    #[test]
+    fn secrets_are_never_shown() {
+        let secret = Secret::new("token");
+        assert_eq!(format!("{:?}", secret), REDACTED);
+        assert_eq!(secret.to_string(), REDACTED);
+        assert_eq!(
+            serde_json::to_string(&secret).unwrap(),
+            format!("\"{}\"", REDACTED)
+        );
+        assert_eq!(secret.expose(), "token");
+
+        let empty = Secret::default();
+        assert_eq!(format!("{:?}", empty), "\"\"");
+        assert_eq!(empty.to_string(), "");
+    }
+
+    #[test]
+    fn redact_replaces_every_occurrence() {
+        let secret = Secret::new("token");
+        assert_eq!(
+            secret.redact("Bearer token, again token"),
+            "Bearer [REDACTED], again [REDACTED]"
+        );
+        assert_eq!(Secret::default().redact("no token"), "no token");
+    }
+
+    #[test]
+    fn token_sources_take_precedence_over_each_other() {
+        let dir = std::env::temp_dir().join(format!("laluisa-secret-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("token");
+        std::fs::write(&path, "file-token\n").unwrap();
+        let getenv = |name: &str| (name == "TOKEN_ENV").then(|| "env-token".to_string());
+        let mut config = Config {
+            token: Secret::new("config-token"),
+            token_env: Some("TOKEN_ENV".to_string()),
+            token_file: Some(path),
+            token_command: Some("echo command-token".to_string()),
+            ..Config::default()
+        };
+        let command = config.get_token(getenv);
+        config.token_command = None;
+        let file = config.get_token(getenv);
+        config.token_file = None;
+        let env = config.get_token(getenv);
+        let unset = config.get_token(|_| None);
+        config.token_env = None;
+        let token = config.get_token(getenv);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(command.unwrap().expose(), "command-token");
+        assert_eq!(file.unwrap().expose(), "file-token");
+        assert_eq!(env.unwrap().expose(), "env-token");
+        assert_eq!(
+            unset.unwrap_err().to_string(),
+            "Invalid config: `token_env`: environment variable `TOKEN_ENV` is not set"
+        );
+        assert_eq!(token.unwrap().expose(), "config-token");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn world_readable_files_are_detected() {
+        use std::os::unix::fs::PermissionsExt;
+        let path = std::env::temp_dir().join(format!("laluisa-readable-{}", std::process::id()));
+        std::fs::write(&path, "token").unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+        let readable = is_world_readable(&path);
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600)).unwrap();
+        let private = is_world_readable(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(readable);
+        assert!(!private);
+        assert!(!is_world_readable(&path));
+    }
+}
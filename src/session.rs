@@ -0,0 +1,166 @@
+// this module saves the state of a documentation run after each turn, so that an
+// interrupted run can be continued later with `resume`. a running session also reads the
+// messages of the user from its inbox, a file next to the session file.
+
+use crate::agent::Agent;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+pub const DEFAULT_SESSION_DIR: &str = ".laluisa/sessions";
+
+#[derive(Serialize, Deserialize)]
+pub struct Session {
+    pub id: String,
+    pub codebase: PathBuf,
+    pub dry_run: bool,
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+    // the files of a worker of a concurrent run, which may not document any other file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub assigned: Option<Vec<String>>,
+    pub system_prompt: String,
+    pub messages: Vec<serde_json::Value>,
+    #[serde(skip)]
+    path: PathBuf,
+}
+
+pub fn new_session_id() -> String {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default();
+    format!("{}-{}", now.as_secs(), std::process::id())
+}
+
+impl Session {
+    pub fn new(
+        dir: &Path,
+        id: &str,
+        codebase: &Path,
+        dry_run: bool,
+        include: &[String],
+        exclude: &[String],
+    ) -> Self {
+        Self {
+            id: id.to_string(),
+            codebase: codebase.to_path_buf(),
+            dry_run,
+            include: include.to_vec(),
+            exclude: exclude.to_vec(),
+            assigned: None,
+            system_prompt: String::new(),
+            messages: Vec::new(),
+            path: dir.join(format!("{}.json", id)),
+        }
+    }
+
+    // accepts either a session id in the session directory or a path to a session file
+    pub fn load(dir: &Path, name: &str) -> Result<Self> {
+        let path = if Path::new(name).is_file() {
+            PathBuf::from(name)
+        } else {
+            dir.join(format!("{}.json", name))
+        };
+        let file = std::fs::File::open(&path)
+            .map_err(|e| anyhow::anyhow!("Cannot open session {}: {}", path.display(), e))?;
+        let mut session: Session = serde_json::from_reader(file)
+            .map_err(|e| anyhow::anyhow!("Invalid session file {}: {}", path.display(), e))?;
+        session.path = path;
+        Ok(session)
+    }
+
+    pub fn get_path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn get_inbox_path(&self) -> PathBuf {
+        self.path.with_extension("inbox")
+    }
+
+    // appends a message to the inbox, which the run sends to the model before its next request.
+    // the inbox is locked while it is written, so that it is never emptied in between.
+    pub fn send(&self, message: &str) -> Result<()> {
+        let path = self.get_inbox_path();
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .map_err(|e| anyhow::anyhow!("Cannot open inbox {}: {}", path.display(), e))?;
+        file.lock()?;
+        writeln!(file, "{}", message.trim())?;
+        Ok(())
+    }
+
+    // takes the messages sent since the last call, all of them as a single message. the inbox
+    // is read and emptied under the lock of `send`, so that no message is lost in between.
+    pub fn take_inbox(&self) -> Result<Option<String>> {
+        let path = self.get_inbox_path();
+        let mut file = match std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&path)
+        {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => anyhow::bail!("Cannot open inbox {}: {}", path.display(), e),
+        };
+        file.lock()?;
+        let mut message = String::new();
+        file.read_to_string(&mut message)?;
+        file.set_len(0)?;
+        let message = message.trim();
+        Ok((!message.is_empty()).then(|| message.to_string()))
+    }
+
+    pub fn save(&mut self, agent: &Agent) -> Result<()> {
+        self.system_prompt = agent.get_system_prompt().to_string();
+        self.messages = agent.get_messages();
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&self.path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    pub fn restore(&self, agent: &mut Agent) {
+        agent.set_system_prompt(&self.system_prompt);
+        agent.set_messages(&self.messages);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn messages_sent_while_taking_the_inbox_are_kept() {
+        let dir = std::env::temp_dir().join(format!("laluisa-session-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let session = Session::new(&dir, "inbox", &dir, false, &[], &[]);
+        assert_eq!(session.take_inbox().unwrap(), None);
+
+        let mut taken = Vec::new();
+        std::thread::scope(|scope| {
+            let sender = scope.spawn(|| {
+                for i in 0..200 {
+                    session.send(&format!("message {}", i)).unwrap();
+                }
+            });
+            while !sender.is_finished() {
+                taken.extend(session.take_inbox().unwrap());
+            }
+        });
+        taken.extend(session.take_inbox().unwrap());
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let messages = taken
+            .iter()
+            .flat_map(|message| message.lines())
+            .collect::<Vec<_>>();
+        let expected = (0..200)
+            .map(|i| format!("message {}", i))
+            .collect::<Vec<_>>();
+        assert_eq!(messages, expected);
+    }
+}
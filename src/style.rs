@@ -0,0 +1,890 @@
+// this module implements the documentation style profiles. each profile describes the
+// expected comment format of a language, the sections it requires, and validates the
+// documentation written by the model.
+
+use crate::config::Config;
+use crate::lexer;
+use anyhow::Result;
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Language {
+    Cpp,
+    Rust,
+    Python,
+    TypeScript,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Section {
+    Params,
+    Returns,
+    Throws,
+}
+
+impl Section {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Section::Params => "parameters",
+            Section::Returns => "return value",
+            Section::Throws => "errors",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct SectionRule {
+    pub section: Section,
+    // any of the markers satisfies the rule, the first one is the preferred spelling
+    pub markers: &'static [&'static str],
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommentSyntax {
+    Line(&'static str),
+    Block(&'static str, &'static str),
+    Docstring,
+}
+
+pub type Validator = fn(&StyleProfile, &str) -> Vec<String>;
+
+pub struct StyleProfile {
+    pub name: &'static str,
+    pub language: Language,
+    pub description: &'static str,
+    pub syntax: CommentSyntax,
+    pub sections: &'static [SectionRule],
+    pub validator: Validator,
+}
+
+impl StyleProfile {
+    pub fn validate(&self, text: &str) -> Vec<String> {
+        (self.validator)(self, text)
+    }
+
+    pub fn get_rules(&self) -> String {
+        let mut rules = format!("{} ({})", self.description, self.name);
+        if !self.sections.is_empty() {
+            let sections = self
+                .sections
+                .iter()
+                .map(|rule| format!("{} (`{}`)", rule.section.name(), rule.markers[0]))
+                .collect::<Vec<_>>()
+                .join(", ");
+            rules.push_str(&format!(
+                "\n  Required sections when applicable: {}.",
+                sections
+            ));
+        }
+        rules
+    }
+}
+
+const DOXYGEN_SECTIONS: &[SectionRule] = &[
+    SectionRule {
+        section: Section::Params,
+        markers: &["@param", "\\param"],
+    },
+    SectionRule {
+        section: Section::Returns,
+        markers: &["@return", "\\return"],
+    },
+    SectionRule {
+        section: Section::Throws,
+        markers: &["@throws", "\\throws", "@throw", "\\throw", "@exception"],
+    },
+];
+
+pub static PROFILES: &[StyleProfile] = &[
+    StyleProfile {
+        name: "doxygen",
+        language: Language::Cpp,
+        description: "Doxygen with `///` line comments placed right before the declaration, starting with a `@brief` line.",
+        syntax: CommentSyntax::Line("///"),
+        sections: DOXYGEN_SECTIONS,
+        validator: validate_comments,
+    },
+    StyleProfile {
+        name: "doxygen-block",
+        language: Language::Cpp,
+        description: "Doxygen with `/** ... */` block comments placed right before the declaration, starting with a `@brief` line.",
+        syntax: CommentSyntax::Block("/**", "*/"),
+        sections: DOXYGEN_SECTIONS,
+        validator: validate_comments,
+    },
+    StyleProfile {
+        name: "rustdoc",
+        language: Language::Rust,
+        description: "Rustdoc with `///` item comments and `//!` module comments in Markdown, starting with a one-line summary.",
+        syntax: CommentSyntax::Line("///"),
+        sections: &[SectionRule {
+            section: Section::Throws,
+            markers: &["# Errors"],
+        }],
+        validator: validate_comments,
+    },
+    StyleProfile {
+        name: "python-google",
+        language: Language::Python,
+        description: "Google-style Python docstrings in triple double quotes right after the `def` or `class` line.",
+        syntax: CommentSyntax::Docstring,
+        sections: &[
+            SectionRule {
+                section: Section::Params,
+                markers: &["Args:", "Arguments:"],
+            },
+            SectionRule {
+                section: Section::Returns,
+                markers: &["Returns:", "Yields:"],
+            },
+            SectionRule {
+                section: Section::Throws,
+                markers: &["Raises:"],
+            },
+        ],
+        validator: validate_docstrings,
+    },
+    StyleProfile {
+        name: "python-numpy",
+        language: Language::Python,
+        description: "NumPy-style Python docstrings in triple double quotes right after the `def` or `class` line, with underlined section headers.",
+        syntax: CommentSyntax::Docstring,
+        sections: &[
+            SectionRule {
+                section: Section::Params,
+                markers: &["Parameters"],
+            },
+            SectionRule {
+                section: Section::Returns,
+                markers: &["Returns", "Yields"],
+            },
+            SectionRule {
+                section: Section::Throws,
+                markers: &["Raises"],
+            },
+        ],
+        validator: validate_docstrings,
+    },
+    StyleProfile {
+        name: "python-rest",
+        language: Language::Python,
+        description: "reStructuredText (Sphinx) Python docstrings in triple double quotes right after the `def` or `class` line.",
+        syntax: CommentSyntax::Docstring,
+        sections: &[
+            SectionRule {
+                section: Section::Params,
+                markers: &[":param"],
+            },
+            SectionRule {
+                section: Section::Returns,
+                markers: &[":return"],
+            },
+            SectionRule {
+                section: Section::Throws,
+                markers: &[":raises"],
+            },
+        ],
+        validator: validate_docstrings,
+    },
+    StyleProfile {
+        name: "tsdoc",
+        language: Language::TypeScript,
+        description: "TSDoc with `/** ... */` block comments placed right before the declaration.",
+        syntax: CommentSyntax::Block("/**", "*/"),
+        sections: &[
+            SectionRule {
+                section: Section::Params,
+                markers: &["@param"],
+            },
+            SectionRule {
+                section: Section::Returns,
+                markers: &["@returns"],
+            },
+            SectionRule {
+                section: Section::Throws,
+                markers: &["@throws"],
+            },
+        ],
+        validator: validate_comments,
+    },
+];
+
+const DEFAULT_STYLE: &str = "doxygen";
+
+const DEFAULT_EXTENSIONS: &[(&str, &str)] = &[
+    ("h", "doxygen"),
+    ("hh", "doxygen"),
+    ("hpp", "doxygen"),
+    ("hxx", "doxygen"),
+    ("inl", "doxygen"),
+    ("c", "doxygen"),
+    ("cc", "doxygen"),
+    ("cpp", "doxygen"),
+    ("cxx", "doxygen"),
+    ("cu", "doxygen"),
+    ("m", "doxygen"),
+    ("mm", "doxygen"),
+    ("rs", "rustdoc"),
+    ("py", "python-google"),
+    ("pyi", "python-google"),
+    ("ts", "tsdoc"),
+    ("tsx", "tsdoc"),
+    ("mts", "tsdoc"),
+    ("cts", "tsdoc"),
+];
+
+pub fn find_profile(name: &str) -> Result<&'static StyleProfile> {
+    PROFILES
+        .iter()
+        .find(|profile| profile.name == name)
+        .ok_or(anyhow::anyhow!(
+            "Unknown documentation style `{}`, expected one of: {}",
+            name,
+            PROFILES
+                .iter()
+                .map(|profile| profile.name)
+                .collect::<Vec<_>>()
+                .join(", ")
+        ))
+}
+
+pub struct StyleRegistry {
+    by_extension: HashMap<String, &'static StyleProfile>,
+    fallback: &'static StyleProfile,
+}
+
+impl StyleRegistry {
+    // reads the optional `styles` (extension -> profile name) and `default_style` config keys
+    pub fn from_config(config: &Config) -> Result<Self> {
+        let mut by_extension = HashMap::new();
+        for (extension, name) in DEFAULT_EXTENSIONS {
+            by_extension.insert(extension.to_string(), find_profile(name)?);
+        }
+        for (extension, name) in &config.styles {
+            by_extension.insert(
+                extension.trim_start_matches('.').to_lowercase(),
+                find_profile(name).map_err(|e| {
+                    anyhow::anyhow!("Invalid config: `styles.{}`: {}", extension, e)
+                })?,
+            );
+        }
+        let fallback = find_profile(config.default_style.as_deref().unwrap_or(DEFAULT_STYLE))
+            .map_err(|e| anyhow::anyhow!("Invalid config: `default_style`: {}", e))?;
+        Ok(Self {
+            by_extension,
+            fallback,
+        })
+    }
+
+    pub fn for_path(&self, path: &Path) -> &'static StyleProfile {
+        path.extension()
+            .and_then(|extension| extension.to_str())
+            .and_then(|extension| self.by_extension.get(&extension.to_lowercase()))
+            .copied()
+            .unwrap_or(self.fallback)
+    }
+
+    pub fn has_style(&self, path: &Path) -> bool {
+        path.extension()
+            .and_then(|extension| extension.to_str())
+            .is_some_and(|extension| self.by_extension.contains_key(&extension.to_lowercase()))
+    }
+
+    pub fn get_prompt(&self) -> String {
+        let mut extensions: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+        for (extension, profile) in &self.by_extension {
+            extensions.entry(profile.name).or_default().push(extension);
+        }
+        let mut prompt = String::new();
+        for profile in PROFILES {
+            if let Some(extensions) = extensions.get_mut(profile.name) {
+                extensions.sort();
+                let extensions = extensions
+                    .iter()
+                    .map(|extension| format!("`.{}`", extension))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                prompt.push_str(&format!("- {}: {}\n", extensions, profile.get_rules()));
+            }
+        }
+        prompt.push_str(&format!("- Other files: {}\n", self.fallback.get_rules()));
+        prompt
+    }
+}
+
+struct Signature {
+    params: Vec<String>,
+    returns: bool,
+    fallible: bool,
+}
+
+// splits at top-level commas, ignoring the ones nested in brackets
+fn split_top_level(text: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0i32;
+    let mut begin = 0;
+    for (i, c) in text.char_indices() {
+        match c {
+            '(' | '[' | '{' | '<' => depth += 1,
+            ')' | ']' | '}' | '>' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(&text[begin..i]);
+                begin = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&text[begin..]);
+    parts
+        .into_iter()
+        .map(|part| part.trim())
+        .filter(|part| !part.is_empty())
+        .collect()
+}
+
+// returns the text between the first `(` and its matching `)`, and the text after it
+fn split_parens(text: &str) -> Option<(&str, &str, &str)> {
+    let open = text.find('(')?;
+    let mut depth = 0;
+    for (i, c) in text[open..].char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => {
+                depth -= 1;
+                if depth == 0 {
+                    let close = open + i;
+                    return Some((&text[..open], &text[open + 1..close], &text[close + 1..]));
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+fn is_identifier(text: &str) -> bool {
+    !text.is_empty()
+        && !text.starts_with(|c: char| c.is_ascii_digit())
+        && text.chars().all(|c| c.is_alphanumeric() || c == '_')
+}
+
+fn identifiers(text: &str) -> Vec<&str> {
+    text.split(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .filter(|token| is_identifier(token))
+        .collect()
+}
+
+// the item is tokenized so that `fn` in comments, strings and attributes is not taken for the
+// keyword
+fn parse_rust_signature(item: &str) -> Option<Signature> {
+    let tokens = lexer::tokenize(Language::Rust, item);
+    let tokens = tokens
+        .iter()
+        .map(|token| token.text.as_str())
+        .collect::<Vec<_>>();
+    let mut depth = 0;
+    let mut keyword = None;
+    for (i, token) in tokens.iter().enumerate() {
+        match *token {
+            "(" | "[" | "{" => depth += 1,
+            ")" | "]" | "}" => depth -= 1,
+            "fn" if depth == 0 => {
+                keyword = Some(i);
+                break;
+            }
+            _ => {}
+        }
+    }
+    // the name, then the generic parameters if any
+    let mut i = keyword? + 2;
+    if tokens.get(i) == Some(&"<") {
+        let mut depth = 0;
+        while i < tokens.len() {
+            match tokens[i] {
+                "<" => depth += 1,
+                ">" if tokens[i - 1] != "-" => depth -= 1,
+                _ => {}
+            }
+            i += 1;
+            if depth == 0 {
+                break;
+            }
+        }
+    }
+    if tokens.get(i) != Some(&"(") {
+        return None;
+    }
+    let mut params = Vec::new();
+    let mut param = Vec::new();
+    let mut depth = 0;
+    loop {
+        i += 1;
+        let token = *tokens.get(i)?;
+        match token {
+            "(" | "[" | "{" | "<" => depth += 1,
+            ")" if depth == 0 => break,
+            ")" | "]" | "}" => depth -= 1,
+            ">" if tokens[i - 1] != "-" => depth -= 1,
+            "," if depth == 0 => {
+                params.push(std::mem::take(&mut param));
+                continue;
+            }
+            _ => {}
+        }
+        param.push(token);
+    }
+    params.push(param);
+    let params = params
+        .into_iter()
+        .filter_map(|param| {
+            let param = param.strip_prefix(&["mut"]).unwrap_or(&param);
+            match param {
+                [name, ":", ..] if is_identifier(name) => Some(name.to_string()),
+                _ => None,
+            }
+        })
+        .filter(|name| name != "self" && name != "_")
+        .collect();
+    let returns = if tokens.get(i + 1) == Some(&"-") && tokens.get(i + 2) == Some(&">") {
+        tokens[i + 3..]
+            .iter()
+            .take_while(|token| !matches!(**token, "{" | ";" | "where"))
+            .copied()
+            .collect::<String>()
+    } else {
+        String::new()
+    };
+    Some(Signature {
+        params,
+        fallible: returns.starts_with("Result") || returns.contains("::Result"),
+        returns: !returns.is_empty() && returns != "()",
+    })
+}
+
+const CPP_SPECIFIERS: &[&str] = &[
+    "static",
+    "inline",
+    "virtual",
+    "constexpr",
+    "consteval",
+    "explicit",
+    "friend",
+    "extern",
+    "const",
+    "volatile",
+    "typename",
+];
+
+fn parse_cpp_signature(item: &str) -> Option<Signature> {
+    let item = item.trim();
+    let item = if item.starts_with("template") {
+        // skip the template parameter list
+        let mut depth = 0;
+        let end = item.char_indices().find_map(|(i, c)| match c {
+            '<' => {
+                depth += 1;
+                None
+            }
+            '>' => {
+                depth -= 1;
+                (depth == 0).then_some(i + 1)
+            }
+            _ => None,
+        })?;
+        item[end..].trim()
+    } else {
+        item
+    };
+    let first = identifiers(item).into_iter().next()?;
+    if matches!(
+        first,
+        "class"
+            | "struct"
+            | "union"
+            | "enum"
+            | "namespace"
+            | "using"
+            | "typedef"
+            | "return"
+            | "if"
+            | "for"
+            | "while"
+            | "switch"
+    ) || item.starts_with('#')
+    {
+        return None;
+    }
+    let (head, params, rest) = split_parens(item)?;
+    let head = head.trim();
+    let name = head
+        .rsplit(|c: char| c.is_whitespace() || c == '*' || c == '&')
+        .next()
+        .unwrap_or_default();
+    if name.is_empty() || name.ends_with("operator") {
+        return None;
+    }
+    let prefix = head[..head.len() - name.len()]
+        .replace("[[nodiscard]]", "")
+        .split_whitespace()
+        .filter(|token| !CPP_SPECIFIERS.contains(token))
+        // export macros such as `LUISA_API` carry no type information
+        .filter(|token| {
+            !(token.len() > 1
+                && token
+                    .chars()
+                    .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_'))
+        })
+        .collect::<Vec<_>>()
+        .join(" ");
+    let trailing = rest
+        .split_once("->")
+        .map(|(_, ret)| ret.split(['{', ';', '=']).next().unwrap_or_default().trim())
+        .unwrap_or_default();
+    let return_type = if prefix == "auto" && !trailing.is_empty() {
+        trailing
+    } else {
+        prefix.as_str()
+    };
+    let params = split_top_level(params)
+        .into_iter()
+        .filter_map(|param| {
+            let param = param.split('=').next().unwrap_or_default();
+            let tokens = identifiers(param)
+                .into_iter()
+                .filter(|token| !CPP_SPECIFIERS.contains(token))
+                .collect::<Vec<_>>();
+            // unnamed parameters only have a type
+            (tokens.len() >= 2).then(|| tokens[tokens.len() - 1].to_string())
+        })
+        .collect();
+    Some(Signature {
+        params,
+        returns: !name.starts_with('~') && !return_type.is_empty() && return_type != "void",
+        fallible: false,
+    })
+}
+
+fn parse_python_signature(item: &str) -> Option<Signature> {
+    let item = item.trim_start().trim_start_matches("async ").trim_start();
+    item.strip_prefix("def ")?;
+    let (_, params, rest) = split_parens(item)?;
+    let params = split_top_level(params)
+        .into_iter()
+        .filter_map(|param| {
+            let name = param
+                .split([':', '='])
+                .next()
+                .unwrap_or_default()
+                .trim()
+                .trim_start_matches('*');
+            is_identifier(name).then(|| name.to_string())
+        })
+        .filter(|name| name != "self" && name != "cls")
+        .collect();
+    let returns = rest
+        .split_once("->")
+        .map(|(_, ret)| ret.trim().trim_end_matches(':').trim())
+        .unwrap_or_default();
+    Some(Signature {
+        params,
+        returns: !returns.is_empty() && returns != "None",
+        fallible: false,
+    })
+}
+
+fn parse_typescript_signature(item: &str) -> Option<Signature> {
+    let (head, params, rest) = split_parens(item)?;
+    let head_tokens = identifiers(head);
+    let name = *head_tokens.last()?;
+    if head.contains('=') && !rest.contains("=>")
+        || matches!(
+            name,
+            "if" | "for" | "while" | "switch" | "return" | "catch" | "super"
+        )
+        || matches!(
+            head_tokens.first().copied(),
+            Some("class" | "interface" | "type" | "enum" | "namespace")
+        )
+    {
+        return None;
+    }
+    let params = split_top_level(params)
+        .into_iter()
+        .filter_map(|param| {
+            let name = param
+                .trim_start_matches("...")
+                .split([':', '?', '='])
+                .next()
+                .unwrap_or_default()
+                .trim();
+            // strip accessibility modifiers of constructor parameter properties
+            let name = identifiers(name).last().copied().unwrap_or_default();
+            is_identifier(name).then(|| name.to_string())
+        })
+        .filter(|name| name != "this")
+        .collect();
+    let returns = rest
+        .trim_start()
+        .strip_prefix(':')
+        .map(|ret| ret.split(['{', ';']).next().unwrap_or_default().trim())
+        .unwrap_or_default();
+    Some(Signature {
+        params,
+        returns: name != "constructor"
+            && !returns.is_empty()
+            && returns != "void"
+            && returns != "Promise<void>",
+        fallible: false,
+    })
+}
+
+fn parse_signature(language: Language, item: &str) -> Option<Signature> {
+    match language {
+        Language::Cpp => parse_cpp_signature(item),
+        Language::Rust => parse_rust_signature(item),
+        Language::Python => parse_python_signature(item),
+        Language::TypeScript => parse_typescript_signature(item),
+    }
+}
+
+fn contains_word(text: &str, word: &str) -> bool {
+    text.split(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .any(|token| token == word)
+}
+
+fn summarize(item: &str) -> String {
+    let item = item.split_whitespace().collect::<Vec<_>>().join(" ");
+    if item.chars().count() > 60 {
+        format!("{}...", item.chars().take(60).collect::<String>())
+    } else {
+        item
+    }
+}
+
+fn check_sections(profile: &StyleProfile, doc: &str, item: &str, violations: &mut Vec<String>) {
+    let signature = match parse_signature(profile.language, item) {
+        Some(signature) => signature,
+        None => return,
+    };
+    for rule in profile.sections {
+        let required = match rule.section {
+            Section::Params => !signature.params.is_empty(),
+            Section::Returns => signature.returns,
+            Section::Throws => signature.fallible,
+        };
+        if !required {
+            continue;
+        }
+        if !rule.markers.iter().any(|marker| doc.contains(marker)) {
+            violations.push(format!(
+                "`{}`: missing the {} section (`{}`)",
+                summarize(item),
+                rule.section.name(),
+                rule.markers[0]
+            ));
+        } else if rule.section == Section::Params {
+            for param in &signature.params {
+                if !contains_word(doc, param) {
+                    violations.push(format!(
+                        "`{}`: parameter `{}` is not documented",
+                        summarize(item),
+                        param
+                    ));
+                }
+            }
+        }
+    }
+}
+
+fn is_line_doc(line: &str) -> bool {
+    (line.starts_with("///") && !line.starts_with("////") && !line.starts_with("/// <"))
+        || line.starts_with("//!")
+}
+
+fn is_block_doc(line: &str) -> bool {
+    (line.starts_with("/**") && !line.starts_with("/**/")) || line.starts_with("/*!")
+}
+
+// collects the declaration following a comment, skipping attributes and joining
+// continuation lines until the signature is complete
+fn collect_item(lines: &[&str]) -> Option<String> {
+    let mut lines = lines
+        .iter()
+        .map(|line| line.trim())
+        .skip_while(|line| line.is_empty() || line.starts_with("#[") || line.starts_with('@'));
+    let mut item = lines.next()?.to_string();
+    for line in lines.take(8) {
+        if item.contains(['{', ';']) || split_parens(&item).is_some() || !item.contains('(') {
+            break;
+        }
+        item.push(' ');
+        item.push_str(line);
+    }
+    Some(item)
+}
+
+fn validate_comments(profile: &StyleProfile, text: &str) -> Vec<String> {
+    let lines = text.lines().collect::<Vec<_>>();
+    let mut violations = Vec::new();
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i].trim_start();
+        let end = if is_line_doc(line) {
+            let mut end = i;
+            while end < lines.len() && is_line_doc(lines[end].trim_start()) {
+                end += 1;
+            }
+            if let CommentSyntax::Block(open, close) = profile.syntax {
+                violations.push(format!(
+                    "`{}`: use `{} ... {}` block comments instead of line comments",
+                    summarize(line),
+                    open,
+                    close
+                ));
+            }
+            end
+        } else if is_block_doc(line) {
+            let mut end = i;
+            while end < lines.len() && !lines[end].contains("*/") {
+                end += 1;
+            }
+            if let CommentSyntax::Line(prefix) = profile.syntax {
+                violations.push(format!(
+                    "`{}`: use `{}` line comments instead of block comments",
+                    summarize(line),
+                    prefix
+                ));
+            }
+            (end + 1).min(lines.len())
+        } else {
+            i += 1;
+            continue;
+        };
+        // module-level comments do not document the following item
+        if !line.starts_with("//!")
+            && !line.starts_with("/*!")
+            && let Some(item) = collect_item(&lines[end..])
+        {
+            check_sections(profile, &lines[i..end].join("\n"), &item, &mut violations);
+        }
+        i = end;
+    }
+    violations
+}
+
+fn validate_docstrings(profile: &StyleProfile, text: &str) -> Vec<String> {
+    let lines = text.lines().collect::<Vec<_>>();
+    let mut violations = Vec::new();
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i].trim();
+        let is_definition = ["def ", "async def ", "class "]
+            .iter()
+            .any(|keyword| line.starts_with(keyword));
+        if !is_definition {
+            i += 1;
+            continue;
+        }
+        // a signature may span several lines, it ends with the colon of the header
+        let mut end = i;
+        let mut item = line.to_string();
+        while !item.trim_end().ends_with(':') && end + 1 < lines.len() {
+            end += 1;
+            item.push(' ');
+            item.push_str(lines[end].trim());
+        }
+        i = end + 1;
+        let begin = match lines[i.min(lines.len())..]
+            .iter()
+            .position(|line| !line.trim().is_empty())
+        {
+            Some(offset) => i + offset,
+            None => break,
+        };
+        let first = lines[begin].trim().trim_start_matches(['r', 'u', 'R', 'U']);
+        let quote = if first.starts_with("\"\"\"") {
+            "\"\"\""
+        } else if first.starts_with("'''") {
+            violations.push(format!(
+                "`{}`: use triple double quotes for docstrings",
+                summarize(&item)
+            ));
+            "'''"
+        } else {
+            continue;
+        };
+        // a docstring on a single line closes on the line it opens
+        let close = if first[3..].contains(quote) {
+            begin
+        } else {
+            (begin + 1..lines.len())
+                .find(|&j| lines[j].contains(quote))
+                .unwrap_or(lines.len() - 1)
+        };
+        check_sections(
+            profile,
+            &lines[begin..=close].join("\n"),
+            &item,
+            &mut violations,
+        );
+        i = close + 1;
+    }
+    violations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn validate(name: &str, text: &str) -> Vec<String> {
+        find_profile(name).unwrap().validate(text)
+    }
+
+    #[test]
+    fn rust_signature_ignores_fn_outside_the_keyword() {
+        let signature = parse_rust_signature(
+            "#[doc = \"fn x(y: u8)\"]\n#[cfg(fn )]\npub const fn size(/* fn z(w: u8) */ a: &str, mut b: Vec<(u8, u8)>) -> io::Result<()>",
+        )
+        .unwrap();
+        assert_eq!(signature.params, ["a", "b"]);
+        assert!(signature.returns);
+        assert!(signature.fallible);
+
+        let signature =
+            parse_rust_signature("fn new<T: Fn() -> u8>(&self, f: T) where T: Copy {").unwrap();
+        assert_eq!(signature.params, ["f"]);
+        assert!(!signature.returns);
+        assert!(parse_rust_signature("struct Functions;").is_none());
+    }
+
+    #[test]
+    fn rustdoc_requires_errors_of_fallible_functions() {
+        let text = "/// Reads the file.\npub fn read(path: &str) -> Result<String> {";
+        let violations = validate("rustdoc", text);
+        assert_eq!(violations.len(), 1);
+        assert!(violations[0].contains("missing the errors section"));
+        let text = "/// Reads the file.\n///\n/// # Errors\n/// When it is missing.\npub fn read(path: &str) -> Result<String> {";
+        assert!(validate("rustdoc", text).is_empty());
+    }
+
+    #[test]
+    fn crlf_line_endings_validate_like_lf() {
+        let text = "/// @brief Adds.\n/// @param a The first.\nint add(int a, int b);\n";
+        let lf = validate("doxygen", text);
+        assert_eq!(lf.len(), 2, "{:?}", lf);
+        assert_eq!(validate("doxygen", &text.replace('\n', "\r\n")), lf);
+
+        let text = "def add(a, b):\n    \"\"\"Adds.\n\n    Args:\n        a: The first.\n        b: The second.\n    \"\"\"\n";
+        assert!(validate("python-google", text).is_empty());
+        assert!(validate("python-google", &text.replace('\n', "\r\n")).is_empty());
+    }
+
+    #[test]
+    fn single_line_docstrings_close_on_their_line() {
+        let text = "def first(a):\n    \"\"\"Does nothing.\"\"\"\n\ndef second(b):\n    \"\"\"Does more.\n\n    Args:\n        b: The value.\n    \"\"\"\n";
+        let violations = validate("python-google", text);
+        assert_eq!(violations.len(), 1, "{:?}", violations);
+        assert!(violations[0].starts_with("`def first(a):`"));
+    }
+}
@@ -0,0 +1,57 @@
+// prints the schemas of a tool in the formats of the enabled features.
+// run with `cargo run -p tool_protocol --example export --features openai,anthropic,mcp`.
+
+use serde::{Deserialize, Serialize};
+use tool_protocol::{ToolProtocol, ToolSet, tool};
+
+#[derive(ToolProtocol, Serialize, Deserialize, Debug)]
+#[tool_protocol(
+    name = "search",
+    help = "Finds the files containing a text.",
+    read_only
+)]
+struct SearchToolProtocol {
+    #[tool_protocol(help = "The text to find.", example = "fn main", required)]
+    text: String,
+
+    #[tool_protocol(help = "The directory to search.", default = ".")]
+    path: String,
+
+    #[tool_protocol(help = "The most files to list.", default = 20, min = 1, max = 100)]
+    limit: u32,
+
+    #[tool_protocol(help = "The extensions of the files to search.", default = [])]
+    extensions: Vec<String>,
+}
+
+#[tool(SearchToolProtocol)]
+struct Search;
+
+impl Search {
+    fn invoke(&self, args: SearchToolProtocol) -> anyhow::Result<String> {
+        Ok(format!("Searching {} for {:?}", args.path, args.text))
+    }
+}
+
+fn print(format: &str, value: &serde_json::Value) {
+    println!(
+        "{}:\n{}\n",
+        format,
+        serde_json::to_string_pretty(value).unwrap()
+    );
+}
+
+fn main() {
+    let mut toolset = ToolSet::new();
+    toolset.register_tool(Search::create().unwrap());
+
+    for schema in toolset.get_schemas() {
+        print("JSON Schema", &schema.to_json_schema());
+    }
+    #[cfg(feature = "openai")]
+    print("OpenAI", &toolset.to_openai());
+    #[cfg(feature = "anthropic")]
+    print("Anthropic", &toolset.to_anthropic());
+    #[cfg(feature = "mcp")]
+    print("MCP", &toolset.to_mcp());
+}
@@ -0,0 +1,127 @@
+// defines two tools, calls them through a tool set and logs the calls with an observer.
+// run with `cargo run -p tool_protocol --example toolset`.
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use tool_protocol::{
+    CancellationToken, ErrorCode, ToolEnum, ToolError, ToolEvent, ToolObserver, ToolOutput,
+    ToolProtocol, ToolSet, registered_tools, tool,
+};
+
+#[derive(ToolEnum, Serialize, Deserialize, Debug, Default)]
+#[serde(rename_all = "lowercase")]
+enum Case {
+    #[default]
+    Upper,
+    Lower,
+}
+
+#[derive(ToolProtocol, Serialize, Deserialize, Debug)]
+#[tool_protocol(name = "convert", help = "Changes the case of a text.", read_only)]
+struct ConvertToolProtocol {
+    #[tool_protocol(
+        help = "The text to convert.",
+        example = "Hello",
+        alias = "input",
+        required
+    )]
+    text: String,
+
+    #[tool_protocol(help = "The case to convert to.", enum, default)]
+    case: Case,
+}
+
+#[tool(ConvertToolProtocol)]
+struct Convert;
+
+impl Convert {
+    fn invoke(&self, args: ConvertToolProtocol) -> anyhow::Result<String> {
+        Ok(match args.case {
+            Case::Upper => args.text.to_uppercase(),
+            Case::Lower => args.text.to_lowercase(),
+        })
+    }
+}
+
+#[derive(ToolProtocol, Serialize, Deserialize, Debug)]
+#[tool_protocol(name = "wait", help = "Waits and counts the waits.")]
+struct WaitToolProtocol {
+    #[tool_protocol(help = "How long to wait in milliseconds.", default = 10, max = 1000)]
+    millis: u64,
+}
+
+// an async tool with state, the other fields of a tool start from their defaults
+#[tool(WaitToolProtocol, async)]
+struct Wait {
+    waits: AtomicUsize,
+}
+
+impl Wait {
+    async fn invoke(&self, args: WaitToolProtocol) -> anyhow::Result<ToolOutput> {
+        tokio::time::sleep(Duration::from_millis(args.millis)).await;
+        let waits = self.waits.fetch_add(1, Ordering::Relaxed) + 1;
+        Ok(ToolOutput::json(json!({ "waits": waits })))
+    }
+}
+
+// logs the calls, and refuses to wait for longer than half a second
+struct Log;
+
+impl ToolObserver for Log {
+    fn notify(&self, event: &ToolEvent) {
+        match event {
+            ToolEvent::Call { id, tool, args } => eprintln!("[{}] {} {}", id, tool, args),
+            ToolEvent::Result {
+                id,
+                output,
+                elapsed,
+                ..
+            } => eprintln!(
+                "[{}] done in {:?}, error: {:?}",
+                id, elapsed, output.error_code
+            ),
+            _ => {}
+        }
+    }
+
+    fn review(&self, tool: &str, args: &serde_json::Value) -> Result<(), ToolError> {
+        match args["millis"].as_u64() {
+            Some(millis) if tool == "wait" && millis > 500 => Err(ToolError::new(
+                ErrorCode::Denied,
+                "Waiting for more than 500 ms is not allowed",
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+fn main() {
+    let mut toolset = ToolSet::new();
+    // `Convert` and `Wait`, as `#[tool]` registers them
+    toolset.register_tools(registered_tools().unwrap());
+    toolset.set_timeout("wait", Duration::from_millis(200));
+    toolset.add_observer(Arc::new(Log));
+
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&toolset.get_help()).unwrap()
+    );
+
+    let calls = [
+        ("convert", json!({ "input": "Hello", "case": "lower" })),
+        ("convert", json!({ "text": "Hello" })),
+        ("convert", json!({ "text": "Hello", "case": "title" })),
+        ("wait", json!({ "millis": "20" })),
+        ("wait", json!({ "millis": 300 })),
+        ("wait", json!({ "millis": 800 })),
+        ("unknown", json!({})),
+    ]
+    .map(|(name, args)| (name.to_string(), args));
+    let outputs = toolset.invoke_all(&calls, &CancellationToken::new());
+    for ((name, args), output) in calls.iter().zip(outputs) {
+        println!("{} {}\n{}\n", name, args, output.to_text());
+    }
+}
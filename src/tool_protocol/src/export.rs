@@ -0,0 +1,298 @@
+// this module describes tools in the formats of the APIs of model providers. each format wraps
+// the JSON Schema of the arguments, which is always available, while the formats are enabled by
+// the features `openai`, `anthropic` and `mcp`.
+
+use crate::{ToolSchema, ToolSet, validate};
+use serde_json::{Map, Value, json};
+
+// a bound as an integer if it is one, e.g. `1` rather than `1.0` for an integer argument
+fn bound(value: f64) -> Value {
+    if value.fract() == 0.0 && value.abs() < 1e15 {
+        (value as i64).into()
+    } else {
+        value.into()
+    }
+}
+
+impl ToolSchema {
+    /// The JSON Schema of an object of the arguments. Hidden arguments are left out, as the
+    /// model should not use them.
+    pub fn to_json_schema(&self) -> Value {
+        let mut properties = Map::new();
+        let mut required = Vec::new();
+        for arg in self.arguments.iter().filter(|arg| !arg.hidden) {
+            let mut property = match validate::json_schema(&arg.type_) {
+                Value::Object(property) => property,
+                _ => Map::new(),
+            };
+            // the allowed values and the bounds of a `Vec` apply to its elements
+            let mut constraints = Map::new();
+            if !arg.enum_values.is_empty() {
+                constraints.insert("enum".to_string(), arg.enum_values.clone().into());
+            }
+            if let Some(min) = arg.min {
+                constraints.insert("minimum".to_string(), bound(min));
+            }
+            if let Some(max) = arg.max {
+                constraints.insert("maximum".to_string(), bound(max));
+            }
+            let values = match property.get_mut("items") {
+                Some(Value::Object(items)) => items,
+                _ => &mut property,
+            };
+            // e.g. the values of a `ToolEnum`, which the type does not tell
+            if !values.contains_key("type")
+                && !arg.enum_values.is_empty()
+                && arg.enum_values.iter().all(Value::is_string)
+            {
+                values.insert("type".to_string(), "string".into());
+            }
+            values.extend(constraints);
+            property.insert("description".to_string(), arg.help.clone().into());
+            if let Some(default) = &arg.default {
+                property.insert("default".to_string(), default.clone());
+            }
+            if let Some(example) = &arg.example {
+                property.insert("examples".to_string(), json!([example]));
+            }
+            if arg.deprecated.is_some() {
+                property.insert("deprecated".to_string(), true.into());
+            }
+            properties.insert(arg.name.clone(), Value::Object(property));
+            if arg.required {
+                required.push(arg.name.clone());
+            }
+        }
+        json!({
+            "type": "object",
+            "properties": properties,
+            "required": required,
+        })
+    }
+
+    /// The tool as a function of the OpenAI Chat Completions API.
+    #[cfg(feature = "openai")]
+    pub fn to_openai(&self) -> Value {
+        json!({
+            "type": "function",
+            "function": {
+                "name": self.name,
+                "description": self.help,
+                "parameters": self.to_json_schema(),
+            },
+        })
+    }
+
+    /// The tool as a tool of the Anthropic Messages API.
+    #[cfg(feature = "anthropic")]
+    pub fn to_anthropic(&self) -> Value {
+        json!({
+            "name": self.name,
+            "description": self.help,
+            "input_schema": self.to_json_schema(),
+        })
+    }
+
+    /// The tool as a tool of the Model Context Protocol, as listed by `tools/list`.
+    #[cfg(feature = "mcp")]
+    pub fn to_mcp(&self) -> Value {
+        json!({
+            "name": self.name,
+            "description": self.help,
+            "inputSchema": self.to_json_schema(),
+            "annotations": { "readOnlyHint": self.read_only },
+        })
+    }
+}
+
+impl ToolSet {
+    /// The schemas of the tools, sorted by name.
+    pub fn get_schemas(&self) -> Vec<&ToolSchema> {
+        let mut schemas = self
+            .get_tools()
+            .values()
+            .map(|tool| tool.get_schema())
+            .collect::<Vec<_>>();
+        schemas.sort_by(|a, b| a.name.cmp(&b.name));
+        schemas
+    }
+
+    /// The tools as an array of functions of the OpenAI Chat Completions API, see
+    /// [`ToolSchema::to_openai`].
+    #[cfg(feature = "openai")]
+    pub fn to_openai(&self) -> Value {
+        self.get_schemas()
+            .iter()
+            .map(|schema| schema.to_openai())
+            .collect()
+    }
+
+    /// The tools as an array of tools of the Anthropic Messages API, see
+    /// [`ToolSchema::to_anthropic`].
+    #[cfg(feature = "anthropic")]
+    pub fn to_anthropic(&self) -> Value {
+        self.get_schemas()
+            .iter()
+            .map(|schema| schema.to_anthropic())
+            .collect()
+    }
+
+    /// The tools as the result of the `tools/list` request of the Model Context Protocol, see
+    /// [`ToolSchema::to_mcp`].
+    #[cfg(feature = "mcp")]
+    pub fn to_mcp(&self) -> Value {
+        json!({ "tools": self.get_schemas().iter().map(|schema| schema.to_mcp()).collect::<Vec<_>>() })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_schema() -> ToolSchema {
+        serde_json::from_value(json!({
+            "name": "search",
+            "help": "Searches the codebase.",
+            "read_only": true,
+            "arguments": [
+                {
+                    "name": "pattern",
+                    "help": "The text to search.",
+                    "type": "String",
+                    "required": true,
+                    "example": "fn main",
+                },
+                {
+                    "name": "limit",
+                    "help": "How many matches.",
+                    "type": "Option<u32>",
+                    "required": false,
+                    "default": 10,
+                    "min": 1,
+                    "max": 100.5,
+                    "deprecated": "use `max_matches` instead",
+                },
+                {
+                    "name": "mode",
+                    "help": "How to match.",
+                    "type": "Mode",
+                    "required": false,
+                    "enum": ["exact", "regex"],
+                },
+                {
+                    "name": "kinds",
+                    "help": "The kinds of files.",
+                    "type": "Vec<Kind>",
+                    "required": false,
+                    "enum": ["code", "docs"],
+                },
+                {
+                    "name": "trace",
+                    "help": "Logs the search.",
+                    "type": "bool",
+                    "required": false,
+                    "hidden": true,
+                },
+            ],
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn arguments_are_described_by_a_json_schema() {
+        assert_eq!(
+            create_schema().to_json_schema(),
+            json!({
+                "type": "object",
+                "properties": {
+                    "pattern": {
+                        "type": "string",
+                        "description": "The text to search.",
+                        "examples": ["fn main"],
+                    },
+                    "limit": {
+                        "type": "integer",
+                        "minimum": 1,
+                        "maximum": 100.5,
+                        "description": "How many matches.",
+                        "default": 10,
+                        "deprecated": true,
+                    },
+                    "mode": {
+                        "type": "string",
+                        "enum": ["exact", "regex"],
+                        "description": "How to match.",
+                    },
+                    "kinds": {
+                        "type": "array",
+                        "items": { "type": "string", "enum": ["code", "docs"] },
+                        "description": "The kinds of files.",
+                    },
+                },
+                "required": ["pattern"],
+            })
+        );
+    }
+
+    #[cfg(feature = "openai")]
+    #[test]
+    fn tools_are_exported_as_openai_functions() {
+        let schema = create_schema();
+        assert_eq!(
+            schema.to_openai(),
+            json!({
+                "type": "function",
+                "function": {
+                    "name": "search",
+                    "description": "Searches the codebase.",
+                    "parameters": schema.to_json_schema(),
+                },
+            })
+        );
+    }
+
+    #[cfg(feature = "anthropic")]
+    #[test]
+    fn tools_are_exported_as_anthropic_tools() {
+        let schema = create_schema();
+        assert_eq!(
+            schema.to_anthropic(),
+            json!({
+                "name": "search",
+                "description": "Searches the codebase.",
+                "input_schema": schema.to_json_schema(),
+            })
+        );
+    }
+
+    #[cfg(feature = "mcp")]
+    #[test]
+    fn tools_are_exported_as_mcp_tools() {
+        let schema = create_schema();
+        assert_eq!(
+            schema.to_mcp(),
+            json!({
+                "name": "search",
+                "description": "Searches the codebase.",
+                "inputSchema": schema.to_json_schema(),
+                "annotations": { "readOnlyHint": true },
+            })
+        );
+
+        struct Search(ToolSchema);
+
+        impl crate::Tool for Search {
+            fn get_schema(&self) -> &ToolSchema {
+                &self.0
+            }
+
+            fn invoke<'a>(&'a self, _args: &'a Value) -> crate::ToolFuture<'a> {
+                Box::pin(async { crate::ToolOutput::text("") })
+            }
+        }
+
+        let mut toolset = ToolSet::new();
+        toolset.register_tool(Box::new(Search(create_schema())));
+        assert_eq!(toolset.to_mcp(), json!({ "tools": [schema.to_mcp()] }));
+    }
+}
@@ -1,19 +1,113 @@
+//! Tools for LLMs defined by Rust structs.
+//!
+//! The arguments of a tool are a struct deriving [`ToolProtocol`], whose attributes give the
+//! help, defaults and bounds the model sees. `#[tool]` turns a struct with an `invoke` method
+//! into a [`Tool`], which validates the arguments of a call, coerces the values a model often
+//! gets slightly wrong, and returns a [`ToolOutput`]:
+//!
+//! ```
+//! use serde::{Deserialize, Serialize};
+//! use tool_protocol::{Tool, ToolOutput, ToolProtocol, ToolSet, tool};
+//!
+//! #[derive(ToolProtocol, Serialize, Deserialize, Debug)]
+//! #[tool_protocol(name = "add", help = "Adds two numbers.", read_only)]
+//! struct AddToolProtocol {
+//!     #[tool_protocol(help = "The first number.", example = 1, required)]
+//!     a: i64,
+//!     #[tool_protocol(help = "The second number.", default = 1)]
+//!     b: i64,
+//! }
+//!
+//! #[tool(AddToolProtocol)]
+//! struct Add;
+//!
+//! impl Add {
+//!     fn invoke(&self, args: AddToolProtocol) -> anyhow::Result<String> {
+//!         Ok((args.a + args.b).to_string())
+//!     }
+//! }
+//!
+//! let mut toolset = ToolSet::new();
+//! toolset.register_tool(Add::create()?);
+//! let output = toolset.invoke("add", &serde_json::json!({ "a": "2" }));
+//! assert_eq!(output, ToolOutput::text("3"));
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+//!
+//! A [`ToolSet`] calls tools by name with timeouts and cancellation, and reports the calls to
+//! its [`ToolObserver`]s. Async code calls [`ToolSet::invoke_async`], as the blocking
+//! [`ToolSet::invoke`] and [`ToolSet::invoke_all`] return an error inside an async runtime.
+//! Every `#[tool]` is also listed by [`registered_tools`].
+//!
+//! The features `openai`, `anthropic` and `mcp` describe the tools in the formats of these APIs,
+//! e.g. `ToolSet::to_openai`. `derive`, enabled by default, provides the macros.
+
+#![warn(missing_docs)]
+
+mod export;
+mod output;
+mod toolset;
+mod validate;
+
+pub use output::{ContentPart, ErrorCode, ToolError, ToolOutput, Truncation};
+pub use tokio_util::sync::CancellationToken;
+#[cfg(feature = "derive")]
+pub use tool_protocol_derive::{ToolEnum, ToolProtocol, tool};
+pub use toolset::{DEFAULT_TOOL_TIMEOUT, ToolEvent, ToolObserver, ToolSet};
+
+// the code generated by `tool_protocol_derive` refers to these, so that tools need not depend on
+// them
+pub use anyhow;
+pub use inventory;
+pub use serde_json;
+
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
-use serde_json;
+use std::future::Future;
+use std::pin::Pin;
 
+/// An argument of a tool, usually a field of a struct deriving [`ToolProtocol`].
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ToolArgument {
+    /// The name the model passes the argument by.
     pub name: String,
+    /// What the argument means, shown to the model.
     pub help: String,
+    /// The Rust type of the argument, e.g. `Option<u32>`, which decides how values are coerced.
     #[serde(rename = "type")]
     pub type_: String,
+    /// Whether a call must give the argument.
     pub required: bool,
-    pub default: serde_json::Value,
-    pub example: serde_json::Value,
+    /// Filled in when the argument is missing, which may be `null` for an `Option`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<serde_json::Value>,
+    /// A value shown in the example call of the tool.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub example: Option<serde_json::Value>,
+    /// The allowed values, any value of the type if empty.
+    #[serde(rename = "enum", default, skip_serializing_if = "Vec::is_empty")]
+    pub enum_values: Vec<serde_json::Value>,
+    /// The smallest allowed number.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min: Option<f64>,
+    /// The largest allowed number.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max: Option<f64>,
+    /// Other names accepted for the argument, as models often guess them, e.g. `file` for
+    /// `path`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub aliases: Vec<String>,
+    /// The reason the argument should not be used any more, empty if none is given.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deprecated: Option<String>,
+    /// Whether the argument is accepted but not shown to the model.
+    #[serde(default)]
+    pub hidden: bool,
 }
 
 impl ToolArgument {
+    /// Describes the argument to the model: its type, whether it is required, and its default,
+    /// example, allowed values and bounds when it has them.
     pub fn get_help(&self) -> serde_json::Value {
         let mut help = serde_json::Map::new();
         help.insert(
@@ -24,34 +118,92 @@ impl ToolArgument {
             "required".to_string(),
             serde_json::Value::Bool(self.required),
         );
-        if !self.default.is_null() {
-            help.insert("default".to_string(), self.default.clone());
+        if let Some(default) = &self.default {
+            help.insert("default".to_string(), default.clone());
         }
-        if !self.example.is_null() {
-            help.insert("example".to_string(), self.example.clone());
+        if let Some(example) = &self.example {
+            help.insert("example".to_string(), example.clone());
+        }
+        if !self.enum_values.is_empty() {
+            help.insert("enum".to_string(), self.enum_values.clone().into());
+        }
+        if let Some(min) = self.min {
+            help.insert("minimum".to_string(), min.into());
+        }
+        if let Some(max) = self.max {
+            help.insert("maximum".to_string(), max.into());
+        }
+        if let Some(reason) = &self.deprecated {
+            help.insert("deprecated".to_string(), reason.as_str().into());
         }
         serde_json::Value::Object(help)
     }
+
+    fn is_shown(&self) -> bool {
+        !self.hidden
+    }
+
+    // e.g. `- depth (u32, at least 0): The maximum depth.` for the list of expected arguments
+    fn get_summary(&self) -> String {
+        let mut notes = vec![self.type_.clone()];
+        if self.required {
+            notes.push("required".to_string());
+        }
+        if self.deprecated.is_some() {
+            notes.push("deprecated".to_string());
+        }
+        if !self.enum_values.is_empty() {
+            let values = self
+                .enum_values
+                .iter()
+                .map(|value| value.to_string())
+                .collect::<Vec<_>>();
+            notes.push(format!("one of {}", values.join(", ")));
+        }
+        if let Some(min) = self.min {
+            notes.push(format!("at least {}", min));
+        }
+        if let Some(max) = self.max {
+            notes.push(format!("at most {}", max));
+        }
+        format!("- {} ({}): {}", self.name, notes.join(", "), self.help)
+    }
+}
+
+/// The values allowed for a Rust enum, see `#[derive(ToolEnum)]`.
+pub trait ToolEnum {
+    /// The values serde reads for the variants, in the order of the variants.
+    fn values() -> Vec<serde_json::Value>;
 }
 
+/// The name, help and arguments of a tool, usually created by `#[derive(ToolProtocol)]`.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ToolSchema {
+    /// The name the tool is called by.
     pub name: String,
+    /// What the tool does, shown to the model.
     pub help: String,
+    /// The arguments in the order they are shown.
     pub arguments: Vec<ToolArgument>,
+    /// Whether the tool only reads, so that several calls of it may run in parallel.
+    #[serde(default)]
+    pub read_only: bool,
 }
 
 impl ToolSchema {
+    /// An example of the arguments, made of the example or default of each argument shown to
+    /// the model. Deprecated arguments are left out.
     pub fn get_example(&self) -> serde_json::Value {
         let mut example = serde_json::Map::new();
-        for arg in &self.arguments {
-            let value = if !arg.example.is_null() {
-                arg.example.clone()
-            } else if !arg.default.is_null() {
-                arg.default.clone()
-            } else {
-                assert!(!arg.required);
-                serde_json::Value::Null
+        for arg in self.arguments.iter().filter(|arg| arg.is_shown()) {
+            if arg.deprecated.is_some() {
+                continue;
+            }
+            // a required argument of a hand-written schema may have neither
+            let value = match (&arg.example, &arg.default) {
+                (Some(value), _) | (None, Some(value)) => value.clone(),
+                (None, None) if arg.required => format!("<{}>", arg.type_).into(),
+                (None, None) => continue,
             };
             if !value.is_null() {
                 example.insert(arg.name.clone(), value);
@@ -60,11 +212,60 @@ impl ToolSchema {
         serde_json::Value::Object(example)
     }
 
+    /// The error for invalid arguments, which lists the problems and tells the model the
+    /// expected arguments with an example.
+    pub fn argument_error(&self, problems: &[String]) -> anyhow::Error {
+        let expected = self
+            .arguments
+            .iter()
+            .filter(|arg| arg.is_shown())
+            .map(ToolArgument::get_summary)
+            .collect::<Vec<_>>()
+            .join("\n");
+        ToolError::new(
+            ErrorCode::InvalidArguments,
+            format!(
+                "Invalid arguments of `{}`:\n{}\nThe expected arguments are:\n{}\nFor example: {}",
+                self.name,
+                problems
+                    .iter()
+                    .map(|problem| format!("- {}", problem))
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+                expected,
+                self.get_example()
+            ),
+        )
+        .into()
+    }
+
+    /// Notes telling the model which deprecated arguments the call gives, by name or by alias,
+    /// so that it stops using them.
+    pub fn get_deprecation_notes(&self, args: &serde_json::Value) -> Vec<String> {
+        let Some(args) = args.as_object() else {
+            return Vec::new();
+        };
+        args.keys()
+            .filter_map(|name| {
+                let arg = self.arguments.iter().find(|arg| {
+                    &arg.name == name || arg.aliases.iter().any(|alias| alias == name)
+                })?;
+                let reason = arg.deprecated.as_deref()?;
+                Some(if reason.is_empty() {
+                    format!("(The argument `{}` is deprecated.)", name)
+                } else {
+                    format!("(The argument `{}` is deprecated: {})", name, reason)
+                })
+            })
+            .collect()
+    }
+
+    /// Describes the tool to the model: an example call and the help of each shown argument.
     pub fn get_help(&self) -> serde_json::Value {
         let mut help = serde_json::Map::new();
         help.insert("example".to_string(), self.get_example());
         let mut args = serde_json::Map::new();
-        for arg in &self.arguments {
+        for arg in self.arguments.iter().filter(|arg| arg.is_shown()) {
             args.insert(arg.name.clone(), arg.get_help());
         }
         help.insert("arguments".to_string(), serde_json::Value::Object(args));
@@ -72,39 +273,144 @@ impl ToolSchema {
     }
 }
 
+/// Checks the arguments against the schema and returns them with the defaults filled in.
+///
+/// Aliases are replaced by the names of the arguments, and values a model often gets slightly
+/// wrong, such as numbers in strings, are coerced to the types of the arguments.
+/// Deprecated arguments are still accepted, so that old calls keep working. [`ToolSet`] tells
+/// the model about them in the output, see [`ToolSchema::get_deprecation_notes`].
+///
+/// # Errors
+///
+/// Unknown, repeated and missing arguments and invalid values are all reported in one
+/// [`ToolError`] with [`ErrorCode::InvalidArguments`], see [`ToolSchema::argument_error`].
 pub fn canonicalize_tool_args(
     schema: &ToolSchema,
     args: &serde_json::Value,
 ) -> Result<serde_json::Value> {
+    let empty = serde_json::Map::new();
+    let args = match args {
+        serde_json::Value::Object(args) => args,
+        serde_json::Value::Null => &empty,
+        _ => return Err(schema.argument_error(&[format!("expected an object, got {}", args)])),
+    };
+    let mut problems = Vec::new();
+    // the values by the names of the schema, with aliases replaced
+    let mut given = serde_json::Map::new();
+    for (name, value) in args {
+        let arg = schema
+            .arguments
+            .iter()
+            .find(|arg| &arg.name == name || arg.aliases.iter().any(|alias| alias == name));
+        match arg {
+            Some(arg) if given.contains_key(&arg.name) => problems.push(format!(
+                "`{}` is given more than once, also as one of {:?}",
+                arg.name, arg.aliases
+            )),
+            Some(arg) => {
+                given.insert(arg.name.clone(), value.clone());
+            }
+            None => problems.push(format!("unknown argument `{}`", name)),
+        }
+    }
     let mut cargs = serde_json::Map::new();
     for arg in &schema.arguments {
-        if let Some(value) = args.get(&arg.name) {
-            cargs.insert(arg.name.clone(), value.clone());
-        } else if !arg.default.is_null() {
-            cargs.insert(arg.name.clone(), arg.default.clone());
+        if let Some(value) = given.get(&arg.name) {
+            match validate::coerce(&arg.type_, value).and_then(|value| validate::check(arg, value))
+            {
+                Ok(value) => {
+                    cargs.insert(arg.name.clone(), value);
+                }
+                Err(problem) => problems.push(format!("`{}` {}", arg.name, problem)),
+            }
+        } else if let Some(default) = &arg.default {
+            cargs.insert(arg.name.clone(), default.clone());
         } else if arg.required {
-            return Err(anyhow::anyhow!("Missing required argument: {}", arg.name));
+            problems.push(format!("missing required argument `{}`", arg.name));
         }
     }
+    if !problems.is_empty() {
+        return Err(schema.argument_error(&problems));
+    }
     Ok(serde_json::Value::Object(cargs))
 }
 
+/// The arguments of a tool, implemented by `#[derive(ToolProtocol)]`.
 pub trait ToolProtocol<T> {
-    fn create_schema() -> ToolSchema;
+    /// Creates the schema of the arguments.
+    ///
+    /// # Errors
+    ///
+    /// Fails when a default given by an expression or by `Default` cannot be converted to JSON.
+    fn create_schema() -> Result<ToolSchema>;
+
+    /// Reads the arguments of a call, after checking them with [`canonicalize_tool_args`].
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`ErrorCode::InvalidArguments`] when the arguments do not match the schema.
     fn parse_args(schema: &ToolSchema, args: &serde_json::Value) -> Result<T>
     where
         T: Sized;
 }
 
-pub fn create_schema<T: ToolProtocol<T>>() -> ToolSchema {
+/// Creates the schema of the arguments `T`, see [`ToolProtocol::create_schema`].
+///
+/// # Errors
+///
+/// Fails when a default of `T` cannot be converted to JSON.
+pub fn create_schema<T: ToolProtocol<T>>() -> Result<ToolSchema> {
     T::create_schema()
 }
 
+/// Reads the arguments `T` of a call, see [`ToolProtocol::parse_args`].
+///
+/// # Errors
+///
+/// Fails with [`ErrorCode::InvalidArguments`] when the arguments do not match the schema.
 pub fn parse_args<T: ToolProtocol<T>>(schema: &ToolSchema, args: &serde_json::Value) -> Result<T> {
     T::parse_args(schema, args)
 }
 
-pub trait Tool {
+/// The result of a call. Dropping it cancels the call at its next await, and a failed call
+/// resolves to an output with `is_error` set.
+pub type ToolFuture<'a> = Pin<Box<dyn Future<Output = ToolOutput> + Send + 'a>>;
+
+/// A tool the model can call, usually implemented by `#[tool]`.
+///
+/// Tools are shared between threads, so a tool with state keeps it behind a lock.
+pub trait Tool: Send + Sync {
+    /// The schema of the tool, which names it and describes its arguments.
     fn get_schema(&self) -> &ToolSchema;
-    fn invoke(&mut self, args: &serde_json::Value) -> Result<String>;
+
+    /// Calls the tool with arguments not checked yet.
+    fn invoke<'a>(&'a self, args: &'a serde_json::Value) -> ToolFuture<'a>;
+
+    /// Whether the call blocks its thread. A blocking call then runs on a thread of its own
+    /// and cannot be interrupted, so a timed out or cancelled call only stops being waited for.
+    fn is_blocking(&self) -> bool {
+        true
+    }
+}
+
+/// A tool registered by `#[tool]`, see [`registered_tools`].
+pub struct ToolRegistration {
+    /// Creates the tool.
+    pub create: fn() -> Result<Box<dyn Tool>>,
+}
+
+inventory::collect!(ToolRegistration);
+
+/// Creates each tool registered by `#[tool]` anywhere in the program, sorted by name.
+///
+/// # Errors
+///
+/// Fails when the schema of a tool cannot be created, see [`ToolProtocol::create_schema`].
+pub fn registered_tools() -> Result<Vec<Box<dyn Tool>>> {
+    let mut tools = inventory::iter::<ToolRegistration>
+        .into_iter()
+        .map(|registration| (registration.create)())
+        .collect::<Result<Vec<_>>>()?;
+    tools.sort_by(|a, b| a.get_schema().name.cmp(&b.get_schema().name));
+    Ok(tools)
 }
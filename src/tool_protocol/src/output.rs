@@ -0,0 +1,281 @@
+// this module defines the result of a tool call. a result has parts of text, JSON and references
+// to files, and tells whether the call failed, with a code for programs, and whether the tool
+// left something out.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// A part of the result of a call.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ContentPart {
+    /// Text for the model.
+    Text {
+        /// The text.
+        text: String,
+    },
+    /// A JSON value, shown to the model pretty-printed.
+    Json {
+        /// The value.
+        value: serde_json::Value,
+    },
+    /// A file the result is about, e.g. the file that was read.
+    File {
+        /// The path of the file.
+        path: String,
+    },
+}
+
+/// Why a call failed, for programs handling the result.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    /// The arguments do not match the schema of the tool.
+    InvalidArguments,
+    /// No tool has the name.
+    UnknownTool,
+    /// The request of the model contains no tool call that can be parsed.
+    InvalidCall,
+    /// A file or other resource does not exist.
+    NotFound,
+    /// The tool may not access a file or other resource.
+    PermissionDenied,
+    /// An I/O error other than the ones above.
+    Io,
+    /// The call did not finish in time.
+    Timeout,
+    /// The call was cancelled.
+    Cancelled,
+    /// The tool is not allowed in the run.
+    Denied,
+    /// The user rejected the call.
+    Rejected,
+    /// Any other failure.
+    Failed,
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let code = serde_json::to_value(self).unwrap_or_default();
+        write!(f, "{}", code.as_str().unwrap_or("failed"))
+    }
+}
+
+/// An error with a code. Tools return it through `anyhow` to keep the code, see
+/// [`ToolOutput::from`].
+#[derive(Debug)]
+pub struct ToolError {
+    /// Why the call failed.
+    pub code: ErrorCode,
+    /// The message for the model.
+    pub message: String,
+}
+
+impl ToolError {
+    /// Creates an error with a code and a message.
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for ToolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for ToolError {}
+
+/// What was left out of a result, counted in the units of the tool, e.g. lines or matches.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Truncation {
+    /// How many units are in the result.
+    pub shown: usize,
+    /// How many units there are in all, unknown when the tool stopped early.
+    pub total: Option<usize>,
+}
+
+/// The result of a call.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolOutput {
+    /// The parts of the result, in the order they are shown.
+    pub content: Vec<ContentPart>,
+    /// Whether the call failed, the content then explains why.
+    #[serde(default)]
+    pub is_error: bool,
+    /// Why the call failed, set with `is_error`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error_code: Option<ErrorCode>,
+    /// What the tool left out of the result, if anything.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub truncated: Option<Truncation>,
+}
+
+impl ToolOutput {
+    /// A successful result of the parts.
+    pub fn new(content: Vec<ContentPart>) -> Self {
+        Self {
+            content,
+            is_error: false,
+            error_code: None,
+            truncated: None,
+        }
+    }
+
+    /// A successful result of text.
+    pub fn text(text: impl Into<String>) -> Self {
+        Self::new(vec![ContentPart::Text { text: text.into() }])
+    }
+
+    /// A successful result of a JSON value.
+    pub fn json(value: serde_json::Value) -> Self {
+        Self::new(vec![ContentPart::Json { value }])
+    }
+
+    /// A failed result with a code and a message.
+    pub fn error(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            is_error: true,
+            error_code: Some(code),
+            ..Self::text(message)
+        }
+    }
+
+    /// Adds a reference to the file before the other parts.
+    pub fn with_file(mut self, path: impl Into<String>) -> Self {
+        self.content
+            .insert(0, ContentPart::File { path: path.into() });
+        self
+    }
+
+    /// Marks the result as truncated, see [`Truncation`].
+    pub fn with_truncation(mut self, shown: usize, total: Option<usize>) -> Self {
+        self.truncated = Some(Truncation { shown, total });
+        self
+    }
+
+    /// The result as text for the model. The parts are joined by lines, and errors start with
+    /// `Error: `.
+    pub fn to_text(&self) -> String {
+        let text = self
+            .content
+            .iter()
+            .map(|part| match part {
+                ContentPart::Text { text } => text.clone(),
+                ContentPart::Json { value } => {
+                    serde_json::to_string_pretty(value).unwrap_or_default()
+                }
+                ContentPart::File { path } => format!("FILE: {}", path),
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        if self.is_error {
+            format!("Error: {}", text)
+        } else {
+            text
+        }
+    }
+}
+
+impl From<String> for ToolOutput {
+    fn from(text: String) -> Self {
+        Self::text(text)
+    }
+}
+
+impl From<&str> for ToolOutput {
+    fn from(text: &str) -> Self {
+        Self::text(text)
+    }
+}
+
+impl From<serde_json::Value> for ToolOutput {
+    fn from(value: serde_json::Value) -> Self {
+        Self::json(value)
+    }
+}
+
+/// A failed result with the message of the error and its causes. The code is taken from a
+/// [`ToolError`] or an I/O error in the chain of the error, and is [`ErrorCode::Failed`]
+/// otherwise.
+impl From<anyhow::Error> for ToolOutput {
+    fn from(error: anyhow::Error) -> Self {
+        let code = error
+            .chain()
+            .find_map(|cause| {
+                if let Some(error) = cause.downcast_ref::<ToolError>() {
+                    Some(error.code)
+                } else {
+                    cause
+                        .downcast_ref::<std::io::Error>()
+                        .map(|error| match error.kind() {
+                            std::io::ErrorKind::NotFound => ErrorCode::NotFound,
+                            std::io::ErrorKind::PermissionDenied => ErrorCode::PermissionDenied,
+                            _ => ErrorCode::Io,
+                        })
+                }
+            })
+            .unwrap_or(ErrorCode::Failed);
+        Self::error(code, format!("{:#}", error))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+
+    fn code_of(error: anyhow::Error) -> Option<ErrorCode> {
+        ToolOutput::from(error).error_code
+    }
+
+    #[test]
+    fn errors_take_the_code_of_any_cause() {
+        let denied = anyhow::Error::from(ToolError::new(ErrorCode::Denied, "not allowed"));
+        let output = ToolOutput::from(denied.context("Cannot call `shell`"));
+        assert!(output.is_error);
+        assert_eq!(output.error_code, Some(ErrorCode::Denied));
+        assert_eq!(output.to_text(), "Error: Cannot call `shell`: not allowed");
+
+        let io = |kind| Err::<(), _>(std::io::Error::from(kind)).context("Cannot read a.rs");
+        assert_eq!(
+            code_of(io(std::io::ErrorKind::NotFound).unwrap_err()),
+            Some(ErrorCode::NotFound)
+        );
+        assert_eq!(
+            code_of(io(std::io::ErrorKind::PermissionDenied).unwrap_err()),
+            Some(ErrorCode::PermissionDenied)
+        );
+        assert_eq!(
+            code_of(io(std::io::ErrorKind::UnexpectedEof).unwrap_err()),
+            Some(ErrorCode::Io)
+        );
+        assert_eq!(
+            code_of(anyhow::anyhow!("something else")),
+            Some(ErrorCode::Failed)
+        );
+    }
+
+    #[test]
+    fn parts_are_joined_by_lines() {
+        let output = ToolOutput::new(vec![
+            ContentPart::Text {
+                text: "found".to_string(),
+            },
+            ContentPart::Json {
+                value: serde_json::json!({ "line": 1 }),
+            },
+        ])
+        .with_file("src/a.rs");
+        assert_eq!(
+            output.to_text(),
+            "FILE: src/a.rs\nfound\n{\n  \"line\": 1\n}"
+        );
+
+        let output = ToolOutput::error(ErrorCode::Timeout, "too slow").with_file("src/a.rs");
+        assert_eq!(output.to_text(), "Error: FILE: src/a.rs\ntoo slow");
+    }
+}
@@ -0,0 +1,422 @@
+// this module implements the tool set, which calls tools by name. each call runs with a timeout,
+// can be cancelled, and is reported to the observers of the tool set. `invoke_async` and
+// `invoke_all_async` run on the runtime of the caller, while `invoke` and `invoke_all` block on a
+// runtime of the tool set created on the first call.
+
+use crate::{ContentPart, ErrorCode, Tool, ToolError, ToolOutput};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
+use tokio::runtime::Runtime;
+use tokio_util::sync::CancellationToken;
+
+/// How long a call may take unless the tool has a timeout of its own.
+pub const DEFAULT_TOOL_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// An event of the calls of a tool set, seen by its [`ToolObserver`]s.
+#[non_exhaustive]
+pub enum ToolEvent<'a> {
+    /// A call is about to start.
+    Call {
+        /// Tells the calls apart, e.g. those running in parallel.
+        id: u64,
+        /// The name of the tool.
+        tool: &'a str,
+        /// The arguments of the call.
+        args: &'a serde_json::Value,
+    },
+    /// A call finished, failed, timed out, was cancelled or was refused.
+    Result {
+        /// The id of the [`ToolEvent::Call`] of the call.
+        id: u64,
+        /// The name of the tool.
+        tool: &'a str,
+        /// The result of the call.
+        output: &'a ToolOutput,
+        /// How long the call took.
+        elapsed: Duration,
+    },
+}
+
+/// An observer added to a tool set, e.g. for logging or metrics, sees every call.
+pub trait ToolObserver: Send + Sync {
+    /// Called for each event of the calls of the tool set.
+    fn notify(&self, event: &ToolEvent);
+
+    /// Called before each call. The returned error is the output of the call, and the tool is
+    /// then not called.
+    fn review(&self, _tool: &str, _args: &serde_json::Value) -> Result<(), ToolError> {
+        Ok(())
+    }
+}
+
+/// Calls tools by name, each with a timeout, and reports the calls to its observers.
+///
+/// Async code calls [`ToolSet::invoke_async`] and [`ToolSet::invoke_all_async`], which run on
+/// the runtime of the caller. The blocking [`ToolSet::invoke`] and [`ToolSet::invoke_all`] run
+/// on a runtime of the tool set created on the first call, and fail inside an async runtime.
+pub struct ToolSet {
+    tools: HashMap<String, Arc<dyn Tool>>,
+    timeouts: HashMap<String, Duration>,
+    default_timeout: Duration,
+    observers: Vec<Arc<dyn ToolObserver>>,
+    next_call_id: AtomicU64,
+    // only created for the blocking calls, so that a tool set can be used from any thread
+    async_runtime: OnceLock<Runtime>,
+}
+
+impl Default for ToolSet {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ToolSet {
+    /// An empty tool set.
+    pub fn new() -> Self {
+        Self {
+            tools: HashMap::new(),
+            timeouts: HashMap::new(),
+            default_timeout: DEFAULT_TOOL_TIMEOUT,
+            observers: Vec::new(),
+            next_call_id: AtomicU64::new(0),
+            async_runtime: OnceLock::new(),
+        }
+    }
+
+    // runs the future on the runtime of the tool set. a runtime cannot block inside another, so
+    // this fails instead when it is called from async code, which should use the async calls.
+    fn block_on<F: Future>(&self, future: F) -> Result<F::Output, ToolError> {
+        if tokio::runtime::Handle::try_current().is_ok() {
+            return Err(ToolError::new(
+                ErrorCode::Failed,
+                "The tool set cannot block inside an async runtime, use `invoke_async` instead",
+            ));
+        }
+        let runtime = match self.async_runtime.get() {
+            Some(runtime) => runtime,
+            None => {
+                let runtime = Runtime::new().map_err(|e| {
+                    ToolError::new(
+                        ErrorCode::Failed,
+                        format!("Failed to create the tool runtime: {}", e),
+                    )
+                })?;
+                self.async_runtime.get_or_init(|| runtime)
+            }
+        };
+        Ok(runtime.block_on(future))
+    }
+
+    /// Adds the tool, replacing a tool of the same name.
+    pub fn register_tool(&mut self, tool: Box<dyn Tool>) {
+        let name = tool.get_schema().name.clone();
+        self.tools.insert(name, Arc::from(tool));
+    }
+
+    /// Adds each of the tools, see [`ToolSet::register_tool`].
+    pub fn register_tools<T: IntoIterator<Item = Box<dyn Tool>>>(&mut self, tools: T) {
+        for tool in tools {
+            self.register_tool(tool);
+        }
+    }
+
+    /// The tools by name.
+    pub fn get_tools(&self) -> &HashMap<String, Arc<dyn Tool>> {
+        &self.tools
+    }
+
+    /// Sets how long a call of the tool may take.
+    pub fn set_timeout(&mut self, name: &str, timeout: Duration) {
+        self.timeouts.insert(name.to_string(), timeout);
+    }
+
+    /// Sets how long a call may take unless its tool has a timeout of its own, by default
+    /// [`DEFAULT_TOOL_TIMEOUT`].
+    pub fn set_default_timeout(&mut self, timeout: Duration) {
+        self.default_timeout = timeout;
+    }
+
+    /// How long a call of the tool may take.
+    pub fn get_timeout(&self, name: &str) -> Duration {
+        self.timeouts
+            .get(name)
+            .copied()
+            .unwrap_or(self.default_timeout)
+    }
+
+    /// Adds an observer, which sees the calls made from then on.
+    pub fn add_observer(&mut self, observer: Arc<dyn ToolObserver>) {
+        self.observers.push(observer);
+    }
+
+    fn notify(&self, event: ToolEvent) {
+        for observer in &self.observers {
+            observer.notify(&event);
+        }
+    }
+
+    /// Describes the tools to the model, see [`crate::ToolSchema::get_help`].
+    pub fn get_help(&self) -> serde_json::Value {
+        let mut help = serde_json::Map::new();
+        for (name, tool) in &self.tools {
+            help.insert(name.clone(), tool.get_schema().get_help());
+        }
+        serde_json::Value::Object(help)
+    }
+
+    /// Calls the tool and blocks until the call finishes or times out, see
+    /// [`ToolSet::invoke_async`].
+    ///
+    /// Inside an async runtime, where a runtime cannot block, the output is an error with
+    /// [`ErrorCode::Failed`] instead.
+    pub fn invoke(&self, name: &str, args: &serde_json::Value) -> ToolOutput {
+        self.block_on(self.invoke_async(name, args, &CancellationToken::new()))
+            .unwrap_or_else(|error| ToolOutput::error(error.code, error.message))
+    }
+
+    /// Runs the call until it finishes, times out or `cancel` is cancelled. The observers see
+    /// the call and its result, and may refuse it.
+    ///
+    /// Failures are returned as outputs with `is_error` set, e.g. [`ErrorCode::UnknownTool`],
+    /// [`ErrorCode::Timeout`] and [`ErrorCode::Cancelled`].
+    pub async fn invoke_async(
+        &self,
+        name: &str,
+        args: &serde_json::Value,
+        cancel: &CancellationToken,
+    ) -> ToolOutput {
+        let id = self.next_call_id.fetch_add(1, Ordering::Relaxed);
+        self.notify(ToolEvent::Call {
+            id,
+            tool: name,
+            args,
+        });
+        let start = Instant::now();
+        let refusal = self
+            .observers
+            .iter()
+            .find_map(|observer| observer.review(name, args).err());
+        let output = match refusal {
+            Some(error) => ToolOutput::error(error.code, error.message),
+            None => self.run(name, args, cancel).await,
+        };
+        self.notify(ToolEvent::Result {
+            id,
+            tool: name,
+            output: &output,
+            elapsed: start.elapsed(),
+        });
+        output
+    }
+
+    async fn run(
+        &self,
+        name: &str,
+        args: &serde_json::Value,
+        cancel: &CancellationToken,
+    ) -> ToolOutput {
+        let tool = match self.tools.get(name) {
+            Some(tool) => tool.clone(),
+            None => {
+                return ToolOutput::error(
+                    ErrorCode::UnknownTool,
+                    format!("Unknown tool: {}", name),
+                );
+            }
+        };
+        let notes = tool.get_schema().get_deprecation_notes(args);
+        let args = args.clone();
+        let timeout = self.get_timeout(name);
+        let task = if tool.is_blocking() {
+            let runtime = tokio::runtime::Handle::current();
+            tokio::task::spawn_blocking(move || runtime.block_on(tool.invoke(&args)))
+        } else {
+            tokio::spawn(async move { tool.invoke(&args).await })
+        };
+        let abort = task.abort_handle();
+        let mut result = tokio::select! {
+            result = task => result.unwrap_or_else(|e| {
+                ToolOutput::error(ErrorCode::Failed, format!("The tool `{}` failed: {}", name, e))
+            }),
+            _ = tokio::time::sleep(timeout) => ToolOutput::error(
+                ErrorCode::Timeout,
+                format!(
+                    "The tool `{}` did not finish within {} seconds",
+                    name,
+                    timeout.as_secs_f64()
+                ),
+            ),
+            _ = cancel.cancelled() => ToolOutput::error(
+                ErrorCode::Cancelled,
+                format!("The call of `{}` was cancelled", name),
+            ),
+        };
+        // a blocking call is left to finish on its thread
+        abort.abort();
+        if !result.is_error {
+            for (i, note) in notes.into_iter().enumerate() {
+                result.content.insert(i, ContentPart::Text { text: note });
+            }
+        }
+        result
+    }
+
+    fn is_read_only(&self, name: &str) -> bool {
+        self.tools
+            .get(name)
+            .is_some_and(|tool| tool.get_schema().read_only)
+    }
+
+    /// Runs the calls and blocks until they finish, see [`ToolSet::invoke_all_async`].
+    ///
+    /// Inside an async runtime, where a runtime cannot block, every output is an error with
+    /// [`ErrorCode::Failed`] instead.
+    pub fn invoke_all(
+        &self,
+        calls: &[(String, serde_json::Value)],
+        cancel: &CancellationToken,
+    ) -> Vec<ToolOutput> {
+        self.block_on(self.invoke_all_async(calls, cancel))
+            .unwrap_or_else(|error| {
+                calls
+                    .iter()
+                    .map(|_| ToolOutput::error(error.code, error.message.clone()))
+                    .collect()
+            })
+    }
+
+    /// Runs the calls and returns their results in order.
+    ///
+    /// Consecutive calls of read-only tools run in parallel, and any other call runs alone after
+    /// the calls before it. The calls which have not finished when `cancel` is cancelled fail.
+    pub async fn invoke_all_async(
+        &self,
+        calls: &[(String, serde_json::Value)],
+        cancel: &CancellationToken,
+    ) -> Vec<ToolOutput> {
+        let mut results = Vec::with_capacity(calls.len());
+        let mut rest = calls;
+        while let Some((name, args)) = rest.first() {
+            let parallel = rest
+                .iter()
+                .take_while(|(name, _)| self.is_read_only(name))
+                .count();
+            if parallel <= 1 {
+                results.push(self.invoke_async(name, args, cancel).await);
+                rest = &rest[1..];
+                continue;
+            }
+            let (batch, next) = rest.split_at(parallel);
+            results.extend(
+                futures_util::future::join_all(
+                    batch
+                        .iter()
+                        .map(|(name, args)| self.invoke_async(name, args, cancel)),
+                )
+                .await,
+            );
+            rest = next;
+        }
+        results
+    }
+}
+
+impl Drop for ToolSet {
+    // dropping a runtime blocks, which panics in async code, and would wait for the blocking
+    // calls left to finish on their threads
+    fn drop(&mut self) {
+        if let Some(runtime) = self.async_runtime.take() {
+            runtime.shutdown_background();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blocking_calls_fail_inside_an_async_runtime() {
+        let toolset = ToolSet::new();
+        assert_eq!(
+            toolset.invoke("missing", &serde_json::json!({})).error_code,
+            Some(ErrorCode::UnknownTool)
+        );
+        let runtime = Runtime::new().unwrap();
+        runtime.block_on(async move {
+            let output = toolset.invoke("missing", &serde_json::json!({}));
+            assert_eq!(output.error_code, Some(ErrorCode::Failed));
+            assert!(output.to_text().contains("use `invoke_async` instead"));
+            let calls = vec![("missing".to_string(), serde_json::json!({})); 2];
+            let outputs = toolset.invoke_all(&calls, &CancellationToken::new());
+            assert_eq!(outputs.len(), 2);
+            assert!(
+                outputs
+                    .iter()
+                    .all(|output| output.error_code == Some(ErrorCode::Failed))
+            );
+            let output = toolset
+                .invoke_async("missing", &serde_json::json!({}), &CancellationToken::new())
+                .await;
+            assert_eq!(output.error_code, Some(ErrorCode::UnknownTool));
+            // the runtime created by the first call is dropped here, inside the async runtime
+            drop(toolset);
+        });
+    }
+
+    struct Echo {
+        schema: crate::ToolSchema,
+    }
+
+    impl Tool for Echo {
+        fn get_schema(&self) -> &crate::ToolSchema {
+            &self.schema
+        }
+
+        fn invoke<'a>(&'a self, args: &'a serde_json::Value) -> crate::ToolFuture<'a> {
+            Box::pin(async move { ToolOutput::json(args.clone()) })
+        }
+    }
+
+    #[test]
+    fn deprecated_arguments_are_reported_to_the_model() {
+        let schema = serde_json::from_value(serde_json::json!({
+            "name": "echo",
+            "help": "Returns its arguments.",
+            "arguments": [
+                {
+                    "name": "max",
+                    "help": "The largest value.",
+                    "type": "u32",
+                    "required": false,
+                    "aliases": ["limit"],
+                    "deprecated": "use `count` instead",
+                },
+                {
+                    "name": "verbose",
+                    "help": "Whether to say more.",
+                    "type": "bool",
+                    "required": false,
+                    "deprecated": "",
+                },
+                { "name": "count", "help": "How many.", "type": "u32", "required": false },
+            ],
+        }))
+        .unwrap();
+        let mut toolset = ToolSet::new();
+        toolset.register_tool(Box::new(Echo { schema }));
+
+        let output = toolset.invoke("echo", &serde_json::json!({ "limit": 2, "verbose": true }));
+        assert_eq!(
+            output.to_text(),
+            "(The argument `limit` is deprecated: use `count` instead)\n\
+             (The argument `verbose` is deprecated.)\n\
+             {\n  \"limit\": 2,\n  \"verbose\": true\n}"
+        );
+        let output = toolset.invoke("echo", &serde_json::json!({ "count": 2 }));
+        assert_eq!(output.to_text(), "{\n  \"count\": 2\n}");
+    }
+}
@@ -0,0 +1,337 @@
+// this module checks the arguments of a call against the types of the schema. values which
+// clearly mean the expected type are converted, e.g. "2" for an integer, anything else is
+// reported as a problem.
+
+use crate::ToolArgument;
+use serde_json::Value;
+
+// the last segment of the path of the type and its generic argument, e.g. `Vec` and
+// `std::path::PathBuf` for `std::vec::Vec<std::path::PathBuf>`
+fn split_type(type_: &str) -> (String, Option<String>) {
+    let type_ = type_.replace(' ', "");
+    let last_segment = |path: &str| path.rsplit("::").next().unwrap_or(path).to_string();
+    match type_.split_once('<') {
+        Some((path, inner)) if inner.ends_with('>') => (
+            last_segment(path),
+            Some(inner[..inner.len() - 1].to_string()),
+        ),
+        _ => (last_segment(&type_), None),
+    }
+}
+
+// the type of an `Option`, or of the elements of a `Vec`
+pub fn inner_type(type_: &str, name: &str) -> Option<String> {
+    match split_type(type_) {
+        (outer, inner) if outer == name => inner,
+        _ => None,
+    }
+}
+
+// the JSON value expected for the Rust type of an argument
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Kind {
+    String,
+    Integer { min: i128, max: i128 },
+    Number,
+    Boolean,
+    Array,
+    // a type the schema cannot describe, left to serde
+    Any,
+}
+
+impl Kind {
+    fn of(type_: &str) -> Self {
+        let integer = |min: i128, max: i128| Self::Integer { min, max };
+        match split_type(type_) {
+            (name, Some(_)) if name == "Vec" => Self::Array,
+            (_, Some(_)) => Self::Any,
+            (name, None) => match name.as_str() {
+                "String" | "&str" | "PathBuf" | "char" => Self::String,
+                "u8" => integer(0, u8::MAX as i128),
+                "u16" => integer(0, u16::MAX as i128),
+                "u32" => integer(0, u32::MAX as i128),
+                "u64" | "u128" | "usize" => integer(0, u64::MAX as i128),
+                "i8" => integer(i8::MIN as i128, i8::MAX as i128),
+                "i16" => integer(i16::MIN as i128, i16::MAX as i128),
+                "i32" => integer(i32::MIN as i128, i32::MAX as i128),
+                "i64" | "i128" | "isize" => integer(i64::MIN as i128, i64::MAX as i128),
+                "f32" | "f64" => Self::Number,
+                "bool" => Self::Boolean,
+                _ => Self::Any,
+            },
+        }
+    }
+
+    fn describe(&self) -> &'static str {
+        match self {
+            Self::String => "a string",
+            Self::Integer { min: 0, .. } => "a non-negative integer",
+            Self::Integer { .. } => "an integer",
+            Self::Number => "a number",
+            Self::Boolean => "true or false",
+            Self::Array => "an array",
+            Self::Any => "a value",
+        }
+    }
+}
+
+// the JSON Schema of the values of the type, e.g. `{"type": "integer", "minimum": 0}` for a `u32`
+pub fn json_schema(type_: &str) -> Value {
+    if let Some(inner) = inner_type(type_, "Option") {
+        return json_schema(&inner);
+    }
+    match Kind::of(type_) {
+        Kind::String => serde_json::json!({ "type": "string" }),
+        Kind::Integer { min: 0, .. } => serde_json::json!({ "type": "integer", "minimum": 0 }),
+        Kind::Integer { .. } => serde_json::json!({ "type": "integer" }),
+        Kind::Number => serde_json::json!({ "type": "number" }),
+        Kind::Boolean => serde_json::json!({ "type": "boolean" }),
+        Kind::Array => {
+            let items = inner_type(type_, "Vec")
+                .map(|inner| json_schema(&inner))
+                .unwrap_or_else(|| serde_json::json!({}));
+            serde_json::json!({ "type": "array", "items": items })
+        }
+        Kind::Any => serde_json::json!({}),
+    }
+}
+
+fn coerce_integer(value: &Value) -> Option<i128> {
+    match value {
+        Value::Number(number) => number
+            .as_i64()
+            .map(i128::from)
+            .or_else(|| number.as_u64().map(i128::from))
+            // 2.0 is an integer as well
+            .or_else(|| {
+                number
+                    .as_f64()
+                    .filter(|float| float.fract() == 0.0 && float.abs() < 1e18)
+                    .map(|float| float as i128)
+            }),
+        Value::String(text) => coerce_integer(&serde_json::from_str(text.trim()).ok()?),
+        _ => None,
+    }
+}
+
+// returns the value as the type, or the problem with it
+pub fn coerce(type_: &str, value: &Value) -> Result<Value, String> {
+    if let Some(inner) = inner_type(type_, "Option") {
+        return if value.is_null() {
+            Ok(Value::Null)
+        } else {
+            coerce(&inner, value)
+        };
+    }
+    let kind = Kind::of(type_);
+    let coerced = match (kind, value) {
+        (Kind::Any, value) => Some(value.clone()),
+        (Kind::String, Value::String(_)) => Some(value.clone()),
+        (Kind::String, Value::Number(_) | Value::Bool(_)) => Some(value.to_string().into()),
+        (Kind::Integer { min, max }, value) => match coerce_integer(value) {
+            Some(integer) if (min..=max).contains(&integer) => Some(if integer < 0 {
+                (integer as i64).into()
+            } else {
+                (integer as u64).into()
+            }),
+            Some(integer) => {
+                return Err(format!(
+                    "must be between {} and {}, got {}",
+                    min, max, integer
+                ));
+            }
+            None => None,
+        },
+        (Kind::Number, Value::Number(_)) => Some(value.clone()),
+        (Kind::Number, Value::String(text)) => text
+            .trim()
+            .parse::<f64>()
+            .ok()
+            .and_then(serde_json::Number::from_f64)
+            .map(Value::Number),
+        (Kind::Boolean, Value::Bool(_)) => Some(value.clone()),
+        (Kind::Boolean, Value::String(text)) => match text.trim() {
+            "true" => Some(true.into()),
+            "false" => Some(false.into()),
+            _ => None,
+        },
+        (Kind::Array, Value::Array(items)) => match inner_type(type_, "Vec") {
+            Some(inner) => {
+                let items = items
+                    .iter()
+                    .enumerate()
+                    .map(|(i, item)| {
+                        coerce(&inner, item).map_err(|problem| format!("element {} {}", i, problem))
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                Some(Value::Array(items))
+            }
+            None => Some(value.clone()),
+        },
+        _ => None,
+    };
+    coerced.ok_or_else(|| format!("expected {}, got {}", kind.describe(), value))
+}
+
+// checks the coerced value against the allowed values and the bounds of the argument, which
+// apply to each element of a `Vec`
+pub fn check(arg: &ToolArgument, value: Value) -> Result<Value, String> {
+    let type_ = inner_type(&arg.type_, "Option");
+    // `null` is only left after `coerce` for an `Option`
+    if value.is_null() && type_.is_some() {
+        return Ok(value);
+    }
+    let type_ = type_.unwrap_or_else(|| arg.type_.clone());
+    match value {
+        Value::Array(items) if inner_type(&type_, "Vec").is_some() => {
+            for (i, item) in items.iter().enumerate() {
+                check_value(arg, item).map_err(|problem| format!("element {} {}", i, problem))?;
+            }
+            Ok(Value::Array(items))
+        }
+        value => check_value(arg, &value).map(|()| value),
+    }
+}
+
+fn check_value(arg: &ToolArgument, value: &Value) -> Result<(), String> {
+    if !arg.enum_values.is_empty() && !arg.enum_values.contains(value) {
+        let values = arg
+            .enum_values
+            .iter()
+            .map(|value| value.to_string())
+            .collect::<Vec<_>>();
+        return Err(format!(
+            "must be one of {}, got {}",
+            values.join(", "),
+            value
+        ));
+    }
+    if let Some(number) = value.as_f64() {
+        if let Some(min) = arg.min
+            && number < min
+        {
+            return Err(format!("must be at least {}, got {}", min, value));
+        }
+        if let Some(max) = arg.max
+            && number > max
+        {
+            return Err(format!("must be at most {}, got {}", max, value));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    // an argument of the type, with the other fields of the schema given in `fields`
+    fn argument(type_: &str, fields: Value) -> ToolArgument {
+        let mut arg = json!({ "name": "arg", "help": "", "type": type_, "required": false });
+        if let (Value::Object(arg), Value::Object(fields)) = (&mut arg, fields) {
+            arg.extend(fields);
+        }
+        serde_json::from_value(arg).unwrap()
+    }
+
+    #[test]
+    fn values_are_coerced_to_the_type() {
+        assert_eq!(coerce("u32", &json!("2")), Ok(json!(2)));
+        assert_eq!(coerce("i64", &json!(-2.0)), Ok(json!(-2)));
+        assert_eq!(coerce("bool", &json!("true")), Ok(json!(true)));
+        assert_eq!(coerce("f64", &json!(" 1.5")), Ok(json!(1.5)));
+        assert_eq!(coerce("String", &json!(3)), Ok(json!("3")));
+        assert_eq!(coerce("Option<u32>", &Value::Null), Ok(Value::Null));
+        assert_eq!(coerce("Option<u32>", &json!("4")), Ok(json!(4)));
+        assert_eq!(coerce("std::path::PathBuf", &json!(1)), Ok(json!("1")));
+        assert_eq!(
+            coerce("std::vec::Vec<std::string::String>", &json!([1, "a"])),
+            Ok(json!(["1", "a"]))
+        );
+        assert_eq!(coerce("Vec<u8>", &json!(["1", 2])), Ok(json!([1, 2])));
+        assert_eq!(
+            coerce("Option<Vec<bool>>", &json!(["false"])),
+            Ok(json!([false]))
+        );
+        assert_eq!(
+            coerce("HashMap<String, u32>", &json!({ "a": "b" })),
+            Ok(json!({ "a": "b" }))
+        );
+    }
+
+    #[test]
+    fn invalid_values_are_explained() {
+        assert_eq!(
+            coerce("u8", &json!(300)),
+            Err("must be between 0 and 255, got 300".to_string())
+        );
+        assert_eq!(
+            coerce("u32", &json!("two")),
+            Err("expected a non-negative integer, got \"two\"".to_string())
+        );
+        assert_eq!(
+            coerce("bool", &json!("yes")),
+            Err("expected true or false, got \"yes\"".to_string())
+        );
+        assert_eq!(
+            coerce("u32", &Value::Null),
+            Err("expected a non-negative integer, got null".to_string())
+        );
+        assert_eq!(
+            coerce("Vec<u32>", &json!([1, "x"])),
+            Err("element 1 expected a non-negative integer, got \"x\"".to_string())
+        );
+        assert_eq!(
+            coerce("std::string::String", &json!([])),
+            Err("expected a string, got []".to_string())
+        );
+    }
+
+    #[test]
+    fn values_are_checked_against_the_argument() {
+        let arg = argument("String", json!({ "enum": ["fast", "slow"] }));
+        assert_eq!(check(&arg, json!("fast")), Ok(json!("fast")));
+        assert_eq!(
+            check(&arg, json!("medium")),
+            Err("must be one of \"fast\", \"slow\", got \"medium\"".to_string())
+        );
+
+        let arg = argument("Option<u32>", json!({ "min": 1, "max": 10 }));
+        assert_eq!(check(&arg, Value::Null), Ok(Value::Null));
+        assert_eq!(check(&arg, json!(10)), Ok(json!(10)));
+        assert_eq!(
+            check(&arg, json!(0)),
+            Err("must be at least 1, got 0".to_string())
+        );
+        assert_eq!(
+            check(&arg, json!(11)),
+            Err("must be at most 10, got 11".to_string())
+        );
+
+        let arg = argument("std::vec::Vec<u32>", json!({ "enum": [1, 2] }));
+        assert_eq!(check(&arg, json!([2, 1])), Ok(json!([2, 1])));
+        assert_eq!(
+            check(&arg, json!([1, 3])),
+            Err("element 1 must be one of 1, 2, got 3".to_string())
+        );
+        let arg = argument("Option<Vec<u32>>", json!({ "max": 5 }));
+        assert_eq!(
+            check(&arg, json!([6])),
+            Err("element 0 must be at most 5, got 6".to_string())
+        );
+    }
+
+    #[test]
+    fn qualified_types_have_a_schema() {
+        assert_eq!(
+            json_schema("std::path::PathBuf"),
+            json!({ "type": "string" })
+        );
+        assert_eq!(
+            json_schema("Option<std::vec::Vec<u16>>"),
+            json!({ "type": "array", "items": { "type": "integer", "minimum": 0 } })
+        );
+        assert_eq!(json_schema("serde_json::Value"), json!({}));
+    }
+}
@@ -1,7 +1,7 @@
 use anyhow::Result;
 use proc_macro::TokenStream;
 use quote::quote;
-use serde_json;
+use syn::parse::Parser;
 use syn::punctuated::Punctuated;
 use syn::spanned::Spanned;
 use syn::{
@@ -12,26 +12,57 @@ fn create_attr_error<Meta, T: Spanned>(meta: &T, msg: &str) -> Result<Meta, syn:
     Err(syn::Error::new(meta.span(), msg))
 }
 
-fn parse_json_value(expr: Option<Expr>) -> Result<serde_json::Value, syn::Error> {
-    match expr {
-        Some(expr) => match serde_json::from_str(&quote!(#expr).to_string()) {
-            Ok(value) => Ok(value),
-            Err(err) => Err(syn::Error::new(expr.span(), err)),
-        },
-        None => Ok(serde_json::Value::Null),
-    }
+fn parse_json_value(expr: &Expr) -> Result<serde_json::Value, syn::Error> {
+    serde_json::from_str(&quote!(#expr).to_string())
+        .map_err(|err| syn::Error::new(expr.span(), format!("expected JSON literal: {}", err)))
+}
+
+enum DefaultValue {
+    Json(serde_json::Value),
+    // a Rust expression of the type of the field
+    Expr(Expr),
+    // `Default::default()` of the type of the field
+    Trait,
 }
 
 struct FieldMeta {
     help: String,
     required: bool,
-    default: Option<Expr>,
-    example: Option<Expr>,
+    default: Option<DefaultValue>,
+    example: Option<serde_json::Value>,
+    // the allowed values, `Some(None)` for the values of a `ToolEnum` type
+    enum_values: Option<Option<Vec<serde_json::Value>>>,
+    min: Option<f64>,
+    max: Option<f64>,
+    aliases: Vec<String>,
+    // the reason, empty if none is given
+    deprecated: Option<String>,
+    hidden: bool,
+    // the arguments of the field's type are added to the tool
+    flatten: bool,
 }
 
 struct StructMeta {
     name: String,
     help: String,
+    read_only: bool,
+}
+
+// a number literal, which may be negative
+fn parse_number_attr(name_value: &MetaNameValue) -> Result<f64, syn::Error> {
+    let (negative, expr) = match &name_value.value {
+        Expr::Unary(unary) if matches!(unary.op, syn::UnOp::Neg(_)) => (true, &*unary.expr),
+        expr => (false, expr),
+    };
+    let value = match expr {
+        Expr::Lit(lit) => match &lit.lit {
+            Lit::Int(int) => int.base10_parse::<f64>()?,
+            Lit::Float(float) => float.base10_parse::<f64>()?,
+            _ => return create_attr_error(&name_value.value, "expected number literal"),
+        },
+        _ => return create_attr_error(&name_value.value, "expected number literal"),
+    };
+    Ok(if negative { -value } else { value })
 }
 
 fn parse_name_value_string_attr(name_value: &MetaNameValue) -> Result<String, syn::Error> {
@@ -44,20 +75,127 @@ fn parse_name_value_string_attr(name_value: &MetaNameValue) -> Result<String, sy
     }
 }
 
-fn parse_protocol_field_attr_list(attr: &Attribute) -> Result<FieldMeta, syn::Error> {
+// `enum` is a keyword, which `Meta` does not accept as a name
+struct FieldAttrMeta(Meta);
+
+impl syn::parse::Parse for FieldAttrMeta {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        if !input.peek(Token![enum]) {
+            return input.parse().map(Self);
+        }
+        let token = input.parse::<Token![enum]>()?;
+        let path = syn::Path::from(syn::Ident::new("enum", token.span));
+        if input.peek(Token![=]) {
+            Ok(Self(Meta::NameValue(MetaNameValue {
+                path,
+                eq_token: input.parse()?,
+                value: input.parse()?,
+            })))
+        } else {
+            Ok(Self(Meta::Path(path)))
+        }
+    }
+}
+
+const FIELD_ATTRS: &str = "expected one of `help`, `required`, `default`, `example`, `enum`, `min`, `max`, `alias`, `deprecated`, `hidden`, `flatten`";
+
+// the inner type of `Option<T>`
+fn get_option_type(ty: &syn::Type) -> Option<&syn::Type> {
+    let syn::Type::Path(path) = ty else {
+        return None;
+    };
+    let segment = path.path.segments.last()?;
+    if segment.ident != "Option" {
+        return None;
+    }
+    match &segment.arguments {
+        syn::PathArguments::AngleBracketed(args) => match args.args.first()? {
+            syn::GenericArgument::Type(ty) => Some(ty),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+// why a literal default or example is not allowed by the other attributes, if it is not
+fn check_literal(meta: &FieldMeta, value: &serde_json::Value) -> Option<String> {
+    if let Some(Some(values)) = &meta.enum_values
+        && !values.contains(value)
+    {
+        return Some(format!("{} is not one of the `enum` values", value));
+    }
+    let number = value.as_f64()?;
+    if let Some(min) = meta.min
+        && number < min
+    {
+        return Some(format!("{} is less than `min`", value));
+    }
+    if let Some(max) = meta.max
+        && number > max
+    {
+        return Some(format!("{} is greater than `max`", value));
+    }
+    None
+}
+
+// whether a literal default or example can be read as the type, other types than the
+// primitive ones and strings are left to serde
+fn literal_matches_type(type_: &str, value: &serde_json::Value) -> bool {
+    if let Some(inner) = type_
+        .strip_prefix("Option<")
+        .and_then(|inner| inner.strip_suffix('>'))
+    {
+        return value.is_null() || literal_matches_type(inner, value);
+    }
+    match type_ {
+        "String" | "PathBuf" | "char" => value.is_string(),
+        "u8" | "u16" | "u32" | "u64" | "u128" | "usize" => value.is_u64(),
+        "i8" | "i16" | "i32" | "i64" | "i128" | "isize" => value.is_i64() || value.is_u64(),
+        "f32" | "f64" => value.is_number(),
+        "bool" => value.is_boolean(),
+        _ if type_.starts_with("Vec<") => value.is_array(),
+        _ => true,
+    }
+}
+
+// `is_option` tells whether the field is an `Option`, which may be absent without a default
+fn parse_protocol_field_attr_list(
+    attr: &Attribute,
+    is_option: bool,
+) -> Result<FieldMeta, syn::Error> {
     let mut help = String::new();
     let mut required = false;
     let mut default = None;
     let mut example = None;
+    let mut enum_values = None;
+    let mut min = None;
+    let mut max = None;
+    let mut aliases = Vec::new();
+    let mut deprecated = None;
+    let mut hidden = false;
+    let mut flatten = false;
 
-    let nested = attr.parse_args_with(Punctuated::<Meta, Token![,]>::parse_terminated)?;
-    for meta in nested {
+    let nested = attr.parse_args_with(Punctuated::<FieldAttrMeta, Token![,]>::parse_terminated)?;
+    for FieldAttrMeta(meta) in nested {
         match meta {
             Meta::Path(path) => {
                 if path.is_ident("required") {
                     required = true;
+                } else if path.is_ident("default") {
+                    default = Some(DefaultValue::Trait);
+                } else if path.is_ident("enum") {
+                    enum_values = Some(None);
+                } else if path.is_ident("deprecated") {
+                    deprecated = Some(String::new());
+                } else if path.is_ident("hidden") {
+                    hidden = true;
+                } else if path.is_ident("flatten") {
+                    flatten = true;
                 } else {
-                    return create_attr_error(&path, "expected one of `required`");
+                    return create_attr_error(
+                        &path,
+                        "expected one of `required`, `default`, `enum`, `deprecated`, `hidden`, `flatten`",
+                    );
                 }
             }
             Meta::NameValue(name_value) => {
@@ -82,53 +220,135 @@ fn parse_protocol_field_attr_list(attr: &Attribute) -> Result<FieldMeta, syn::Er
                         }
                     }
                 } else if name_value.path.is_ident("default") {
-                    default = Some(name_value.value);
+                    // anything but a JSON literal is taken as a Rust expression
+                    default = Some(match parse_json_value(&name_value.value) {
+                        Ok(value) => DefaultValue::Json(value),
+                        Err(_) => DefaultValue::Expr(name_value.value),
+                    });
                 } else if name_value.path.is_ident("example") {
-                    example = Some(name_value.value);
+                    example = Some(parse_json_value(&name_value.value)?);
+                } else if name_value.path.is_ident("enum") {
+                    match parse_json_value(&name_value.value)? {
+                        serde_json::Value::Array(values) if !values.is_empty() => {
+                            enum_values = Some(Some(values));
+                        }
+                        _ => {
+                            return create_attr_error(
+                                &name_value.value,
+                                "expected non-empty array of values",
+                            );
+                        }
+                    }
+                } else if name_value.path.is_ident("min") {
+                    min = Some(parse_number_attr(&name_value)?);
+                } else if name_value.path.is_ident("max") {
+                    max = Some(parse_number_attr(&name_value)?);
+                } else if name_value.path.is_ident("alias") {
+                    aliases.push(parse_name_value_string_attr(&name_value)?);
+                } else if name_value.path.is_ident("deprecated") {
+                    deprecated = Some(parse_name_value_string_attr(&name_value)?);
                 } else {
-                    return create_attr_error(
-                        &name_value.path,
-                        "expected one of `help`, `required`, `default`, `example`",
-                    );
+                    return create_attr_error(&name_value.path, FIELD_ATTRS);
                 }
             }
             _ => {
-                return create_attr_error(
-                    &meta,
-                    "expected one of `help`, `required`, `default`, `example`",
-                );
+                return create_attr_error(&meta, FIELD_ATTRS);
             }
         }
     }
-    if help.is_empty() {
-        Err(syn::Error::new(attr.span(), "Missing `help` attribute"))
-    } else if required && default.is_none() && example.is_none() {
-        Err(syn::Error::new(
-            attr.span(),
-            "Missing `default` or `example` attribute for required field",
-        ))
-    } else if !required && default.is_none() {
-        Err(syn::Error::new(
-            attr.span(),
-            "Missing `default` attribute for optional field",
-        ))
-    } else {
-        Ok(FieldMeta {
-            help,
-            required,
-            default,
-            example,
-        })
+    let meta = FieldMeta {
+        help,
+        required,
+        default,
+        example,
+        enum_values,
+        min,
+        max,
+        aliases,
+        deprecated,
+        hidden,
+        flatten,
+    };
+    if meta.flatten {
+        let has_others = !meta.help.is_empty()
+            || meta.required
+            || meta.default.is_some()
+            || meta.example.is_some()
+            || meta.enum_values.is_some()
+            || meta.min.is_some()
+            || meta.max.is_some()
+            || !meta.aliases.is_empty()
+            || meta.deprecated.is_some()
+            || meta.hidden;
+        return if has_others {
+            Err(syn::Error::new(
+                attr.span(),
+                "`flatten` cannot be combined with other attributes",
+            ))
+        } else {
+            Ok(meta)
+        };
+    }
+    let error = |msg: &str| Err(syn::Error::new(attr.span(), msg));
+    if meta.help.is_empty() {
+        return error("Missing `help` attribute");
+    }
+    if meta.required {
+        if is_option {
+            return error("An `Option` field is optional and cannot be `required`");
+        } else if meta.default.is_some() {
+            return error("A `required` field cannot have a `default`");
+        } else if meta.example.is_none() {
+            return error("Missing `example` attribute for required field");
+        } else if meta.hidden {
+            return error(
+                "A `hidden` field cannot be `required`, as the model does not know of it",
+            );
+        } else if meta.deprecated.is_some() {
+            return error("A `deprecated` field cannot be `required`");
+        }
+    } else if meta.default.is_none() && !is_option {
+        return error(
+            "Missing `default` attribute for optional field, or use `Option` for its type",
+        );
+    }
+    if let (Some(min), Some(max)) = (meta.min, meta.max)
+        && min > max
+    {
+        return error("`min` is greater than `max`");
+    }
+    if let Some(DefaultValue::Json(value)) = &meta.default
+        && let Some(problem) = check_literal(&meta, value)
+    {
+        return error(&format!("Invalid `default`: {}", problem));
+    }
+    if let Some(value) = &meta.example
+        && let Some(problem) = check_literal(&meta, value)
+    {
+        return error(&format!("Invalid `example`: {}", problem));
     }
+    Ok(meta)
 }
 
 fn parse_protocol_struct_attr_list(attr: &Attribute) -> Result<StructMeta, syn::Error> {
     let mut name = String::new();
     let mut help = String::new();
+    let mut read_only = false;
+    // a group of arguments flattened into tools needs no name or help
+    let mut group = false;
 
     let nested = attr.parse_args_with(Punctuated::<Meta, Token![,]>::parse_terminated)?;
     for meta in nested {
         match meta {
+            Meta::Path(path) => {
+                if path.is_ident("read_only") {
+                    read_only = true;
+                } else if path.is_ident("group") {
+                    group = true;
+                } else {
+                    return create_attr_error(&path, "expected one of `read_only`, `group`");
+                }
+            }
             // name: string, help: string
             Meta::NameValue(name_value) => {
                 if name_value.path.is_ident("name") {
@@ -140,19 +360,38 @@ fn parse_protocol_struct_attr_list(attr: &Attribute) -> Result<StructMeta, syn::
                 }
             }
             _ => {
-                return create_attr_error(&meta, "expected one of `name`, `help`");
+                return create_attr_error(
+                    &meta,
+                    "expected one of `name`, `help`, `read_only`, `group`",
+                );
             }
         }
     }
-    if name.is_empty() {
+    if group && (!name.is_empty() || !help.is_empty() || read_only) {
+        Err(syn::Error::new(
+            attr.span(),
+            "A `group` is not a tool and has no `name`, `help` or `read_only`",
+        ))
+    } else if name.is_empty() && !group {
         Err(syn::Error::new(attr.span(), "Missing `name` attribute"))
-    } else if help.is_empty() {
+    } else if help.is_empty() && !group {
         Err(syn::Error::new(attr.span(), "Missing `help` attribute"))
     } else {
-        Ok(StructMeta { name, help })
+        Ok(StructMeta {
+            name,
+            help,
+            read_only,
+        })
     }
 }
 
+/// Implements `ToolProtocol` for a struct of the arguments of a tool.
+///
+/// `#[tool_protocol(name = "...", help = "...")]` on the struct names the tool, and adding
+/// `read_only` lets its calls run in parallel. A struct with `#[tool_protocol(group)]` instead
+/// is a group of arguments flattened into tools. Each field has `#[tool_protocol(help = "...")]`
+/// with any of `required`, `default`, `example`, `enum`, `min`, `max`, `alias`, `deprecated` and
+/// `hidden`, or `#[tool_protocol(flatten)]` together with `#[serde(flatten)]` for a group.
 #[proc_macro_derive(ToolProtocol, attributes(tool_protocol))]
 pub fn derive_tool_protocol(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
@@ -180,20 +419,17 @@ pub fn derive_tool_protocol(input: TokenStream) -> TokenStream {
         let field_name_str = field_name.to_string();
         let field_type = &field.ty;
 
-        let FieldMeta {
-            help,
-            required,
-            default,
-            example,
-        } = match field
+        let meta = match field
             .attrs
             .iter()
             .find(|a| a.path().is_ident("tool_protocol"))
         {
-            Some(attr) => match parse_protocol_field_attr_list(attr) {
-                Ok(meta) => meta,
-                Err(err) => return err.to_compile_error().into(),
-            },
+            Some(attr) => {
+                match parse_protocol_field_attr_list(attr, get_option_type(field_type).is_some()) {
+                    Ok(meta) => meta,
+                    Err(err) => return err.to_compile_error().into(),
+                }
+            }
             None => {
                 return syn::Error::new(field.span(), "Missing `tool_protocol` attribute")
                     .to_compile_error()
@@ -201,31 +437,157 @@ pub fn derive_tool_protocol(input: TokenStream) -> TokenStream {
             }
         };
 
-        let type_str = quote!(#field_type).to_string();
-        let default_value = match parse_json_value(default) {
-            Ok(value) => value.to_string(),
-            Err(err) => return err.to_compile_error().into(),
+        if meta.flatten {
+            // serde has to read the arguments of the group from the same object
+            let serde_flatten = match has_serde_flatten(&field.attrs) {
+                Ok(serde_flatten) => serde_flatten,
+                Err(e) => return e.to_compile_error().into(),
+            };
+            if !serde_flatten {
+                return syn::Error::new(
+                    field.span(),
+                    "A `flatten` field needs `#[serde(flatten)]` as well",
+                )
+                .to_compile_error()
+                .into();
+            }
+            arguments.push(quote! {
+                arguments.extend(
+                    <#field_type as ::tool_protocol::ToolProtocol<#field_type>>::create_schema()?.arguments,
+                );
+            });
+            continue;
+        }
+
+        let FieldMeta {
+            help,
+            required,
+            default,
+            example,
+            enum_values,
+            min,
+            max,
+            aliases,
+            deprecated,
+            hidden,
+            flatten: _,
+        } = meta;
+
+        // e.g. `Option<u32>` rather than `Option < u32 >`
+        let type_str = quote!(#field_type)
+            .to_string()
+            .replace(" <", "<")
+            .replace("< ", "<")
+            .replace(" >", ">")
+            .replace(" ,", ",");
+        for (name, value) in [
+            (
+                "default",
+                default.as_ref().and_then(|default| match default {
+                    DefaultValue::Json(value) => Some(value),
+                    _ => None,
+                }),
+            ),
+            ("example", example.as_ref()),
+        ] {
+            if let Some(value) = value
+                && !literal_matches_type(&type_str, value)
+            {
+                return syn::Error::new(
+                    field.span(),
+                    format!("Invalid `{}`: {} is not a `{}`", name, value, type_str),
+                )
+                .to_compile_error()
+                .into();
+            }
+        }
+        let default_error = format!(
+            "The default of `{}` in `{}` is not a JSON value: {{}}",
+            field_name_str, input.ident
+        );
+        let default = match default {
+            Some(DefaultValue::Json(value)) => {
+                let value = value.to_string();
+                quote!(::std::option::Option::Some(
+                    ::tool_protocol::serde_json::from_str(#value).unwrap()
+                ))
+            }
+            // e.g. a map with keys that are not strings cannot be a JSON value
+            Some(DefaultValue::Expr(expr)) => quote! {
+                ::std::option::Option::Some(
+                    ::tool_protocol::serde_json::to_value({
+                        let value: #field_type = #expr;
+                        value
+                    })
+                    .map_err(|e| ::tool_protocol::anyhow::anyhow!(#default_error, e))?,
+                )
+            },
+            Some(DefaultValue::Trait) => quote! {
+                ::std::option::Option::Some(
+                    ::tool_protocol::serde_json::to_value(
+                        <#field_type as ::std::default::Default>::default(),
+                    )
+                    .map_err(|e| ::tool_protocol::anyhow::anyhow!(#default_error, e))?,
+                )
+            },
+            None => quote!(::std::option::Option::None),
         };
-        let example_value = match parse_json_value(example) {
-            Ok(value) => value.to_string(),
-            Err(err) => return err.to_compile_error().into(),
+        let example = match example {
+            Some(value) => {
+                let value = value.to_string();
+                quote!(::std::option::Option::Some(
+                    ::tool_protocol::serde_json::from_str(#value).unwrap()
+                ))
+            }
+            None => quote!(::std::option::Option::None),
+        };
+        let enum_values = match enum_values {
+            Some(Some(values)) => {
+                let values = serde_json::Value::Array(values).to_string();
+                quote!(::tool_protocol::serde_json::from_str(#values).unwrap())
+            }
+            Some(None) => {
+                // the values of `Option<T>` are those of `T`
+                let enum_type = get_option_type(field_type).unwrap_or(field_type);
+                quote!(<#enum_type as ::tool_protocol::ToolEnum>::values())
+            }
+            None => quote!(::std::vec::Vec::new()),
+        };
+        let min = match min {
+            Some(min) => quote!(::std::option::Option::Some(#min)),
+            None => quote!(::std::option::Option::None),
+        };
+        let max = match max {
+            Some(max) => quote!(::std::option::Option::Some(#max)),
+            None => quote!(::std::option::Option::None),
+        };
+        let deprecated = match deprecated {
+            Some(reason) => quote!(::std::option::Option::Some(#reason.to_string())),
+            None => quote!(::std::option::Option::None),
         };
 
         arguments.push(quote! {
-            ToolArgument {
+            arguments.push(::tool_protocol::ToolArgument {
                 name: #field_name_str.to_string(),
                 help: #help.to_string(),
                 type_: #type_str.to_string(),
                 required: #required,
-                default: serde_json::from_str(#default_value).unwrap(),
-                example: serde_json::from_str(#example_value).unwrap(),
-            }
+                default: #default,
+                example: #example,
+                enum_values: #enum_values,
+                min: #min,
+                max: #max,
+                aliases: ::std::vec![#(#aliases.to_string()),*],
+                deprecated: #deprecated,
+                hidden: #hidden,
+            });
         });
     }
 
     let StructMeta {
         help: struct_help,
-        name: mut struct_name,
+        name: struct_name,
+        read_only,
     } = match input
         .attrs
         .iter()
@@ -244,17 +606,26 @@ pub fn derive_tool_protocol(input: TokenStream) -> TokenStream {
 
     let name = input.ident;
     let expanded = quote! {
-        impl ToolProtocol<#name> for #name {
-            fn create_schema() -> ToolSchema {
-                ToolSchema {
+        impl ::tool_protocol::ToolProtocol<#name> for #name {
+            fn create_schema() -> ::tool_protocol::anyhow::Result<::tool_protocol::ToolSchema> {
+                ::std::result::Result::Ok(::tool_protocol::ToolSchema {
                     name: #struct_name.to_string(),
                     help: #struct_help.to_string(),
-                    arguments: vec![#(#arguments),*],
-                }
+                    arguments: {
+                        let mut arguments = ::std::vec::Vec::new();
+                        #(#arguments)*
+                        arguments
+                    },
+                    read_only: #read_only,
+                })
             }
-            fn parse_args(schema: &ToolSchema, args: &serde_json::Value) -> Result<#name> {
-                let args = canonicalize_tool_args(schema, args)?;
-                serde_json::from_value(args).map_err(Into::into)
+            fn parse_args(
+                schema: &::tool_protocol::ToolSchema,
+                args: &::tool_protocol::serde_json::Value,
+            ) -> ::tool_protocol::anyhow::Result<#name> {
+                let args = ::tool_protocol::canonicalize_tool_args(schema, args)?;
+                ::tool_protocol::serde_json::from_value(args)
+                    .map_err(|e| schema.argument_error(&[e.to_string()]))
             }
         }
     };
@@ -262,39 +633,288 @@ pub fn derive_tool_protocol(input: TokenStream) -> TokenStream {
     TokenStream::from(expanded)
 }
 
+// the arguments of `#[tool]`, the protocol and whether the inherent `invoke` is async
+struct ToolAttr {
+    protocol: syn::Ident,
+    is_async: bool,
+}
+
+impl syn::parse::Parse for ToolAttr {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        let protocol = input.parse()?;
+        let mut is_async = false;
+        if input.parse::<Option<Token![,]>>()?.is_some() && !input.is_empty() {
+            input.parse::<Token![async]>()?;
+            is_async = true;
+        }
+        if !input.is_empty() {
+            return Err(input.error("expected `#[tool(Protocol)]` or `#[tool(Protocol, async)]`"));
+        }
+        Ok(Self { protocol, is_async })
+    }
+}
+
 // #[tool(SomeProtocol)]
+// pub struct SomeTool;
+// expends to:
 // pub struct SomeTool {
 //     schema: ToolSchema,
 // }
-// expends to:
+// impl SomeTool {
+//     pub fn create() -> Result<Box<dyn Tool>> { Ok(Box::new(Self { schema: create_schema::<SomeProtocol>()? })) }
+// }
 // impl Tool for SomeTool {
 //     fn get_schema() -> &ToolSchema { &self.schema }
-//     fn invoke(&mut self, args: &serde_json::Value) -> Result<String> {
-//         self.invoke(parse_args::<SomeProtocol>(self.get_schema(), args)?)
+//     fn invoke<'a>(&'a self, args: &'a serde_json::Value) -> ToolFuture<'a> {
+//         Box::pin(async move { self.invoke(parse_args::<SomeProtocol>(self.get_schema(), args)?) })
 //     }
 // }
+// inventory::submit! { ToolRegistration { create: SomeTool::create } }
+// so that the tool is listed by `registered_tools`. other fields of the struct are created with
+// `Default::default()`.
+/// Implements `Tool` for a struct with an inherent `invoke` method taking the arguments of a
+/// `ToolProtocol`, e.g. `#[tool(ListProtocol)]`, and registers it for `registered_tools`.
+///
+/// With `#[tool(ListProtocol, async)]` the inherent `invoke` is an `async fn`. It returns a
+/// `Result` of anything convertible into a `ToolOutput`, and errors become outputs with
+/// `is_error` set.
 #[proc_macro_attribute]
-pub fn tool(protocol: TokenStream, item: TokenStream) -> TokenStream {
-    let protocol = parse_macro_input!(protocol as syn::Ident);
-    let item = parse_macro_input!(item as syn::ItemStruct);
+pub fn tool(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let ToolAttr { protocol, is_async } = parse_macro_input!(attr as ToolAttr);
+    let mut item = parse_macro_input!(item as syn::ItemStruct);
+
+    if !item.generics.params.is_empty() {
+        return syn::Error::new(item.generics.span(), "A tool cannot be generic")
+            .to_compile_error()
+            .into();
+    }
+    let fields = match &mut item.fields {
+        Fields::Named(fields) => fields,
+        Fields::Unit => {
+            item.fields = Fields::Named(syn::parse_quote!({}));
+            item.semi_token = None;
+            match &mut item.fields {
+                Fields::Named(fields) => fields,
+                _ => unreachable!(),
+            }
+        }
+        Fields::Unnamed(fields) => {
+            return syn::Error::new(
+                fields.span(),
+                "A tool must be a unit struct or have named fields",
+            )
+            .to_compile_error()
+            .into();
+        }
+    };
+    if let Some(field) = fields
+        .named
+        .iter()
+        .find(|field| field.ident.as_ref().is_some_and(|ident| ident == "schema"))
+    {
+        return syn::Error::new(
+            field.span(),
+            "The `schema` field is added by `#[tool]`, remove it",
+        )
+        .to_compile_error()
+        .into();
+    }
+    let other_fields = fields
+        .named
+        .iter()
+        .filter_map(|field| field.ident.clone())
+        .collect::<Vec<_>>();
+    fields.named.push(
+        syn::Field::parse_named
+            .parse2(quote!(schema: ::tool_protocol::ToolSchema))
+            .unwrap(),
+    );
 
     let name = &item.ident;
-    let schema = quote! {
-        create_schema::<#protocol>()
+    let call = if is_async {
+        quote!(self.invoke(args).await)
+    } else {
+        quote!(self.invoke(args))
     };
 
     let expanded = quote! {
         #item
 
-        impl Tool for #name {
-            fn get_schema(&self) -> &ToolSchema {
+        impl #name {
+            pub fn create() -> ::tool_protocol::anyhow::Result<::std::boxed::Box<dyn ::tool_protocol::Tool>> {
+                ::std::result::Result::Ok(::std::boxed::Box::new(Self {
+                    schema: ::tool_protocol::create_schema::<#protocol>()?,
+                    #(#other_fields: ::std::default::Default::default(),)*
+                }))
+            }
+        }
+
+        impl ::tool_protocol::Tool for #name {
+            fn get_schema(&self) -> &::tool_protocol::ToolSchema {
                 &self.schema
             }
 
-            fn invoke(&mut self, args: &serde_json::Value) -> Result<String> {
-                let args = parse_args::<#protocol>(self.get_schema(), args)?;
-                println!("\n\nCalling {:?} with {:?}", stringify!(#name), args);
-                self.invoke(args)
+            fn invoke<'a>(
+                &'a self,
+                args: &'a ::tool_protocol::serde_json::Value,
+            ) -> ::tool_protocol::ToolFuture<'a> {
+                ::std::boxed::Box::pin(async move {
+                    let args = match ::tool_protocol::parse_args::<#protocol>(
+                        ::tool_protocol::Tool::get_schema(self),
+                        args,
+                    ) {
+                        ::std::result::Result::Ok(args) => args,
+                        ::std::result::Result::Err(e) => {
+                            return ::tool_protocol::ToolOutput::error(
+                                ::tool_protocol::ErrorCode::InvalidArguments,
+                                e.to_string(),
+                            );
+                        }
+                    };
+                    match #call {
+                        ::std::result::Result::Ok(output) => ::tool_protocol::ToolOutput::from(output),
+                        ::std::result::Result::Err(e) => ::tool_protocol::ToolOutput::from(e),
+                    }
+                })
+            }
+
+            fn is_blocking(&self) -> bool {
+                !#is_async
+            }
+        }
+
+        ::tool_protocol::inventory::submit! {
+            ::tool_protocol::ToolRegistration { create: #name::create }
+        }
+    };
+
+    TokenStream::from(expanded)
+}
+
+// whether `#[serde(...)]` has the `flatten` flag, the values of the other keys are skipped
+fn has_serde_flatten(attrs: &[Attribute]) -> Result<bool, syn::Error> {
+    let mut flatten = false;
+    for attr in attrs.iter().filter(|a| a.path().is_ident("serde")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("flatten") {
+                flatten = true;
+            } else if meta.input.peek(Token![=]) {
+                meta.value()?.parse::<Expr>()?;
+            } else if meta.input.peek(syn::token::Paren) {
+                let content;
+                syn::parenthesized!(content in meta.input);
+                content.parse::<proc_macro2::TokenStream>()?;
+            }
+            Ok(())
+        })?;
+    }
+    Ok(flatten)
+}
+
+// the string values of `#[serde(rename = "...")]` and `#[serde(rename_all = "...")]`
+fn find_serde_rename(attrs: &[Attribute], key: &str) -> Result<Option<String>, syn::Error> {
+    for attr in attrs.iter().filter(|a| a.path().is_ident("serde")) {
+        let nested = attr.parse_args_with(Punctuated::<Meta, Token![,]>::parse_terminated)?;
+        for meta in nested {
+            if let Meta::NameValue(name_value) = meta
+                && name_value.path.is_ident(key)
+            {
+                return parse_name_value_string_attr(&name_value).map(Some);
+            }
+        }
+    }
+    Ok(None)
+}
+
+// renames a variant like serde does for `rename_all`
+fn rename_variant(name: &str, rule: &str) -> Option<String> {
+    let mut words = Vec::new();
+    for c in name.chars() {
+        if c.is_uppercase() || words.is_empty() {
+            words.push(String::new());
+        }
+        if let Some(word) = words.last_mut() {
+            word.push(c);
+        }
+    }
+    let lower = words.iter().map(|w| w.to_lowercase()).collect::<Vec<_>>();
+    let upper = words.iter().map(|w| w.to_uppercase()).collect::<Vec<_>>();
+    Some(match rule {
+        "lowercase" => name.to_lowercase(),
+        "UPPERCASE" => name.to_uppercase(),
+        "PascalCase" => name.to_string(),
+        "camelCase" => {
+            let mut chars = name.chars();
+            match chars.next() {
+                Some(first) => first.to_lowercase().chain(chars).collect(),
+                None => String::new(),
+            }
+        }
+        "snake_case" => lower.join("_"),
+        "SCREAMING_SNAKE_CASE" => upper.join("_"),
+        "kebab-case" => lower.join("-"),
+        "SCREAMING-KEBAB-CASE" => upper.join("-"),
+        _ => return None,
+    })
+}
+
+// #[derive(ToolEnum, Deserialize)]
+// enum Order { Name, Size }
+// expands to:
+// impl ToolEnum for Order {
+//     fn values() -> Vec<serde_json::Value> { vec!["Name".into(), "Size".into()] }
+// }
+// so that `#[tool_protocol(enum)]` allows the names serde reads for the variants
+/// Implements `ToolEnum` for an enum of unit variants, whose values are the names serde reads
+/// for the variants, following `#[serde(rename_all = "...")]` and `#[serde(rename = "...")]`.
+#[proc_macro_derive(ToolEnum, attributes(serde))]
+pub fn derive_tool_enum(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    let variants = match &input.data {
+        Data::Enum(data) => &data.variants,
+        _ => {
+            return syn::Error::new(input.span(), "Only enums are supported")
+                .to_compile_error()
+                .into();
+        }
+    };
+    let rule = match find_serde_rename(&input.attrs, "rename_all") {
+        Ok(rule) => rule,
+        Err(err) => return err.to_compile_error().into(),
+    };
+
+    let mut values = Vec::new();
+    for variant in variants {
+        if !matches!(variant.fields, Fields::Unit) {
+            return syn::Error::new(variant.span(), "Only unit variants are supported")
+                .to_compile_error()
+                .into();
+        }
+        let name = variant.ident.to_string();
+        let value = match find_serde_rename(&variant.attrs, "rename") {
+            Ok(Some(value)) => value,
+            Ok(None) => match &rule {
+                Some(rule) => match rename_variant(&name, rule) {
+                    Some(value) => value,
+                    None => {
+                        return syn::Error::new(input.span(), "Unknown `rename_all` rule")
+                            .to_compile_error()
+                            .into();
+                    }
+                },
+                None => name,
+            },
+            Err(err) => return err.to_compile_error().into(),
+        };
+        values.push(value);
+    }
+
+    let name = input.ident;
+    let expanded = quote! {
+        impl ::tool_protocol::ToolEnum for #name {
+            fn values() -> ::std::vec::Vec<::tool_protocol::serde_json::Value> {
+                ::std::vec![#(::tool_protocol::serde_json::Value::String(#values.to_string())),*]
             }
         }
     };
@@ -0,0 +1,9 @@
+// the expansions of the derive macros must compile and run, and each misuse must fail with its
+// diagnostic. run with `TRYBUILD=overwrite` to update the expected diagnostics in `ui/fail`.
+
+#[test]
+fn ui() {
+    let tests = trybuild::TestCases::new();
+    tests.pass("tests/ui/pass/*.rs");
+    tests.compile_fail("tests/ui/fail/*.rs");
+}
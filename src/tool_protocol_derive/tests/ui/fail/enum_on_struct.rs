@@ -0,0 +1,6 @@
+#[derive(tool_protocol_derive::ToolEnum)]
+struct Order {
+    value: u32,
+}
+
+fn main() {}
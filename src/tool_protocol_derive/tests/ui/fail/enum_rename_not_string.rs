@@ -0,0 +1,7 @@
+#[derive(tool_protocol_derive::ToolEnum)]
+enum Order {
+    #[serde(rename = 1)]
+    ByName,
+}
+
+fn main() {}
@@ -0,0 +1,7 @@
+#[derive(tool_protocol_derive::ToolEnum)]
+#[serde(rename_all = "Title Case")]
+enum Order {
+    ByName,
+}
+
+fn main() {}
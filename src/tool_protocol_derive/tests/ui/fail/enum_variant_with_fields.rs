@@ -0,0 +1,7 @@
+#[derive(tool_protocol_derive::ToolEnum)]
+enum Order {
+    ByName,
+    BySize(u32),
+}
+
+fn main() {}
@@ -0,0 +1,8 @@
+#[derive(tool_protocol_derive::ToolProtocol)]
+#[tool_protocol(name = "a", help = "A.")]
+struct Protocol {
+    #[tool_protocol(help = "A value.", default = 3, enum = [1, 2])]
+    value: u32,
+}
+
+fn main() {}
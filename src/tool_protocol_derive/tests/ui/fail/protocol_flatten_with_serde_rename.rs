@@ -0,0 +1,14 @@
+use serde::Deserialize;
+
+#[derive(tool_protocol_derive::ToolProtocol, Deserialize)]
+#[tool_protocol(name = "a", help = "A.")]
+struct Protocol {
+    #[tool_protocol(flatten)]
+    #[serde(rename = "flatten_mode", default)]
+    value: Page,
+}
+
+#[derive(Deserialize, Default)]
+struct Page;
+
+fn main() {}
@@ -0,0 +1,10 @@
+#[derive(tool_protocol_derive::ToolProtocol)]
+#[tool_protocol(name = "a", help = "A.")]
+struct Protocol {
+    #[tool_protocol(flatten)]
+    value: Page,
+}
+
+struct Page;
+
+fn main() {}
@@ -0,0 +1,7 @@
+#[derive(tool_protocol_derive::ToolProtocol)]
+#[tool_protocol(name = "a", help = "A.")]
+struct Protocol {
+    value: u32,
+}
+
+fn main() {}
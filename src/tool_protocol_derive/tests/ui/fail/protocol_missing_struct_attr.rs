@@ -0,0 +1,7 @@
+#[derive(tool_protocol_derive::ToolProtocol)]
+struct Protocol {
+    #[tool_protocol(help = "A value.", default = 1)]
+    value: u32,
+}
+
+fn main() {}
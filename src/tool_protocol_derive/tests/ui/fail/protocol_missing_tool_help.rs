@@ -0,0 +1,8 @@
+#[derive(tool_protocol_derive::ToolProtocol)]
+#[tool_protocol(name = "a")]
+struct Protocol {
+    #[tool_protocol(help = "A value.", default = 1)]
+    value: u32,
+}
+
+fn main() {}
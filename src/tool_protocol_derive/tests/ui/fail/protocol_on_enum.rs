@@ -0,0 +1,7 @@
+#[derive(tool_protocol_derive::ToolProtocol)]
+#[tool_protocol(name = "a", help = "A.")]
+enum Protocol {
+    Value,
+}
+
+fn main() {}
@@ -0,0 +1,5 @@
+#[derive(tool_protocol_derive::ToolProtocol)]
+#[tool_protocol(name = "a", help = "A.")]
+struct Protocol(u32);
+
+fn main() {}
@@ -0,0 +1,6 @@
+#[tool_protocol_derive::tool(Protocol, async, send)]
+struct Tool {
+    schema: u32,
+}
+
+fn main() {}
@@ -0,0 +1,6 @@
+#[tool_protocol_derive::tool(Protocol)]
+enum Tool {
+    Value,
+}
+
+fn main() {}
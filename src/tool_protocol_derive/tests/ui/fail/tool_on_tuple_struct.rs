@@ -0,0 +1,4 @@
+#[tool_protocol_derive::tool(Protocol)]
+struct Tool(u32);
+
+fn main() {}
@@ -0,0 +1,6 @@
+#[tool_protocol_derive::tool(Protocol)]
+struct Tool {
+    schema: u32,
+}
+
+fn main() {}
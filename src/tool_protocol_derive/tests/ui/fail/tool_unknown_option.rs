@@ -0,0 +1,6 @@
+#[tool_protocol_derive::tool(Protocol, sync)]
+struct Tool {
+    schema: u32,
+}
+
+fn main() {}
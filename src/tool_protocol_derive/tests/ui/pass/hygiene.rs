@@ -0,0 +1,47 @@
+// the generated code must not depend on the names in scope at the call site
+use serde::{Deserialize, Serialize};
+use tool_protocol_derive::{ToolEnum, ToolProtocol, tool};
+
+#[allow(dead_code)]
+struct ToolSchema;
+#[allow(dead_code)]
+struct ToolArgument;
+#[allow(dead_code)]
+struct ToolOutput;
+#[allow(dead_code)]
+type Result<T> = std::result::Result<T, ()>;
+#[allow(dead_code)]
+mod serde_json {}
+#[allow(dead_code)]
+mod anyhow {}
+
+#[derive(ToolEnum, Serialize, Deserialize, Debug)]
+#[serde(rename_all = "lowercase")]
+enum Mode {
+    Fast,
+    Slow,
+}
+
+#[derive(ToolProtocol, Serialize, Deserialize, Debug)]
+#[tool_protocol(name = "run", help = "Runs.")]
+struct RunToolProtocol {
+    #[tool_protocol(help = "The mode.", enum, default = "fast")]
+    mode: Mode,
+
+    #[tool_protocol(help = "The count.", default, min = 1)]
+    count: u32,
+}
+
+#[tool(RunToolProtocol)]
+struct Run;
+
+impl Run {
+    fn invoke(&self, args: RunToolProtocol) -> std::result::Result<String, ::anyhow::Error> {
+        Ok(format!("{:?} {}", args.mode, args.count))
+    }
+}
+
+fn main() {
+    let run = Run::create().unwrap();
+    assert_eq!(tool_protocol::Tool::get_schema(&*run).arguments.len(), 2);
+}
@@ -0,0 +1,78 @@
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::task::{Context, Poll, Waker};
+use tool_protocol::{ErrorCode, Tool, ToolOutput, registered_tools};
+use tool_protocol_derive::{ToolProtocol, tool};
+
+#[derive(ToolProtocol, Serialize, Deserialize, Debug)]
+#[tool_protocol(name = "echo", help = "Returns the text.")]
+struct EchoToolProtocol {
+    #[tool_protocol(help = "The text.", example = "hello", required)]
+    text: String,
+}
+
+#[derive(ToolProtocol, Serialize, Deserialize, Debug)]
+#[tool_protocol(name = "count", help = "Counts the calls.", read_only)]
+struct CountToolProtocol {}
+
+#[tool(EchoToolProtocol)]
+struct Echo;
+
+impl Echo {
+    fn invoke(&self, args: EchoToolProtocol) -> anyhow::Result<String> {
+        if args.text.is_empty() {
+            anyhow::bail!("The text is empty");
+        }
+        Ok(args.text)
+    }
+}
+
+// the other fields of a tool start from their defaults
+#[tool(CountToolProtocol, async)]
+struct Count {
+    calls: AtomicUsize,
+}
+
+impl Count {
+    async fn invoke(&self, _args: CountToolProtocol) -> anyhow::Result<ToolOutput> {
+        let calls = self.calls.fetch_add(1, Ordering::Relaxed) + 1;
+        Ok(ToolOutput::json(json!({ "calls": calls })))
+    }
+}
+
+// the futures of these tools never wait, so they are done when first polled
+fn call(tool: &dyn Tool, args: serde_json::Value) -> ToolOutput {
+    let mut future = tool.invoke(&args);
+    match future.as_mut().poll(&mut Context::from_waker(Waker::noop())) {
+        Poll::Ready(output) => output,
+        Poll::Pending => panic!("the call did not finish"),
+    }
+}
+
+fn main() {
+    let echo = Echo::create().unwrap();
+    assert!(echo.is_blocking());
+    assert_eq!(echo.get_schema().name, "echo");
+    assert_eq!(call(&*echo, json!({ "text": "hi" })), ToolOutput::text("hi"));
+
+    let failed = call(&*echo, json!({ "text": "" }));
+    assert!(failed.is_error);
+    assert_eq!(failed.error_code, Some(ErrorCode::Failed));
+
+    let invalid = call(&*echo, json!({}));
+    assert_eq!(invalid.error_code, Some(ErrorCode::InvalidArguments));
+
+    let count = Count::create().unwrap();
+    assert!(!count.is_blocking());
+    assert!(count.get_schema().read_only);
+    assert_eq!(call(&*count, json!({})), ToolOutput::json(json!({ "calls": 1 })));
+    assert_eq!(call(&*count, json!({})), ToolOutput::json(json!({ "calls": 2 })));
+
+    let names = registered_tools()
+        .unwrap()
+        .iter()
+        .map(|tool| tool.get_schema().name.clone())
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["count", "echo"]);
+}
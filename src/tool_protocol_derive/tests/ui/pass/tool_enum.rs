@@ -0,0 +1,36 @@
+use serde_json::json;
+use tool_protocol::ToolEnum;
+use tool_protocol_derive::ToolEnum;
+
+#[derive(ToolEnum)]
+enum Plain {
+    First,
+    SecondValue,
+}
+
+#[derive(ToolEnum)]
+#[serde(rename_all = "kebab-case")]
+enum Kebab {
+    FirstValue,
+    #[serde(rename = "other")]
+    SecondValue,
+}
+
+#[derive(ToolEnum)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+enum Screaming {
+    FirstValue,
+}
+
+#[derive(ToolEnum)]
+#[serde(rename_all = "camelCase")]
+enum Camel {
+    FirstValue,
+}
+
+fn main() {
+    assert_eq!(Plain::values(), [json!("First"), json!("SecondValue")]);
+    assert_eq!(Kebab::values(), [json!("first-value"), json!("other")]);
+    assert_eq!(Screaming::values(), [json!("FIRST_VALUE")]);
+    assert_eq!(Camel::values(), [json!("firstValue")]);
+}
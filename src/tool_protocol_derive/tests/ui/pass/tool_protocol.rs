@@ -0,0 +1,139 @@
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::BTreeMap;
+use tool_protocol::*;
+use tool_protocol_derive::{ToolEnum, ToolProtocol};
+
+const DEFAULT_LIMIT: u32 = 20;
+
+#[derive(ToolEnum, Serialize, Deserialize, Debug, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+enum Order {
+    #[default]
+    ByName,
+    BySize,
+}
+
+#[derive(ToolProtocol, Serialize, Deserialize, Debug, PartialEq)]
+#[tool_protocol(group)]
+struct Page {
+    #[tool_protocol(help = "The first result.", default = 0)]
+    offset: u32,
+    #[tool_protocol(help = "The number of results.", default = DEFAULT_LIMIT, min = 1, max = 100)]
+    limit: u32,
+}
+
+#[derive(ToolProtocol, Serialize, Deserialize, Debug, PartialEq)]
+#[tool_protocol(name = "list", help = "Lists files.", read_only)]
+struct List {
+    #[tool_protocol(help = "The directory.", example = "src", alias = "dir", required)]
+    path: String,
+    #[tool_protocol(help = "A pattern of names.", example = "*.rs")]
+    glob: Option<String>,
+    #[tool_protocol(help = "The order.", enum, default)]
+    order: Order,
+    #[tool_protocol(help = "The kind.", enum = ["file", "dir"], default = "file")]
+    kind: String,
+    #[tool_protocol(help = "The depth.", default = 1, min = -1, max = 8)]
+    depth: i32,
+    #[tool_protocol(help = "Whether to recurse.", default = false, deprecated = "use depth")]
+    recursive: bool,
+    #[tool_protocol(help = "For tests.", default, hidden)]
+    trace: bool,
+    #[tool_protocol(flatten)]
+    #[serde(flatten)]
+    page: Page,
+}
+
+// a map with keys that are not strings has no JSON value
+#[derive(ToolProtocol, Serialize, Deserialize, Debug)]
+#[tool_protocol(group)]
+struct Weights {
+    #[tool_protocol(help = "The weight of each path.", default = BTreeMap::from([(vec![1], 2)]))]
+    weights: BTreeMap<Vec<u32>, u32>,
+}
+
+#[derive(ToolProtocol, Serialize, Deserialize, Debug)]
+#[tool_protocol(name = "rank", help = "Ranks paths.")]
+struct Rank {
+    #[tool_protocol(flatten)]
+    #[serde(flatten)]
+    weights: Weights,
+}
+
+fn main() {
+    assert_eq!(
+        create_schema::<Rank>().unwrap_err().to_string(),
+        "The default of `weights` in `Weights` is not a JSON value: key must be a string"
+    );
+
+    let schema = create_schema::<List>().unwrap();
+    assert_eq!(schema.name, "list");
+    assert!(schema.read_only);
+    let names = schema
+        .arguments
+        .iter()
+        .map(|arg| arg.name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        names,
+        ["path", "glob", "order", "kind", "depth", "recursive", "trace", "offset", "limit"]
+    );
+    let glob = &schema.arguments[1];
+    assert!(!glob.required);
+    assert_eq!(glob.type_, "Option<String>");
+    assert_eq!(glob.default, None);
+    assert_eq!(schema.arguments[2].enum_values, [json!("by_name"), json!("by_size")]);
+    assert_eq!(schema.arguments[2].default, Some(json!("by_name")));
+    assert_eq!(schema.arguments[4].min, Some(-1.0));
+    assert_eq!(schema.arguments[8].default, Some(json!(20)));
+
+    // hidden and deprecated arguments are left out of the example and the help
+    assert_eq!(
+        schema.get_example(),
+        json!({
+            "path": "src",
+            "glob": "*.rs",
+            "order": "by_name",
+            "kind": "file",
+            "depth": 1,
+            "offset": 0,
+            "limit": 20,
+        })
+    );
+    assert!(schema.get_help()["arguments"].get("trace").is_none());
+
+    let list = parse_args::<List>(&schema, &json!({ "dir": "src", "limit": "5" })).unwrap();
+    assert_eq!(
+        list,
+        List {
+            path: "src".to_string(),
+            glob: None,
+            order: Order::ByName,
+            kind: "file".to_string(),
+            depth: 1,
+            recursive: false,
+            trace: false,
+            page: Page {
+                offset: 0,
+                limit: 5,
+            },
+        }
+    );
+
+    let error = parse_args::<List>(
+        &schema,
+        &json!({ "order": "by_date", "depth": 9, "size": 1 }),
+    )
+    .unwrap_err()
+    .to_string();
+    for problem in [
+        "unknown argument `size`",
+        "missing required argument `path`",
+        "`order` must be one of \"by_name\", \"by_size\", got \"by_date\"",
+        "`depth` must be at most 8, got 9",
+        "For example: ",
+    ] {
+        assert!(error.contains(problem), "{} is missing in {}", problem, error);
+    }
+}
@@ -1,47 +1,35 @@
 // this module implements the read command, which reads the contents of a text file.
 
-use crate::tools::{Tool, ToolSchema};
-use anyhow::Result;
+use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
-use std::cell::RefCell;
-use tool_protocol::ToolProtocol;
-use tool_protocol::{ToolArgument, canonicalize_tool_args, create_schema, parse_args};
-use tool_protocol_derive::{ToolProtocol, tool};
+use tool_protocol::{ToolOutput, ToolProtocol, tool};
 
 #[derive(ToolProtocol, Serialize, Deserialize, Debug)]
-#[tool_protocol(name = "read", help = "Reads the contents of a text file.")]
-struct ReadToolProtocol {
+#[tool_protocol(name = "read", help = "Reads the contents of a text file.", read_only)]
+pub struct ReadToolProtocol {
     #[tool_protocol(
         help = "The path to the file to read.",
         example = "/path/to/file",
+        alias = "file",
+        alias = "file_path",
         required
     )]
     path: String,
 }
 
 #[tool(ReadToolProtocol)]
-pub struct Read {
-    schema: ToolSchema,
-}
+pub struct Read;
 
 impl Read {
-    pub fn create() -> Box<RefCell<dyn Tool>> {
-        Box::new(RefCell::new(Self {
-            schema: create_schema::<ReadToolProtocol>(),
-        }))
-    }
-
-    pub fn invoke(&mut self, args: ReadToolProtocol) -> Result<String> {
+    pub fn invoke(&self, args: ReadToolProtocol) -> Result<ToolOutput> {
         let path = args.path;
-        let contents = std::fs::read_to_string(&path)?
+        let contents = std::fs::read_to_string(&path)
+            .with_context(|| format!("Cannot read {}", path))?
             .lines()
-            .into_iter()
             .enumerate()
-            .map(|(i, line)| {
-                format!("LINE {:05}: {}", i + 1, line)
-            })
+            .map(|(i, line)| format!("LINE {:05}: {}", i + 1, line))
             .collect::<Vec<_>>()
             .join("\n");
-        Ok(contents)
+        Ok(ToolOutput::text(contents).with_file(path))
     }
 }
@@ -0,0 +1,134 @@
+// this module implements the search command, which finds the lines of the files under a
+// directory that contain a text.
+
+use crate::files::IGNORED_DIRECTORIES;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use tool_protocol::{ErrorCode, ToolError, ToolOutput, ToolProtocol, tool};
+
+#[derive(ToolProtocol, Serialize, Deserialize, Debug)]
+#[tool_protocol(
+    name = "search",
+    help = "Finds the lines containing a text in the files under a directory, e.g. to locate a definition.",
+    read_only
+)]
+struct SearchToolProtocol {
+    #[tool_protocol(
+        help = "The text to search for.",
+        example = "KernelCache",
+        alias = "query",
+        alias = "pattern",
+        required
+    )]
+    text: String,
+
+    #[tool_protocol(
+        help = "The directory to search in, or a single file.",
+        example = "/path/to/directory",
+        alias = "directory",
+        default = "."
+    )]
+    path: String,
+
+    #[tool_protocol(help = "Whether to ignore the case of letters.", default = false)]
+    ignore_case: bool,
+
+    #[tool_protocol(
+        help = "The maximum number of matching lines to return.",
+        default = 50,
+        example = 50,
+        min = 1,
+        max = 1000
+    )]
+    max_results: u32,
+}
+
+#[tool(SearchToolProtocol)]
+pub struct Search;
+
+// appends the matching lines as `path:line: text`, and returns false once there are enough
+fn search_path(
+    path: &Path,
+    args: &SearchToolProtocol,
+    text: &str,
+    results: &mut Vec<String>,
+) -> bool {
+    if path.is_dir() {
+        let mut entries = match std::fs::read_dir(path) {
+            Ok(entries) => entries.filter_map(|entry| entry.ok()).collect::<Vec<_>>(),
+            Err(_) => return true,
+        };
+        entries.sort_by_key(|entry| entry.file_name());
+        for entry in entries {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if name.starts_with('.') || IGNORED_DIRECTORIES.contains(&name.as_str()) {
+                continue;
+            }
+            if !search_path(&entry.path(), args, text, results) {
+                return false;
+            }
+        }
+        return true;
+    }
+    // binary files are not valid UTF-8 and skipped
+    let Ok(contents) = std::fs::read_to_string(path) else {
+        return true;
+    };
+    for (i, line) in contents.lines().enumerate() {
+        let matches = if args.ignore_case {
+            line.to_lowercase().contains(text)
+        } else {
+            line.contains(text)
+        };
+        if matches {
+            if results.len() >= args.max_results as usize {
+                return false;
+            }
+            results.push(format!("{}:{}: {}", path.display(), i + 1, line.trim()));
+        }
+    }
+    true
+}
+
+impl Search {
+    fn invoke(&self, args: SearchToolProtocol) -> Result<ToolOutput> {
+        if args.text.is_empty() {
+            return Err(ToolError::new(
+                ErrorCode::InvalidArguments,
+                "The text to search for is empty",
+            )
+            .into());
+        }
+        let path = Path::new(&args.path);
+        if !path.exists() {
+            return Err(ToolError::new(
+                ErrorCode::NotFound,
+                format!("Path does not exist: {}", path.display()),
+            )
+            .into());
+        }
+        let text = if args.ignore_case {
+            args.text.to_lowercase()
+        } else {
+            args.text.clone()
+        };
+        let mut results = Vec::new();
+        let complete = search_path(path, &args, &text, &mut results);
+        if results.is_empty() {
+            return Ok(ToolOutput::text(format!(
+                "No lines contain `{}`.",
+                args.text
+            )));
+        }
+        if complete {
+            return Ok(ToolOutput::text(results.join("\n")));
+        }
+        let shown = results.len();
+        results.push(format!(
+            "(Only the first {} matches are shown, search in a subdirectory for more.)",
+            args.max_results
+        ));
+        Ok(ToolOutput::text(results.join("\n")).with_truncation(shown, None))
+    }
+}
@@ -1,24 +1,20 @@
 // this module implements the tree command, which lists the contents of a directory.
 
-use crate::tools::Tool;
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
-use std::cell::RefCell;
-use tool_protocol::{
-    ToolArgument, ToolProtocol, ToolSchema, canonicalize_tool_args, create_schema, parse_args,
-};
-use tool_protocol_derive::{ToolProtocol, tool};
+use tool_protocol::{ErrorCode, ToolError, ToolOutput, ToolProtocol, tool};
 
 #[derive(ToolProtocol, Serialize, Deserialize, Debug)]
 #[tool_protocol(
     name = "tree",
-    help = "Lists the contents of a directory, optionally with the given recursive depth."
+    help = "Lists the contents of a directory, optionally with the given recursive depth.",
+    read_only
 )]
 struct TreeToolProtocol {
     #[tool_protocol(
         help = "The path to the directory to list.",
         example = "/path/to/directory",
-        required = true,
+        alias = "directory",
         default = "."
     )]
     path: String,
@@ -26,15 +22,14 @@ struct TreeToolProtocol {
     #[tool_protocol(
         help = "The maximum depth to recurse into the directory.",
         default = 2,
-        example = 2
+        example = 2,
+        max = 10
     )]
     depth: u32,
 }
 
 #[tool(TreeToolProtocol)]
-pub struct Tree {
-    schema: ToolSchema,
-}
+pub struct Tree;
 
 fn list_directory_tree(path: &str, depth: u32) -> Result<serde_json::Value> {
     use std::fs;
@@ -70,10 +65,10 @@ fn list_directory_tree(path: &str, depth: u32) -> Result<serde_json::Value> {
             for entry in entries {
                 let entry = entry?;
                 let child_path = entry.path();
-                if let Ok(child) = build_tree(&child_path, current_depth + 1, max_depth) {
-                    if !child.is_null() {
-                        children.push(child);
-                    }
+                if let Ok(child) = build_tree(&child_path, current_depth + 1, max_depth)
+                    && !child.is_null()
+                {
+                    children.push(child);
                 }
             }
 
@@ -87,31 +82,31 @@ fn list_directory_tree(path: &str, depth: u32) -> Result<serde_json::Value> {
 
     let path = Path::new(path)
         .canonicalize()
-        .map_err(|e| anyhow::anyhow!("Failed to canonicalize path: {}", e))?;
+        .map_err(|e| anyhow::Error::new(e).context("Failed to canonicalize path"))?;
 
     if !path.exists() {
-        return Err(anyhow::anyhow!("Path does not exist: {}", path.display()));
+        return Err(ToolError::new(
+            ErrorCode::NotFound,
+            format!("Path does not exist: {}", path.display()),
+        )
+        .into());
     }
 
     if !path.is_dir() {
-        return Err(anyhow::anyhow!(
-            "Path is not a directory: {}",
-            path.display()
-        ));
+        return Err(ToolError::new(
+            ErrorCode::InvalidArguments,
+            format!("Path is not a directory: {}", path.display()),
+        )
+        .into());
     }
 
     build_tree(&path, 0, depth)
 }
 
 impl Tree {
-    pub fn create() -> Box<RefCell<dyn Tool>> {
-        Box::new(RefCell::new(Self {
-            schema: create_schema::<TreeToolProtocol>(),
-        }))
-    }
-
-    fn invoke(&mut self, args: TreeToolProtocol) -> Result<String> {
-        serde_json::to_string_pretty(&list_directory_tree(&args.path, args.depth)?)
-            .map_err(Into::into)
+    fn invoke(&self, args: TreeToolProtocol) -> Result<ToolOutput> {
+        Ok(ToolOutput::json(list_directory_tree(
+            &args.path, args.depth,
+        )?))
     }
 }
@@ -0,0 +1,215 @@
+// this module writes the transcript of a run, a JSONL file with one event per line: every
+// request sent to the model, its response or error, and every tool call with its result.
+// a transcript can be replayed, which answers the requests with the recorded responses
+// instead of contacting the provider.
+
+use crate::secret::Secret;
+use anyhow::Result;
+use std::collections::VecDeque;
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+pub const DEFAULT_TRANSCRIPT_DIR: &str = ".laluisa/transcripts";
+
+pub struct Transcript {
+    path: PathBuf,
+    file: Mutex<std::fs::File>,
+    secret: Secret,
+}
+
+fn now_millis() -> u128 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis()
+}
+
+impl Transcript {
+    // appends to `{dir}/{id}.jsonl`, so that a resumed session continues its transcript.
+    // occurrences of the secret are redacted from every event.
+    pub fn open(dir: &Path, id: &str, secret: Secret) -> Result<Self> {
+        std::fs::create_dir_all(dir).map_err(|e| {
+            anyhow::anyhow!(
+                "Cannot create transcript directory {}: {}",
+                dir.display(),
+                e
+            )
+        })?;
+        let path = dir.join(format!("{}.jsonl", id));
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .map_err(|e| anyhow::anyhow!("Cannot open transcript {}: {}", path.display(), e))?;
+        Ok(Self {
+            path,
+            file: Mutex::new(file),
+            secret,
+        })
+    }
+
+    pub fn get_path(&self) -> &Path {
+        &self.path
+    }
+
+    // a failure to write the transcript is reported but never stops the run
+    pub fn record(&self, event: &str, data: serde_json::Value) {
+        let mut line = serde_json::json!({
+            "time": now_millis() as u64,
+            "event": event,
+        });
+        if let serde_json::Value::Object(data) = data {
+            for (key, value) in data {
+                line[key] = value;
+            }
+        }
+        // the strings are redacted before serializing, where a secret containing e.g. `"`
+        // would be escaped
+        redact_strings(&mut line, &self.secret);
+        let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        if let Err(e) = writeln!(file, "{}", line) {
+            eprintln!("Failed to write transcript {}: {}", self.path.display(), e);
+        }
+    }
+}
+
+fn redact_strings(value: &mut serde_json::Value, secret: &Secret) {
+    match value {
+        serde_json::Value::String(text) => *text = secret.redact(text),
+        serde_json::Value::Array(items) => {
+            for item in items {
+                redact_strings(item, secret);
+            }
+        }
+        serde_json::Value::Object(fields) => {
+            let redacted = std::mem::take(fields)
+                .into_iter()
+                .map(|(key, mut value)| {
+                    redact_strings(&mut value, secret);
+                    (secret.redact(&key), value)
+                })
+                .collect();
+            *fields = redacted;
+        }
+        _ => {}
+    }
+}
+
+// the recorded outcomes of the requests of a transcript, in order
+pub struct Replay {
+    path: PathBuf,
+    outcomes: Mutex<VecDeque<Result<serde_json::Value, String>>>,
+}
+
+impl Replay {
+    pub fn load(path: &Path) -> Result<Self> {
+        let file = std::fs::File::open(path)
+            .map_err(|e| anyhow::anyhow!("Cannot open transcript {}: {}", path.display(), e))?;
+        let mut outcomes = VecDeque::new();
+        for (i, line) in std::io::BufReader::new(file).lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let event: serde_json::Value = serde_json::from_str(&line).map_err(|e| {
+                anyhow::anyhow!(
+                    "Invalid transcript {} at line {}: {}",
+                    path.display(),
+                    i + 1,
+                    e
+                )
+            })?;
+            match event["event"].as_str() {
+                Some("response") => outcomes.push_back(Ok(event)),
+                Some("error") => {
+                    outcomes.push_back(Err(event["error"].as_str().unwrap_or_default().to_string()))
+                }
+                _ => {}
+            }
+        }
+        Ok(Self {
+            path: path.to_path_buf(),
+            outcomes: Mutex::new(outcomes),
+        })
+    }
+
+    pub fn get_path(&self) -> &Path {
+        &self.path
+    }
+
+    // returns the next recorded response event, or the recorded error of the request
+    pub fn next(&self) -> Result<serde_json::Value> {
+        let mut outcomes = self.outcomes.lock().unwrap_or_else(|e| e.into_inner());
+        match outcomes.pop_front() {
+            Some(Ok(response)) => Ok(response),
+            Some(Err(error)) => Err(anyhow::anyhow!("{}", error)),
+            None => Err(anyhow::anyhow!(
+                "The transcript {} has no more responses to replay",
+                self.path.display()
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escaped_secrets_are_redacted() {
+        let dir = std::env::temp_dir().join(format!("laluisa-transcript-{}", std::process::id()));
+        let secret = Secret::new("to\"k\\en");
+        let transcript = Transcript::open(&dir, "escaped", secret.clone()).unwrap();
+        transcript.record(
+            "request",
+            serde_json::json!({
+                "headers": { "Authorization": format!("Bearer {}", secret.expose()) },
+                "messages": [secret.expose()],
+            }),
+        );
+        let text = std::fs::read_to_string(transcript.get_path()).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(!text.contains("to\\\"k\\\\en"));
+        let event: serde_json::Value = serde_json::from_str(&text).unwrap();
+        assert_eq!(event["headers"]["Authorization"], "Bearer [REDACTED]");
+        assert_eq!(event["messages"][0], "[REDACTED]");
+    }
+
+    #[test]
+    fn replay_answers_with_the_recorded_outcomes() {
+        let dir = std::env::temp_dir().join(format!("laluisa-replay-{}", std::process::id()));
+        let transcript = Transcript::open(&dir, "replay", Secret::new("sk-secret")).unwrap();
+        transcript.record("request", serde_json::json!({ "messages": ["hello"] }));
+        transcript.record(
+            "response",
+            serde_json::json!({ "content": "the token is sk-secret" }),
+        );
+        transcript.record("tool", serde_json::json!({ "output": "done" }));
+        transcript.record("request", serde_json::json!({ "messages": ["again"] }));
+        transcript.record(
+            "error",
+            serde_json::json!({ "error": "401 Unauthorized for sk-secret" }),
+        );
+        let replay = Replay::load(transcript.get_path()).unwrap();
+        let response = replay.next();
+        let error = replay.next();
+        let exhausted = replay.next();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let response = response.unwrap();
+        assert_eq!(response["event"], "response");
+        assert_eq!(response["content"], "the token is [REDACTED]");
+        assert_eq!(
+            error.unwrap_err().to_string(),
+            "401 Unauthorized for [REDACTED]"
+        );
+        assert!(
+            exhausted
+                .unwrap_err()
+                .to_string()
+                .ends_with("has no more responses to replay")
+        );
+    }
+}
@@ -0,0 +1,687 @@
+// this module implements the optional terminal UI of a documentation run. the pipeline runs
+// on a worker thread and reports to `TuiMonitor`, which forwards the events to the UI thread
+// and waits there for the decisions of the user (pausing, reviewing edits and tool calls,
+// adding messages).
+
+use crate::agent::{Reply, Usage};
+use crate::monitor::{self, Control, DocumentOutcome, Event, Monitor, Review};
+use anyhow::Result;
+use ratatui::crossterm::event::{self, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, List, ListItem, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{Receiver, Sender};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+
+const MAX_TOOL_RESULT_LENGTH: usize = 4096;
+
+enum UiEvent {
+    Request,
+    Delta(Reply),
+    Reply(Reply),
+    ToolCall(String),
+    ToolResult {
+        output: String,
+        is_error: bool,
+    },
+    Document {
+        path: PathBuf,
+        outcome: DocumentOutcome,
+    },
+    // an approved edit, shown until the next tool call
+    Preview {
+        title: String,
+        body: String,
+    },
+    Review {
+        title: String,
+        body: String,
+        pending: Pending,
+    },
+    Notice(String),
+    Done,
+    Finished(Result<()>),
+}
+
+#[derive(Default)]
+struct Controls {
+    paused: bool,
+    stopped: bool,
+    auto_approve: bool,
+    messages: Vec<String>,
+    cancellation: CancellationToken,
+}
+
+type SharedControls = Arc<(Mutex<Controls>, Condvar)>;
+
+// a review waiting for the decision of the user. `text` is what the user may edit.
+struct Pending {
+    text: String,
+    extension: String,
+    decision: Sender<Review>,
+}
+
+pub struct TuiMonitor {
+    events: Mutex<Sender<UiEvent>>,
+    controls: SharedControls,
+}
+
+impl TuiMonitor {
+    fn send(&self, event: UiEvent) {
+        let events = self.events.lock().unwrap_or_else(|e| e.into_inner());
+        // the UI may already be closed, then the run is about to end anyway
+        let _ = events.send(event);
+    }
+
+    fn is_auto_approved(&self) -> bool {
+        let controls = self.controls.0.lock().unwrap_or_else(|e| e.into_inner());
+        controls.auto_approve
+    }
+
+    fn review(&self, title: String, body: String, text: &str, extension: &str) -> Review {
+        let (decision, receiver) = std::sync::mpsc::channel();
+        self.send(UiEvent::Review {
+            title,
+            body,
+            pending: Pending {
+                text: text.to_string(),
+                extension: extension.to_string(),
+                decision,
+            },
+        });
+        receiver.recv().unwrap_or(Review::Reject(
+            "The run was stopped by the user.".to_string(),
+        ))
+    }
+}
+
+impl Monitor for TuiMonitor {
+    fn notify(&self, event: Event) {
+        let event = match event {
+            Event::Prompt(_) => return,
+            Event::Request => UiEvent::Request,
+            Event::Delta(delta) => UiEvent::Delta(delta.clone()),
+            Event::Reply(reply) => UiEvent::Reply(reply.clone()),
+            Event::ToolCall { tool, args } => UiEvent::ToolCall(format!("{} {}", tool, args)),
+            Event::ToolResult { output, is_error } => UiEvent::ToolResult {
+                output: output.trim().to_string(),
+                is_error,
+            },
+            Event::Document { path, outcome } => UiEvent::Document {
+                path: path.to_path_buf(),
+                outcome,
+            },
+            Event::RequestFailed(error) => UiEvent::Notice(format!("Request failed: {}", error)),
+            Event::Notice(notice) => UiEvent::Notice(notice.to_string()),
+            Event::Done => UiEvent::Done,
+        };
+        self.send(event);
+    }
+
+    fn before_request(&self) -> Control {
+        let (controls, resumed) = &*self.controls;
+        let mut controls = controls.lock().unwrap_or_else(|e| e.into_inner());
+        while controls.paused && !controls.stopped {
+            controls = resumed.wait(controls).unwrap_or_else(|e| e.into_inner());
+        }
+        if controls.stopped {
+            Control::Stop
+        } else {
+            Control::Continue(std::mem::take(&mut controls.messages))
+        }
+    }
+
+    fn review_edit(&self, path: &Path, diff: &str, updated: &str) -> Review {
+        if self.is_auto_approved() {
+            self.send(UiEvent::Preview {
+                title: format!(" Edit of {} ", path.display()),
+                body: diff.to_string(),
+            });
+            return Review::Approve;
+        }
+        let extension = path
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_string())
+            .unwrap_or_default();
+        self.review(
+            format!(" Pending edit of {} ", path.display()),
+            diff.to_string(),
+            updated,
+            &extension,
+        )
+    }
+
+    fn review_tool(&self, tool: &str, args: &serde_json::Value) -> Review {
+        if self.is_auto_approved() {
+            return Review::Approve;
+        }
+        let args = serde_json::to_string_pretty(args).unwrap_or_default();
+        self.review(
+            format!(" Pending call of {} ", tool),
+            args.clone(),
+            &args,
+            "json",
+        )
+    }
+
+    fn get_cancellation(&self) -> CancellationToken {
+        let controls = self.controls.0.lock().unwrap_or_else(|e| e.into_inner());
+        controls.cancellation.clone()
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Input {
+    // a message added before the next request
+    Message,
+    // the comment of a rejection
+    Comment,
+}
+
+enum Action {
+    None,
+    Edit,
+    Quit,
+}
+
+struct FileEntry {
+    path: PathBuf,
+    outcome: Option<DocumentOutcome>,
+}
+
+struct App {
+    title: String,
+    files: Vec<FileEntry>,
+    turn: usize,
+    reasoning: String,
+    content: String,
+    expand_reasoning: bool,
+    tool_call: String,
+    tool_result: String,
+    tool_failed: bool,
+    // the title and the body of the shown review or preview
+    review: Option<(String, String)>,
+    pending: Option<Pending>,
+    usage: Usage,
+    // completion tokens estimated from the length of replies without usage
+    estimated_tokens: u64,
+    input_price: Option<f64>,
+    output_price: Option<f64>,
+    notice: String,
+    input: Option<(Input, String)>,
+    finished: Option<Result<()>>,
+    controls: SharedControls,
+}
+
+fn canonical(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or(path.to_path_buf())
+}
+
+// splits the text into lines of at most `width` characters and returns the last `height`
+fn tail_lines(text: &str, width: usize, height: usize) -> Vec<String> {
+    let width = width.max(1);
+    let mut lines = Vec::new();
+    for line in text.lines() {
+        let chars = line.chars().collect::<Vec<_>>();
+        if chars.is_empty() {
+            lines.push(String::new());
+        }
+        for chunk in chars.chunks(width) {
+            lines.push(chunk.iter().collect());
+        }
+    }
+    let skip = lines.len().saturating_sub(height);
+    lines.split_off(skip)
+}
+
+impl App {
+    fn with_controls<T>(&self, f: impl FnOnce(&mut Controls) -> T) -> T {
+        let (controls, resumed) = &*self.controls;
+        let result = f(&mut controls.lock().unwrap_or_else(|e| e.into_inner()));
+        resumed.notify_all();
+        result
+    }
+
+    fn handle(&mut self, event: UiEvent) {
+        match event {
+            UiEvent::Request => {
+                self.turn += 1;
+                self.reasoning.clear();
+                self.content.clear();
+            }
+            UiEvent::Delta(delta) => {
+                self.reasoning.push_str(&delta.reasoning);
+                self.content.push_str(&delta.content);
+            }
+            UiEvent::Reply(reply) => match reply.usage {
+                Some(usage) => {
+                    self.usage.prompt_tokens += usage.prompt_tokens;
+                    self.usage.completion_tokens += usage.completion_tokens;
+                }
+                None => {
+                    self.estimated_tokens +=
+                        ((reply.reasoning.len() + reply.content.len()) / 4) as u64
+                }
+            },
+            UiEvent::ToolCall(call) => {
+                self.tool_call = call;
+                self.tool_result.clear();
+                self.review = None;
+            }
+            UiEvent::ToolResult { output, is_error } => {
+                self.tool_result = output.chars().take(MAX_TOOL_RESULT_LENGTH).collect();
+                self.tool_failed = is_error;
+            }
+            UiEvent::Document { path, outcome } => {
+                let path = canonical(&path);
+                match self.files.iter_mut().find(|file| file.path == path) {
+                    Some(file) => file.outcome = Some(outcome),
+                    None => self.files.push(FileEntry {
+                        path,
+                        outcome: Some(outcome),
+                    }),
+                }
+            }
+            UiEvent::Preview { title, body } => self.review = Some((title, body)),
+            UiEvent::Review {
+                title,
+                body,
+                pending,
+            } => {
+                self.review = Some((title, body));
+                self.pending = Some(pending);
+                self.notice = "Waiting for review: [a]pprove, [r]eject or [e]dit".to_string();
+            }
+            UiEvent::Notice(notice) => self.notice = notice,
+            UiEvent::Done => self.notice = "The model reported that it is done.".to_string(),
+            UiEvent::Finished(result) => self.finished = Some(result),
+        }
+    }
+
+    fn decide(&mut self, review: Review) {
+        if let Some(pending) = self.pending.take() {
+            let _ = pending.decision.send(review);
+            self.notice.clear();
+        }
+    }
+
+    fn submit(&mut self, kind: Input, text: String) {
+        match kind {
+            Input::Message => {
+                if !text.trim().is_empty() {
+                    self.with_controls(|controls| controls.messages.push(text));
+                    self.notice = "The message will be sent with the next request.".to_string();
+                }
+            }
+            Input::Comment => self.decide(Review::Reject(if text.trim().is_empty() {
+                "The user rejected it without a comment.".to_string()
+            } else {
+                text
+            })),
+        }
+    }
+
+    fn handle_key(&mut self, code: KeyCode) -> Action {
+        if let Some((kind, input)) = &mut self.input {
+            match code {
+                KeyCode::Enter => {
+                    let (kind, text) = (*kind, std::mem::take(input));
+                    self.input = None;
+                    self.submit(kind, text);
+                }
+                KeyCode::Esc => self.input = None,
+                KeyCode::Backspace => {
+                    input.pop();
+                }
+                KeyCode::Char(c) => input.push(c),
+                _ => {}
+            }
+            return Action::None;
+        }
+        match code {
+            KeyCode::Char('q') | KeyCode::Esc => {
+                self.with_controls(|controls| {
+                    controls.stopped = true;
+                    controls.cancellation.cancel();
+                });
+                self.decide(Review::Reject(
+                    "The run was stopped by the user.".to_string(),
+                ));
+                return Action::Quit;
+            }
+            KeyCode::Char('p') => {
+                let paused = self.with_controls(|controls| {
+                    controls.paused = !controls.paused;
+                    controls.paused
+                });
+                self.notice = if paused {
+                    "Paused before the next request.".to_string()
+                } else {
+                    "Resumed.".to_string()
+                };
+            }
+            KeyCode::Char('a') => self.decide(Review::Approve),
+            KeyCode::Char('r') if self.pending.is_some() => {
+                self.input = Some((Input::Comment, String::new()))
+            }
+            KeyCode::Char('e') if self.pending.is_some() => return Action::Edit,
+            KeyCode::Char('A') => {
+                let auto_approve = self.with_controls(|controls| {
+                    controls.auto_approve = !controls.auto_approve;
+                    controls.auto_approve
+                });
+                self.notice = format!(
+                    "Edits and tool calls are {}.",
+                    if auto_approve {
+                        "approved automatically"
+                    } else {
+                        "reviewed"
+                    }
+                );
+                if auto_approve {
+                    self.decide(Review::Approve);
+                }
+            }
+            KeyCode::Char('i') => self.input = Some((Input::Message, String::new())),
+            KeyCode::Char('t') => self.expand_reasoning = !self.expand_reasoning,
+            _ => {}
+        }
+        Action::None
+    }
+
+    fn draw(&self, frame: &mut Frame) {
+        let [main, footer] =
+            Layout::vertical([Constraint::Min(0), Constraint::Length(1)]).areas(frame.area());
+        let [left, right] =
+            Layout::horizontal([Constraint::Percentage(70), Constraint::Percentage(30)])
+                .areas(main);
+        let [output, tool] =
+            Layout::vertical([Constraint::Percentage(60), Constraint::Percentage(40)]).areas(left);
+        let [files, usage] =
+            Layout::vertical([Constraint::Min(0), Constraint::Length(7)]).areas(right);
+        self.draw_output(frame, output);
+        if self.review.is_some() && (self.pending.is_some() || self.tool_result.is_empty()) {
+            self.draw_review(frame, tool);
+        } else {
+            self.draw_tool(frame, tool);
+        }
+        self.draw_files(frame, files);
+        self.draw_usage(frame, usage);
+        self.draw_footer(frame, footer);
+    }
+
+    fn draw_output(&self, frame: &mut Frame, area: Rect) {
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .title(format!(" {} - turn {} ", self.title, self.turn));
+        let inner = block.inner(area);
+        let width = inner.width as usize;
+        let mut lines = Vec::new();
+        if !self.reasoning.is_empty() {
+            let style = Style::default().fg(Color::DarkGray);
+            if self.expand_reasoning {
+                lines.extend(
+                    tail_lines(&self.reasoning, width, inner.height as usize)
+                        .into_iter()
+                        .map(|line| Line::styled(line, style)),
+                );
+            } else {
+                lines.push(Line::styled(
+                    format!(
+                        "▸ reasoning ({} characters, [t] to expand)",
+                        self.reasoning.chars().count()
+                    ),
+                    style,
+                ));
+            }
+        }
+        let height = (inner.height as usize).saturating_sub(lines.len());
+        lines.extend(
+            tail_lines(&self.content, width, height)
+                .into_iter()
+                .map(Line::raw),
+        );
+        let skip = lines.len().saturating_sub(inner.height as usize);
+        frame.render_widget(Paragraph::new(lines.split_off(skip)).block(block), area);
+    }
+
+    fn draw_tool(&self, frame: &mut Frame, area: Rect) {
+        let block = Block::default().borders(Borders::ALL).title(" Tool ");
+        let inner = block.inner(area);
+        let mut lines = vec![Line::styled(
+            self.tool_call.clone(),
+            Style::default().add_modifier(Modifier::BOLD),
+        )];
+        let style = if self.tool_failed {
+            Style::default().fg(Color::Red)
+        } else {
+            Style::default()
+        };
+        let height = (inner.height as usize).saturating_sub(1);
+        lines.extend(
+            tail_lines(&self.tool_result, inner.width as usize, height)
+                .into_iter()
+                .map(|line| Line::styled(line, style)),
+        );
+        frame.render_widget(Paragraph::new(lines).block(block), area);
+    }
+
+    fn draw_review(&self, frame: &mut Frame, area: Rect) {
+        let (title, body) = match &self.review {
+            Some(review) => review,
+            None => return,
+        };
+        let lines = body
+            .lines()
+            .take(area.height as usize)
+            .map(|line| {
+                let color = if line.starts_with("+++") || line.starts_with("---") {
+                    Color::White
+                } else if line.starts_with('+') {
+                    Color::Green
+                } else if line.starts_with('-') {
+                    Color::Red
+                } else if line.starts_with("@@") {
+                    Color::Cyan
+                } else {
+                    Color::Gray
+                };
+                Line::styled(line.to_string(), Style::default().fg(color))
+            })
+            .collect::<Vec<_>>();
+        let block = Block::default().borders(Borders::ALL).title(title.as_str());
+        frame.render_widget(Paragraph::new(lines).block(block), area);
+    }
+
+    fn draw_files(&self, frame: &mut Frame, area: Rect) {
+        let done = self
+            .files
+            .iter()
+            .filter(|file| {
+                matches!(
+                    file.outcome,
+                    Some(DocumentOutcome::Written | DocumentOutcome::DryRun)
+                )
+            })
+            .count();
+        let items = self
+            .files
+            .iter()
+            .map(|file| {
+                let (symbol, color) = match file.outcome {
+                    None => ("·", Color::Gray),
+                    Some(DocumentOutcome::Written) => ("✓", Color::Green),
+                    Some(DocumentOutcome::DryRun) => ("○", Color::Green),
+                    Some(DocumentOutcome::Rejected) => ("✗", Color::Yellow),
+                    Some(DocumentOutcome::Failed) => ("!", Color::Red),
+                };
+                let name = file
+                    .path
+                    .file_name()
+                    .map(|name| name.to_string_lossy().to_string())
+                    .unwrap_or_default();
+                ListItem::new(Line::from(vec![
+                    Span::styled(format!("{} ", symbol), Style::default().fg(color)),
+                    Span::raw(name),
+                ]))
+            })
+            .collect::<Vec<_>>();
+        let block = Block::default().borders(Borders::ALL).title(format!(
+            " Files {}/{} ",
+            done,
+            self.files.len()
+        ));
+        frame.render_widget(List::new(items).block(block), area);
+    }
+
+    fn draw_usage(&self, frame: &mut Frame, area: Rect) {
+        let mut lines = vec![
+            Line::raw(format!("prompt:     {}", self.usage.prompt_tokens)),
+            Line::raw(format!("completion: {}", self.usage.completion_tokens)),
+        ];
+        if self.estimated_tokens > 0 {
+            lines.push(Line::raw(format!("estimated:  ~{}", self.estimated_tokens)));
+        }
+        if self.input_price.is_some() || self.output_price.is_some() {
+            let cost = (self.usage.prompt_tokens as f64 * self.input_price.unwrap_or_default()
+                + (self.usage.completion_tokens + self.estimated_tokens) as f64
+                    * self.output_price.unwrap_or_default())
+                / 1e6;
+            lines.push(Line::raw(format!("cost:       {:.4}", cost)));
+        }
+        let block = Block::default().borders(Borders::ALL).title(" Tokens ");
+        frame.render_widget(Paragraph::new(lines).block(block), area);
+    }
+
+    fn draw_footer(&self, frame: &mut Frame, area: Rect) {
+        let line = if let Some((kind, input)) = &self.input {
+            let label = match kind {
+                Input::Message => "Message: ",
+                Input::Comment => "Comment for the model: ",
+            };
+            Line::from(vec![
+                Span::styled(label, Style::default().fg(Color::Cyan)),
+                Span::raw(format!("{}▏", input)),
+            ])
+        } else if let Some(result) = &self.finished {
+            let status = match result {
+                Ok(()) => "The run has finished".to_string(),
+                Err(e) => format!("The run failed: {}", e),
+            };
+            Line::raw(format!("{}, press [q] to exit.", status))
+        } else {
+            let keys = "[p]ause [a]pprove [r]eject [e]dit [A]uto-approve [i]nject [t]hink [q]uit";
+            if self.notice.is_empty() {
+                Line::raw(keys)
+            } else {
+                Line::from(vec![
+                    Span::styled(
+                        format!("{}  ", self.notice),
+                        Style::default().fg(Color::Yellow),
+                    ),
+                    Span::raw(keys),
+                ])
+            }
+        };
+        frame.render_widget(Paragraph::new(line), area);
+    }
+}
+
+fn run_app(terminal: &mut DefaultTerminal, app: &mut App, events: Receiver<UiEvent>) -> Result<()> {
+    loop {
+        let mut redraw = false;
+        while let Ok(event) = events.try_recv() {
+            // tools may print to the terminal, so the screen is redrawn from scratch after them
+            redraw |= matches!(event, UiEvent::ToolResult { .. });
+            app.handle(event);
+        }
+        // a failed redraw only leaves stray output on the screen, so it is not an error
+        if redraw {
+            let _ = terminal.clear();
+        }
+        terminal.draw(|frame| app.draw(frame))?;
+        if event::poll(Duration::from_millis(50))?
+            && let event::Event::Key(key) = event::read()?
+            && key.kind == KeyEventKind::Press
+        {
+            match app.handle_key(key.code) {
+                Action::None => {}
+                Action::Edit => edit_pending(terminal, app),
+                Action::Quit => return Ok(()),
+            }
+        }
+    }
+}
+
+// leaves the UI while the user edits the pending text in the editor
+fn edit_pending(terminal: &mut DefaultTerminal, app: &mut App) {
+    let Some(pending) = &app.pending else {
+        return;
+    };
+    ratatui::restore();
+    let edited = monitor::edit_text(&pending.text, &pending.extension);
+    *terminal = ratatui::init();
+    match edited {
+        Ok(text) => app.decide(Review::Edit(text)),
+        Err(e) => app.notice = e.to_string(),
+    }
+}
+
+// runs `worker` on its own thread with a monitor showing its progress, and returns when the
+// user closes the UI. the files are listed in the progress pane before they are documented.
+pub fn run<F>(
+    title: &str,
+    files: &[String],
+    prices: (Option<f64>, Option<f64>),
+    worker: F,
+) -> Result<()>
+where
+    F: FnOnce(Arc<dyn Monitor>) -> Result<()> + Send + 'static,
+{
+    let (sender, receiver) = std::sync::mpsc::channel();
+    let controls = SharedControls::default();
+    let monitor = Arc::new(TuiMonitor {
+        events: Mutex::new(sender.clone()),
+        controls: controls.clone(),
+    });
+    std::thread::spawn(move || {
+        let result = worker(monitor);
+        let _ = sender.send(UiEvent::Finished(result));
+    });
+    let mut app = App {
+        title: title.to_string(),
+        files: files
+            .iter()
+            .map(|path| FileEntry {
+                path: canonical(Path::new(path)),
+                outcome: None,
+            })
+            .collect(),
+        turn: 0,
+        reasoning: String::new(),
+        content: String::new(),
+        expand_reasoning: false,
+        tool_call: String::new(),
+        tool_result: String::new(),
+        tool_failed: false,
+        review: None,
+        pending: None,
+        usage: Usage::default(),
+        estimated_tokens: 0,
+        input_price: prices.0,
+        output_price: prices.1,
+        notice: String::new(),
+        input: None,
+        finished: None,
+        controls,
+    };
+    let mut terminal = ratatui::init();
+    let result = run_app(&mut terminal, &mut app, receiver);
+    ratatui::restore();
+    result
+}
@@ -0,0 +1,270 @@
+// this module implements the optional verification hook, which runs a user-configured
+// command (e.g. `cargo check` or `clang -fsyntax-only {file}`) after each documented file.
+
+use crate::config::Config;
+use anyhow::Result;
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::{Arc, Mutex, mpsc};
+use std::time::{Duration, Instant};
+
+const MAX_DIAGNOSTICS_LENGTH: usize = 8192;
+
+// how long the output is waited for after the command ended, as processes it left in the
+// background may keep the pipes open
+const OUTPUT_GRACE_PERIOD: Duration = Duration::from_secs(1);
+
+pub struct Verification {
+    pub command: String,
+    pub success: bool,
+    pub output: String,
+}
+
+pub struct Verifier {
+    commands: HashMap<String, String>,
+    root: PathBuf,
+    timeout: Duration,
+}
+
+impl Verifier {
+    // reads the optional `verify` (extension -> command) and `verify_timeout` (seconds) config keys.
+    // commands may refer to `{file}` and `{codebase}`, and run in the codebase directory.
+    pub fn from_config(config: &Config, root: &Path) -> Result<Self> {
+        Ok(Self {
+            commands: config
+                .verify
+                .iter()
+                .map(|(extension, command)| {
+                    (
+                        extension.trim_start_matches('.').to_lowercase(),
+                        command.clone(),
+                    )
+                })
+                .collect(),
+            root: root.to_path_buf(),
+            timeout: Duration::from_secs(config.verify_timeout),
+        })
+    }
+
+    pub fn get_command(&self, path: &Path) -> Option<String> {
+        let extension = path.extension()?.to_str()?.to_lowercase();
+        let command = self.commands.get(&extension)?;
+        Some(
+            command
+                .replace("{file}", &shell_quote(&path.to_string_lossy()))
+                .replace("{codebase}", &shell_quote(&self.root.to_string_lossy())),
+        )
+    }
+
+    // returns `None` when no command is configured for the file
+    pub fn verify(&self, path: &Path) -> Result<Option<Verification>> {
+        match self.get_command(path) {
+            Some(command) => {
+                let (success, output) = run_command(&command, &self.root, self.timeout)?;
+                Ok(Some(Verification {
+                    command,
+                    success,
+                    output: truncate_diagnostics(&output),
+                }))
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+fn shell_quote(text: &str) -> String {
+    if cfg!(windows) {
+        format!("\"{}\"", text)
+    } else {
+        format!("'{}'", text.replace('\'', r"'\''"))
+    }
+}
+
+fn truncate_diagnostics(output: &str) -> String {
+    let output = output.trim();
+    if output.is_empty() {
+        return "(no output)".to_string();
+    }
+    if output.len() <= MAX_DIAGNOSTICS_LENGTH {
+        return output.to_string();
+    }
+    // the first diagnostics are usually the most relevant ones
+    let mut end = MAX_DIAGNOSTICS_LENGTH;
+    while !output.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!(
+        "{}\n... ({} more bytes truncated)",
+        &output[..end],
+        output.len() - end
+    )
+}
+
+// the command runs in its own process group, so that the processes it starts, e.g. `rustc`
+// under `cargo`, are killed with it
+#[cfg(unix)]
+fn kill(child: &mut Child) {
+    // SAFETY: `killpg` has no memory effects, the group is the one of the child, which has not
+    // been waited for yet
+    unsafe {
+        libc::killpg(child.id() as libc::pid_t, libc::SIGKILL);
+    }
+    let _ = child.kill();
+}
+
+#[cfg(not(unix))]
+fn kill(child: &mut Child) {
+    let _ = child.kill();
+}
+
+// reads the pipe on a thread. the output read so far can be taken even if the pipe is never
+// closed, and the receiver tells when it is.
+struct BackgroundReader {
+    output: Arc<Mutex<Vec<u8>>>,
+    closed: mpsc::Receiver<()>,
+}
+
+impl BackgroundReader {
+    fn new(mut pipe: impl Read + Send + 'static) -> Self {
+        let output = Arc::new(Mutex::new(Vec::new()));
+        let (sender, closed) = mpsc::channel();
+        let buffer = output.clone();
+        std::thread::spawn(move || {
+            let mut chunk = [0; 4096];
+            while let Ok(length @ 1..) = pipe.read(&mut chunk) {
+                buffer
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .extend_from_slice(&chunk[..length]);
+            }
+            let _ = sender.send(());
+        });
+        Self { output, closed }
+    }
+
+    // waits until the pipe is closed or the deadline passed
+    fn take(self, deadline: Instant) -> String {
+        let _ = self
+            .closed
+            .recv_timeout(deadline.saturating_duration_since(Instant::now()));
+        let output = self.output.lock().unwrap_or_else(|e| e.into_inner());
+        String::from_utf8_lossy(&output).into_owned()
+    }
+}
+
+fn run_command(command: &str, cwd: &Path, timeout: Duration) -> Result<(bool, String)> {
+    let mut command_line = if cfg!(windows) {
+        let mut command_line = Command::new("cmd");
+        command_line.arg("/C");
+        command_line
+    } else {
+        let mut command_line = Command::new("sh");
+        command_line.arg("-c");
+        #[cfg(unix)]
+        std::os::unix::process::CommandExt::process_group(&mut command_line, 0);
+        command_line
+    };
+    let mut child = command_line
+        .arg(command)
+        .current_dir(cwd)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| anyhow::anyhow!("Failed to run verify command `{}`: {}", command, e))?;
+
+    let stdout = BackgroundReader::new(child.stdout.take().unwrap());
+    let stderr = BackgroundReader::new(child.stderr.take().unwrap());
+
+    let start = Instant::now();
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break Some(status);
+        }
+        if start.elapsed() > timeout {
+            kill(&mut child);
+            let _ = child.wait();
+            break None;
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    };
+    let deadline = Instant::now() + OUTPUT_GRACE_PERIOD;
+    let mut output = stdout.take(deadline);
+    output.push_str(&stderr.take(deadline));
+    match status {
+        Some(status) => Ok((status.success(), output)),
+        None => Ok((
+            false,
+            format!(
+                "{}\nThe command timed out after {} seconds.",
+                output,
+                timeout.as_secs()
+            ),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_the_output_and_status() {
+        let (success, output) = run_command(
+            "echo out; echo err >&2; exit 3",
+            Path::new("."),
+            Duration::from_secs(10),
+        )
+        .unwrap();
+        assert!(!success);
+        assert_eq!(output, "out\nerr\n");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn timeout_kills_the_processes_of_the_command() {
+        let pid_file = std::env::temp_dir().join(format!("laluisa-verify-{}", std::process::id()));
+        let command = format!(
+            "sleep 60 & echo $! > {}; sleep 60",
+            shell_quote(&pid_file.to_string_lossy())
+        );
+        let start = Instant::now();
+        let (success, output) =
+            run_command(&command, Path::new("."), Duration::from_secs(1)).unwrap();
+        assert!(!success);
+        assert!(output.contains("timed out"), "{}", output);
+        assert!(start.elapsed() < Duration::from_secs(10));
+        // the background `sleep` is gone as well
+        let pid = std::fs::read_to_string(&pid_file).unwrap();
+        let _ = std::fs::remove_file(&pid_file);
+        // a killed process may stay a zombie until it is reaped
+        let state = Command::new("ps")
+            .args(["-o", "stat=", "-p", pid.trim()])
+            .output()
+            .unwrap()
+            .stdout;
+        let state = String::from_utf8_lossy(&state);
+        assert!(
+            state.trim().is_empty() || state.starts_with('Z'),
+            "{}",
+            state
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn background_processes_do_not_hold_the_result() {
+        let start = Instant::now();
+        let (success, output) = run_command(
+            "sleep 60 & echo done",
+            Path::new("."),
+            Duration::from_secs(30),
+        )
+        .unwrap();
+        assert!(success);
+        assert_eq!(output, "done\n");
+        assert!(start.elapsed() < Duration::from_secs(10));
+    }
+}